@@ -0,0 +1,505 @@
+use crate::split::SplitMode;
+use crate::{convert_dxf_file, ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use walkdir::WalkDir;
+
+/// How the input paths of a batch are expanded into the list of .dxf files
+#[derive(Debug, Default, Clone)]
+pub struct BatchOptions {
+    /// Descend into sub directories of the given directories
+    pub recursive: bool,
+    /// Glob patterns a file found in a directory must match, empty means all .dxf files
+    pub include: Vec<String>,
+    /// Glob patterns excluding files found in a directory
+    pub exclude: Vec<String>,
+    /// Number of worker threads, `None` uses one per logical cpu
+    pub jobs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Success,
+    Failed,
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        //pad instead of write! so the table can align the status column
+        f.pad(match self {
+            Self::Success => "OK",
+            Self::Failed => "FAILED",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub status: FileStatus,
+    pub message: String,
+    pub warnings: Vec<String>,
+    pub elapsed_ms: u128,
+    pub stats: Option<ConversionStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub warnings: usize,
+    pub elapsed_ms: u128,
+    pub files: Vec<FileReport>,
+}
+
+pub(crate) fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pat in patterns {
+        builder.add(Glob::new(pat).context(format!("Invalid glob pattern: {pat}"))?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+fn is_dxf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dxf"))
+}
+
+/// Expands the given files and directories into the sorted list of .dxf files to convert.
+///
+/// Files given explicitly are always kept, the include/exclude globs are only applied to
+/// files found while walking directories, matched against the path relative to that directory.
+///
+/// # Errors
+///
+/// Returns an error if a glob pattern is invalid or a directory can't be read.
+pub fn collect_inputs(paths: &[PathBuf], options: &BatchOptions) -> Result<Vec<PathBuf>> {
+    let include = build_globset(&options.include)?;
+    let exclude = build_globset(&options.exclude)?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let walker = WalkDir::new(path).max_depth(if options.recursive { usize::MAX } else { 1 });
+        for entry in walker {
+            let entry = entry.context(format!("Failed to read directory {}", path.display()))?;
+            if !entry.file_type().is_file() || !is_dxf(entry.path()) {
+                continue;
+            }
+
+            let rel = entry.path().strip_prefix(path).unwrap_or(entry.path());
+            if include.as_ref().is_some_and(|gs| !gs.is_match(rel)) {
+                continue;
+            }
+            if exclude.as_ref().is_some_and(|gs| gs.is_match(rel)) {
+                continue;
+            }
+            files.push(entry.into_path());
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Converts a single file, turning any error into a failed report instead of returning it
+#[must_use]
+pub fn convert_file(file: &Path, options: &ConversionOptions) -> FileReport {
+    let now = Instant::now();
+    let output = (!options.verbose).then(|| {
+        //split files write several elements, report the directory they went into
+        let out = options.output_path(file);
+        match (options.split, out.parent()) {
+            (SplitMode::None, _) | (_, None) => out,
+            (_, Some(dir)) => dir.to_path_buf(),
+        }
+    });
+    FileReport::new(file, output, now, convert_dxf_file(file, options))
+}
+
+/// Converts every file on a thread pool, a failing file doesn't stop the rest of the batch.
+///
+/// # Errors
+///
+/// Returns an error only if the thread pool can't be created.
+pub fn convert_batch(
+    files: &[PathBuf],
+    options: &ConversionOptions,
+    batch_options: &BatchOptions,
+) -> Result<BatchSummary> {
+    convert_batch_with(files, batch_options, |_| Cow::Borrowed(options))
+}
+
+/// Same as [`convert_batch`], with `options_for` giving the options of each file.
+///
+/// # Errors
+///
+/// Returns an error only if the thread pool can't be created.
+pub fn convert_batch_with<'a, F>(
+    files: &[PathBuf],
+    batch_options: &BatchOptions,
+    options_for: F,
+) -> Result<BatchSummary>
+where
+    F: Fn(&Path) -> Cow<'a, ConversionOptions> + Sync,
+{
+    run_batch(files, batch_options, |f| convert_file(f, &options_for(f)))
+}
+
+/// Runs `convert` for every file on a thread pool and sums up the reports.
+///
+/// # Errors
+///
+/// Returns an error only if the thread pool can't be created.
+pub fn run_batch<F>(files: &[PathBuf], batch_options: &BatchOptions, convert: F) -> Result<BatchSummary>
+where
+    F: Fn(&Path) -> FileReport + Sync,
+{
+    let now = Instant::now();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(batch_options.jobs.unwrap_or(0))
+        .build()
+        .context("Failed to create the conversion thread pool")?;
+
+    let reports: Vec<FileReport> =
+        pool.install(|| files.par_iter().map(|f| convert(f)).collect());
+
+    let succeeded = reports
+        .iter()
+        .filter(|r| r.status == FileStatus::Success)
+        .count();
+    Ok(BatchSummary {
+        total: reports.len(),
+        succeeded,
+        failed: reports.len() - succeeded,
+        warnings: reports.iter().map(|r| r.warnings.len()).sum(),
+        elapsed_ms: now.elapsed().as_millis(),
+        files: reports,
+    })
+}
+
+impl FileReport {
+    /// Report of the conversion of `file` that started at `started`, written to `output`
+    /// when it succeeded
+    #[must_use]
+    pub fn new(
+        file: &Path,
+        output: Option<PathBuf>,
+        started: Instant,
+        result: Result<ConversionResult>,
+    ) -> Self {
+        match result {
+            Ok(res) => {
+                let mut warnings = Vec::new();
+                if let Some(stats) = &res.stats {
+                    if stats.unsupported > 0 {
+                        warnings.push(format!(
+                            "{} unsupported entities were skipped",
+                            stats.unsupported
+                        ));
+                    }
+                }
+
+                FileReport {
+                    input: file.to_path_buf(),
+                    output,
+                    status: FileStatus::Success,
+                    message: res.message,
+                    warnings,
+                    elapsed_ms: started.elapsed().as_millis(),
+                    stats: res.stats,
+                }
+            }
+            Err(e) => FileReport {
+                input: file.to_path_buf(),
+                output: None,
+                status: FileStatus::Failed,
+                //use the alternate format to get the whole context chain, and flatten it to a
+                //single line so it fits in the table and the csv report
+                message: format!("{e:#}")
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                warnings: Vec::new(),
+                elapsed_ms: started.elapsed().as_millis(),
+                stats: None,
+            },
+        }
+    }
+
+    /// Writes the report as a row of the batch table, with the error and warnings below it
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing to `out`.
+    pub fn write_row(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{:<8} {:>10} {:>5}  {}",
+            self.status,
+            self.elapsed_ms,
+            self.warnings.len(),
+            self.input.display()
+        )?;
+        if self.status == FileStatus::Failed {
+            writeln!(out, "{:>27}{}", "", self.message)?;
+        }
+        for warn in &self.warnings {
+            writeln!(out, "{:>27}warning: {warn}", "")?;
+        }
+        Ok(())
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl BatchSummary {
+    #[must_use]
+    pub fn has_failures(&self) -> bool {
+        self.failed > 0
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the summary can't be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize the batch summary")
+    }
+
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("input,output,status,elapsed_ms,warnings,message\n");
+        for rep in &self.files {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                csv_field(&rep.input.to_string_lossy()),
+                csv_field(
+                    &rep.output
+                        .as_ref()
+                        .map(|o| o.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                ),
+                csv_field(&rep.status.to_string()),
+                rep.elapsed_ms,
+                csv_field(&rep.warnings.join("; ")),
+                csv_field(&rep.message),
+            );
+        }
+        csv
+    }
+
+    /// Writes a human readable table of every file followed by the totals
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing to `out`.
+    pub fn write_table(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{:<8} {:>10} {:>5}  FILE", "STATUS", "TIME (ms)", "WARN")?;
+        for rep in &self.files {
+            rep.write_row(out)?;
+        }
+        writeln!(
+            out,
+            "\nConverted {} of {} files ({} failed, {} warnings) in {} ms",
+            self.succeeded, self.total, self.failed, self.warnings, self.elapsed_ms
+        )
+    }
+
+    /// Writes the summary as JSON, or as CSV when `path` has a .csv extension
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the report file can't be written.
+    pub fn write_report(&self, path: &Path) -> Result<()> {
+        let content = if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        {
+            self.to_csv()
+        } else {
+            self.to_json()?
+        };
+        std::fs::write(path, content)
+            .context(format!("Failed to write report file: {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn names(files: &[PathBuf], root: &Path) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(root).unwrap_or(f).to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn collect_inputs_filters_directories_by_relative_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        std::fs::create_dir(root.join("sub"))?;
+        for file in ["a.dxf", "b.DXF", "notes.txt", "sub/c.dxf", "sub/skip.dxf"] {
+            std::fs::write(root.join(file), "")?;
+        }
+        let collect = |options: &BatchOptions| -> Result<Vec<String>> {
+            Ok(names(&collect_inputs(&[root.to_path_buf()], options)?, root))
+        };
+
+        assert_eq!(collect(&BatchOptions::default())?, ["a.dxf", "b.DXF"]);
+        let recursive = BatchOptions {
+            recursive: true,
+            ..BatchOptions::default()
+        };
+        assert_eq!(collect(&recursive)?, ["a.dxf", "b.DXF", "sub/c.dxf", "sub/skip.dxf"]);
+        //the globs see the path below the directory, not the absolute one
+        let filtered = BatchOptions {
+            include: vec!["sub/*.dxf".into()],
+            exclude: vec!["**/skip.dxf".into()],
+            ..recursive
+        };
+        assert_eq!(collect(&filtered)?, ["sub/c.dxf"]);
+
+        //files given explicitly are kept whatever the globs say
+        let explicit = collect_inputs(&[root.join("sub/skip.dxf")], &filtered)?;
+        assert_eq!(names(&explicit, root), ["sub/skip.dxf"]);
+        assert!(collect_inputs(&[root.to_path_buf()], &BatchOptions {
+            include: vec!["[".into()],
+            ..BatchOptions::default()
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    fn summary() -> Result<BatchSummary> {
+        let files = [PathBuf::from("ok.dxf"), PathBuf::from("bad.dxf")];
+        let options = BatchOptions {
+            jobs: Some(2),
+            ..BatchOptions::default()
+        };
+        run_batch(&files, &options, |file| {
+            let result = if file == Path::new("ok.dxf") {
+                Ok(ConversionResult {
+                    success: true,
+                    message: "Successfully converted ok".into(),
+                    stats: Some(ConversionStats {
+                        unsupported: 2,
+                        ..ConversionStats::default()
+                    }),
+                    xml_content: None,
+                })
+            } else {
+                Err(anyhow!("Failed to read \"bad.dxf\",\n not a drawing"))
+            };
+            FileReport::new(file, Some(file.with_extension("elmt")), Instant::now(), result)
+        })
+    }
+
+    #[test]
+    fn run_batch_counts_failures_and_warnings() -> Result<()> {
+        let summary = summary()?;
+        assert_eq!(
+            (summary.total, summary.succeeded, summary.failed, summary.warnings),
+            (2, 1, 1, 1)
+        );
+        assert!(summary.has_failures());
+        //the files keep the order they were given in
+        assert_eq!(summary.files[0].input, Path::new("ok.dxf"));
+        let failed = &summary.files[1];
+        assert_eq!(failed.status, FileStatus::Failed);
+        assert_eq!(failed.output, None);
+        assert_eq!(failed.message, "Failed to read \"bad.dxf\", not a drawing");
+        Ok(())
+    }
+
+    #[test]
+    fn summary_reports_as_json_and_csv() -> Result<()> {
+        let summary = summary()?;
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json()?)?;
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["files"][0]["status"], "success");
+        assert_eq!(json["files"][0]["warnings"][0], "2 unsupported entities were skipped");
+        assert_eq!(json["files"][1]["status"], "failed");
+
+        let csv = summary.to_csv();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "input,output,status,elapsed_ms,warnings,message");
+        assert!(rows[1].starts_with("ok.dxf,ok.elmt,OK,"));
+        assert!(rows[2].starts_with("bad.dxf,,FAILED,"));
+        assert!(rows[2].ends_with(",,\"Failed to read \"\"bad.dxf\"\", not a drawing\""));
+        Ok(())
+    }
+
+    #[test]
+    fn split_drawings_sharing_a_block_name_keep_both_elements() -> Result<()> {
+        use dxf::entities::{Entity, EntityType, Line};
+        use dxf::{Block, Drawing, Point};
+
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for (drawing, length) in [("relays", 10.0), ("sensors", 20.0)] {
+            let mut drw = Drawing::new();
+            drw.add_block(Block {
+                name: "COIL".to_string(),
+                entities: vec![Entity::new(EntityType::Line(Line::new(
+                    Point::new(0.0, 0.0, 0.0),
+                    Point::new(length, 0.0, 0.0),
+                )))],
+                ..Block::default()
+            });
+            let file = dir.path().join(format!("{drawing}.dxf"));
+            drw.save_file(&file)?;
+            files.push(file);
+        }
+
+        let options = ConversionOptions {
+            split: SplitMode::Blocks,
+            ..ConversionOptions::default()
+        };
+        let batch_options = BatchOptions {
+            jobs: Some(2),
+            ..BatchOptions::default()
+        };
+        let summary = convert_batch(&files, &options, &batch_options)?;
+        assert_eq!(summary.succeeded, 2);
+        assert!(dir.path().join("relays-COIL.elmt").is_file());
+        assert!(dir.path().join("sensors-COIL.elmt").is_file());
+        Ok(())
+    }
+}
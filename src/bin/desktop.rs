@@ -1,10 +1,328 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 use dioxus::prelude::*;
+use dxf2elmt::batch::{collect_inputs, convert_file, run_batch, BatchOptions, FileReport, FileStatus};
+use dxf2elmt::config::{Config, DesktopSettings, Preset};
+use dxf2elmt::diagnostics::{diagnose, Bounds, Diagnostic};
+use dxf2elmt::inspect::{BlockInfo, DrawingReport, LayerInfo};
+use dxf2elmt::progress::{CancelToken, Cancelled, Phase, Progress, ProgressHooks};
+use dxf2elmt::metadata::{ElementMetadata, TerminalPlacement, ELEMENT_INFO_NAMES};
+use dxf2elmt::qelmt::{Definition, EntityRules, GridAnchor, GridSnap, LinkType, Placement, ScaleMode, TermOrient, TextMode, GRID_STEP};
+use dxf2elmt::svg::{drawing_svg, element_svg, element_view_box, highlight_svg, overlay_svg, SvgOptions};
+use dxf2elmt::validate::Severity;
+use dxf2elmt::{ConversionOptions, ConversionResult};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 
 fn main() {
     launch(App);
 }
 
+// Opciones de conversión: primero el preset de la configuración y encima lo elegido en la ventana
+#[allow(clippy::too_many_arguments)]
+fn conversion_options(preset: &Preset, spline_step: u32, px_per_mm: f64, scale_mode: ScaleMode, grid: (Option<GridAnchor>, &[GridSnap]), text_mode: TextMode, verbose: bool, info: bool, unchecked: Option<(&HashSet<String>, &HashSet<String>)>) -> ConversionOptions {
+    let mut opts = ConversionOptions::default();
+    preset.apply(&mut opts);
+    opts.spline_step = spline_step;
+    opts.px_per_mm = px_per_mm;
+    opts.scale_mode = scale_mode;
+    (opts.grid_anchor, opts.grid_snap) = (grid.0, grid.1.to_vec());
+    opts.text_mode = text_mode;
+    opts.verbose = verbose;
+    opts.info = info;
+    // Con las capas y bloques del DXF cargados mandan las casillas, que ya parten de los patrones del preset
+    if let Some((unchecked_layers, unchecked_blocks)) = unchecked {
+        opts.include_layers.clear();
+        opts.skip_hidden_layers = false;
+        opts.exclude_layers = unchecked_layers.iter().map(|name| globset::escape(name)).collect();
+        opts.exclude_blocks = unchecked_blocks.iter().map(|name| globset::escape(name)).collect();
+    }
+    opts
+}
+
+// Capas desmarcadas por defecto: las ocultas y las que filtran los patrones del preset
+fn default_unchecked_layers(layers: &[LayerInfo], preset: &Preset) -> HashSet<String> {
+    let mut opts = ConversionOptions::default();
+    preset.apply(&mut opts);
+    // Sin capas en el dibujo las reglas solo comprueban los patrones
+    // Un patrón inválido no desmarca nada, la conversión ya avisa del error
+    let rules = EntityRules::new(&opts, &dxf::Drawing::new()).unwrap_or_default();
+    layers
+        .iter()
+        .filter(|l| (opts.skip_hidden_layers && (!l.is_on || !l.is_plotted)) || !rules.layer_allowed(&l.name))
+        .map(|l| l.name.clone())
+        .collect()
+}
+
+// Bloques desmarcados por defecto: los que excluyen los patrones del preset
+fn default_unchecked_blocks(blocks: &[BlockInfo], preset: &Preset) -> HashSet<String> {
+    let mut opts = ConversionOptions::default();
+    preset.apply(&mut opts);
+    let rules = EntityRules::new(&opts, &dxf::Drawing::new()).unwrap_or_default();
+    blocks
+        .iter()
+        .filter(|b| !rules.block_allowed(&b.name))
+        .map(|b| b.name.clone())
+        .collect()
+}
+
+// Color CSS de los índices ACI básicos, el resto solo se muestra con su número
+fn aci_css(index: u8) -> Option<&'static str> {
+    Some(match index {
+        1 => "#ff0000",
+        2 => "#ffff00",
+        3 => "#00ff00",
+        4 => "#00ffff",
+        5 => "#0000ff",
+        6 => "#ff00ff",
+        7 => "#000000",
+        8 => "#808080",
+        9 => "#c0c0c0",
+        _ => return None,
+    })
+}
+
+// Estado de un archivo de la cola de conversión
+#[derive(Clone, Copy, PartialEq)]
+enum QueueState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl QueueState {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "Pendiente",
+            Self::Running => "Convirtiendo...",
+            Self::Done => "OK",
+            Self::Failed => "Error",
+            Self::Cancelled => "Cancelado",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct QueueEntry {
+    path: PathBuf,
+    state: QueueState,
+    // Corta la conversión del archivo, o hace que ni empiece si aún no le ha llegado el turno
+    cancel: CancelToken,
+    report: Option<FileReport>,
+}
+
+// Lo que el hilo de la cola cuenta a la interfaz de cada archivo
+enum QueueEvent {
+    Started(PathBuf),
+    Finished(FileReport),
+}
+
+// DXF de las rutas elegidas o arrastradas, las carpetas se recorren con sus subcarpetas
+fn dxf_inputs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let batch_options = BatchOptions { recursive: true, ..BatchOptions::default() };
+    let files = collect_inputs(paths, &batch_options).map_err(|e| format!("{e:#}"))?;
+    Ok(files
+        .into_iter()
+        .filter(|f| f.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dxf")))
+        .collect())
+}
+
+// Añade a la cola los archivos que aún no estén en ella
+fn enqueue(queue: &mut Vec<QueueEntry>, files: Vec<PathBuf>) {
+    for path in files {
+        if queue.iter().any(|e| e.path == path) {
+            continue;
+        }
+        queue.push(QueueEntry {
+            path,
+            state: QueueState::Pending,
+            cancel: CancelToken::new(),
+            report: None,
+        });
+    }
+}
+
+// Guarda los ajustes de la ventana en la configuración del usuario, la misma que lee la CLI.
+// Con un preset elegido se recuerda el preset, sin él los valores van a la base
+fn save_settings(preset_name: Option<String>, values: &Preset, desktop: DesktopSettings) -> anyhow::Result<()> {
+    // Nada inválido llega al archivo, que luego no se podría cargar
+    values.validate()?;
+    let mut config = Config::load_user()?;
+    let saved = config.clone();
+    if preset_name.is_none() {
+        config.base.merge(values);
+    }
+    config.default_preset = preset_name;
+    config.desktop = desktop;
+    if config != saved {
+        config.save_user()?;
+    }
+    Ok(())
+}
+
+// Formulario de metadatos, con los pares en listas para poder editar también idiomas y nombres
+#[derive(Clone, Default, PartialEq)]
+struct MetadataForm {
+    names: Vec<(String, String)>,
+    informations: String,
+    link_type: Option<LinkType>,
+    infos: Vec<(String, String)>,
+    // Bornes colocados a mano, None deja los que detecta la conversión
+    terminals: Option<Vec<TerminalPlacement>>,
+}
+
+impl From<ElementMetadata> for MetadataForm {
+    fn from(metadata: ElementMetadata) -> Self {
+        Self {
+            names: metadata.names.into_iter().collect(),
+            informations: metadata.informations.unwrap_or_default(),
+            link_type: metadata.link_type,
+            infos: metadata.element_infos.into_iter().collect(),
+            terminals: metadata.terminals,
+        }
+    }
+}
+
+impl MetadataForm {
+    // Las filas sin idioma o sin nombre no cuentan
+    fn to_metadata(&self) -> ElementMetadata {
+        let pairs = |rows: &[(String, String)]| {
+            rows.iter()
+                .map(|(k, v)| (k.trim().to_string(), v.clone()))
+                .filter(|(k, _)| !k.is_empty())
+                .collect()
+        };
+        ElementMetadata {
+            names: pairs(&self.names),
+            informations: Some(self.informations.clone()).filter(|i| !i.is_empty()),
+            link_type: self.link_type,
+            element_infos: pairs(&self.infos),
+            terminals: self.terminals.clone(),
+        }
+    }
+
+    // Bornes a editar, la primera vez se parte de los que ha detectado la conversión
+    fn placed_terminals(&mut self, detected: &[TerminalPlacement]) -> &mut Vec<TerminalPlacement> {
+        self.terminals.get_or_insert_with(|| detected.to_vec())
+    }
+}
+
+// Metadatos con los que se abre el formulario: los del archivo auxiliar o, si no hay, los del .elmt ya convertido
+fn initial_metadata(path: &Path, preset: &Preset) -> Result<ElementMetadata, String> {
+    if let Some(metadata) = ElementMetadata::load(path).map_err(|e| format!("{e:#}"))? {
+        return Ok(metadata);
+    }
+    let mut opts = ConversionOptions::default();
+    preset.apply(&mut opts);
+    Ok(Definition::read_file(&opts.output_path(path)).map(|d| d.metadata()).unwrap_or_default())
+}
+
+// Vista previa calculada: los dos SVG, el viewBox del elemento y sus bornes para poder editarlos
+#[derive(Clone, PartialEq)]
+struct PreviewData {
+    source_svg: String,
+    elmt_svg: String,
+    view_box: (f64, f64, f64, f64),
+    // Bornes del elemento en píxeles, para dibujarlos y editarlos
+    terminals: Vec<TerminalPlacement>,
+    // Los mismos bornes en coordenadas del dibujo, que es como se guardan en los metadatos
+    detected: Vec<TerminalPlacement>,
+    placement: Placement,
+    // Lo que ha movido la alineación a la rejilla, para mostrarlo junto a sus opciones
+    grid_displacement: f64,
+}
+
+// Elemento de la vista previa con los bornes colocados, None si la conversión falla
+fn build_preview(
+    drawing: &dxf::Drawing,
+    opts: &ConversionOptions,
+    placed: &ElementMetadata,
+    overlay: bool,
+) -> Option<PreviewData> {
+    let mut definition = Definition::new("preview", opts, drawing).ok()?;
+    definition.apply_metadata(placed);
+    let svg_options = SvgOptions::default();
+    let element = if overlay {
+        overlay_svg(&definition, drawing, opts, &svg_options).ok()?
+    } else {
+        element_svg(&definition, &svg_options)
+    };
+    Some(PreviewData {
+        source_svg: drawing_svg(drawing, opts).ok()?.to_string(),
+        elmt_svg: element.to_string(),
+        view_box: element_view_box(&definition),
+        terminals: definition.terminals().map(|t| TerminalPlacement { x: t.x, y: t.y, orientation: t.orientation, name: t.name.clone(), uuid: t.uuid }).collect(),
+        detected: definition.terminals().map(|t| TerminalPlacement::from_terminal(t, definition.placement())).collect(),
+        placement: definition.placement(),
+        grid_displacement: definition.grid_displacement(),
+    })
+}
+
+// Punto del elemento bajo el ratón. Deshace el zoom y desplazamiento del panel (origen en el
+// centro) y el ajuste del SVG a su caja, que lo centra manteniendo la proporción.
+fn element_point(
+    client: (f64, f64),
+    pane: (f64, f64, f64, f64),
+    pan: (f64, f64),
+    zoom: f64,
+    view_box: (f64, f64, f64, f64),
+) -> (f64, f64) {
+    let (left, top, width, height) = pane;
+    let (vx, vy, vw, vh) = view_box;
+    let qx = width / 2.0 + (client.0 - left - width / 2.0 - pan.0) / zoom;
+    let qy = height / 2.0 + (client.1 - top - height / 2.0 - pan.1) / zoom;
+    let s = (width / vw).min(height / vh);
+    let (ox, oy) = ((width - vw * s) / 2.0, (height - vh * s) / 2.0);
+    (vx + (qx - ox) / s, vy + (qy - oy) / s)
+}
+
+fn snap(v: f64) -> f64 {
+    (v / GRID_STEP).round() * GRID_STEP
+}
+
+// Pestañas con el resultado de la última conversión
+#[derive(Clone, Copy, PartialEq)]
+enum OutputTab {
+    Xml,
+    Log,
+}
+
+// XML del elemento convertido (en memoria si es verbose, si no el .elmt escrito) y su registro
+fn conversion_output(conv: &ConversionResult, out_path: &Path) -> Option<(String, Vec<Diagnostic>)> {
+    let xml = conv.xml_content.clone().or_else(|| std::fs::read_to_string(out_path).ok())?;
+    let log = Definition::from_xml(&xml)
+        .and_then(|definition| diagnose(&definition, conv.stats.as_ref()))
+        .unwrap_or_else(|e| vec![Diagnostic::new(Severity::Error, format!("{e:#}"))]);
+    Some((xml, log))
+}
+
+fn severity_label(severity: Severity) -> (&'static str, &'static str) {
+    match severity {
+        Severity::Error => ("Errores", "#b91c1c"),
+        Severity::Warning => ("Avisos", "#b45309"),
+        Severity::Info => ("Información", "#374151"),
+    }
+}
+
+// Texto y fracción hecha de la barra de progreso, construir los objetos es casi todo el trabajo
+#[allow(clippy::cast_precision_loss)]
+fn progress_status(progress: Progress) -> (f64, String) {
+    match progress {
+        Progress::Phase(Phase::Loading) => (0.0, "Cargando DXF...".to_string()),
+        Progress::Phase(Phase::Building) => (0.1, "Construyendo objetos...".to_string()),
+        Progress::Entities { done, total } => (
+            0.1 + 0.7 * done as f64 / total.max(1) as f64,
+            format!("Construyendo objetos ({done}/{total})..."),
+        ),
+        Progress::Phase(Phase::Scaling) => (0.8, "Escalando...".to_string()),
+        Progress::Phase(Phase::Serializing) => (0.85, "Generando XML...".to_string()),
+        Progress::Phase(Phase::Writing) => (0.95, "Escribiendo archivo...".to_string()),
+    }
+}
+
 #[component]
 fn App() -> Element {
     // Configurar el título de la ventana
@@ -15,20 +333,262 @@ fn App() -> Element {
         window.set_title("DXF to ELMT Converter");
     }
     
+    // Misma configuración que la CLI: config.toml del usuario y dxf2elmt.toml del proyecto
+    let (config, config_error) = use_hook(|| match Config::load(None) {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(format!("Error en la configuración: {e:#}"))),
+    });
+    let initial_preset = config.resolve(None).unwrap_or_default();
+    let defaults = ConversionOptions::default();
+    // La relación tal como se escribió, si aún da el px-per-mm que resulta de la configuración
+    let initial_px_per_mm = initial_preset.px_per_mm.unwrap_or(defaults.px_per_mm);
+    let initial_ratio = match (config.desktop.ratio_px, config.desktop.ratio_mm) {
+        (Some(px), Some(mm)) if mm > 0.0 && (px / mm - initial_px_per_mm).abs() < 1e-9 => (px, mm),
+        _ => (initial_px_per_mm, 1.0),
+    };
+
     let mut selected_path = use_signal(|| Option::<String>::None);
-    let mut last_output_dir = use_signal(|| Option::<String>::None);
-    let mut spline_step = use_signal(|| 20u32);
-    let mut px_per_mm_px = use_signal(|| 2.0f64);
-    let mut px_per_mm_mm = use_signal(|| 1.0f64);
-    let mut verbose = use_signal(|| false);
-    let mut info_flag = use_signal(|| false);
+    let mut last_output_dir = use_signal(|| config.desktop.last_output_dir.as_ref().map(|d| d.display().to_string()));
+    // Última carpeta de entrada y archivos recientes, se guardan entre sesiones
+    let mut desktop_state = use_signal(|| config.desktop.clone());
+    let mut preset_name = use_signal(|| config.default_preset.clone());
+    let mut preset = use_signal(|| initial_preset.clone());
+    let mut spline_step = use_signal(|| initial_preset.spline_step.unwrap_or(defaults.spline_step));
+    let mut px_per_mm_px = use_signal(|| initial_ratio.0);
+    let mut px_per_mm_mm = use_signal(|| initial_ratio.1);
+    let mut scale_mode = use_signal(|| initial_preset.scale_mode.unwrap_or_default());
+    // Alineación a la rejilla: punto que se lleva a ella y primitivas que se redondean
+    let mut grid_anchor = use_signal(|| initial_preset.grid_anchor);
+    let mut grid_snap = use_signal(|| initial_preset.grid_snap.clone().unwrap_or_default());
+    let mut text_mode = use_signal(|| initial_preset.text_mode.unwrap_or_default());
+    let mut verbose = use_signal(|| config.desktop.verbose.unwrap_or(false));
+    let mut info_flag = use_signal(|| initial_preset.info.unwrap_or(false));
     let mut is_processing = use_signal(|| false);
-    let mut status = use_signal(|| String::new());
+    // Progreso de la conversión en curso y el token para cancelarla
+    let mut conversion_progress = use_signal(|| Option::<(f64, String)>::None);
+    let mut conversion_cancel = use_signal(|| Option::<CancelToken>::None);
+    let mut status = use_signal(|| config_error.clone().unwrap_or_default());
     let mut preview_stats = use_signal(|| Option::<dxf2elmt::ConversionStats>::None);
+    // Capas del DXF seleccionado y las que el usuario ha desmarcado
+    let mut layers = use_signal(Vec::<LayerInfo>::new);
+    let mut unchecked_layers = use_signal(HashSet::<String>::new);
+    // Bloques del DXF con los desmarcados, y los tipos de entidad que no se convierten
+    let mut blocks = use_signal(Vec::<BlockInfo>::new);
+    let mut unchecked_blocks = use_signal(HashSet::<String>::new);
+    let mut unsupported_types = use_signal(BTreeMap::<String, usize>::new);
+    // Metadatos del elemento y si hay cambios sin guardar en el archivo auxiliar
+    let mut meta_form = use_signal(MetadataForm::default);
+    let mut meta_dirty = use_signal(|| false);
+    // Bandera de parada del hilo que vigila el DXF, None si no se está vigilando
+    let mut watching = use_signal(|| Option::<Arc<AtomicBool>>::None);
+    let mut watch_log = use_signal(Vec::<String>::new);
+    // Opciones con las que el hilo vigilante convierte, al día con los ajustes de la ventana
+    let watch_options = use_hook(|| Arc::new(RwLock::new(ConversionOptions::default())));
+    // DXF cargado para la vista previa y estado del visor (zoom, desplazamiento y arrastre)
+    let mut source_drawing = use_signal(|| Option::<Arc<dxf::Drawing>>::None);
+    let mut overlay_preview = use_signal(|| false);
+    let mut preview_zoom = use_signal(|| 1.0f64);
+    let mut preview_pan = use_signal(|| (0.0f64, 0.0f64));
+    let mut drag_from = use_signal(|| Option::<(f64, f64)>::None);
+    // Edición de bornes: activada, borne seleccionado, si el ratón se ha arrastrado desde que se
+    // pulsó (entonces no es un clic) y el panel ELMT para saber dónde cae el clic
+    let mut edit_terminals = use_signal(|| false);
+    let mut selected_terminal = use_signal(|| Option::<usize>::None);
+    let mut dragged = use_signal(|| false);
+    let mut elmt_pane = use_signal(|| Option::<std::rc::Rc<MountedData>>::None);
+    // Solo los bornes, para no reconvertir la vista previa al escribir nombres
+    let placed_terminals = use_memo(move || meta_form.read().terminals.clone());
+    // XML y registro de la última conversión, con la pestaña visible, los niveles mostrados y
+    // el objeto resaltado en la vista previa
+    let mut conversion_xml = use_signal(|| Option::<String>::None);
+    let mut conversion_log = use_signal(Vec::<Diagnostic>::new);
+    let mut output_tab = use_signal(|| OutputTab::Log);
+    let mut shown_severities = use_signal(|| BTreeSet::from([Severity::Error, Severity::Warning, Severity::Info]));
+    let mut highlight = use_signal(|| Option::<Bounds>::None);
+    // Cola de conversión por lotes y si su hilo está trabajando
+    let mut queue = use_signal(Vec::<QueueEntry>::new);
+    let mut queue_running = use_signal(|| false);
+    let mut queue_status = use_signal(String::new);
+
+    // Vista previa: DXF original y elemento resultante, se recalcula al cambiar el archivo o las opciones
+    let preview = use_resource(move || {
+        let drawing = source_drawing();
+        let opts = conversion_options(
+            &preset.read(),
+            spline_step(),
+            px_per_mm_px() / px_per_mm_mm(),
+            scale_mode(),
+            (grid_anchor(), &grid_snap.read()),
+            text_mode(),
+            false,
+            false,
+            Some((&unchecked_layers(), &unchecked_blocks())).filter(|_| !layers.read().is_empty()),
+        );
+        let overlay = overlay_preview();
+        let placed = ElementMetadata { terminals: placed_terminals(), ..ElementMetadata::default() };
+        async move {
+            let drawing = drawing?;
+            // El hilo avisa al terminar, esperar con join bloquearía la interfaz
+            let (done_tx, done_rx) = futures_channel::oneshot::channel();
+            std::thread::spawn(move || {
+                let _ = done_tx.send(build_preview(&drawing, &opts, &placed, overlay));
+            });
+            done_rx.await.ok().flatten()
+        }
+    });
+
+    // Cada conversión del vigilante usa los ajustes del momento, no los de cuando empezó
+    let current_watch_options = watch_options.clone();
+    use_effect(move || {
+        let opts = conversion_options(
+            &preset.read(),
+            spline_step(),
+            px_per_mm_px() / px_per_mm_mm(),
+            scale_mode(),
+            (grid_anchor(), &grid_snap.read()),
+            text_mode(),
+            false,
+            info_flag(),
+            Some((&unchecked_layers(), &unchecked_blocks())).filter(|_| !layers.read().is_empty()),
+        );
+        if let Ok(mut current) = current_watch_options.write() {
+            *current = opts;
+        }
+    });
+
+    // Guarda los ajustes al cambiarlos, sin escribir nada solo por abrir la ventana
+    let first_run = use_hook(|| std::rc::Rc::new(std::cell::Cell::new(true)));
+    use_effect(move || {
+        let name = preset_name();
+        let values = Preset {
+            px_per_mm: Some(px_per_mm_px() / px_per_mm_mm()),
+            scale_mode: Some(scale_mode()),
+            grid_anchor: grid_anchor(),
+            grid_snap: Some(grid_snap()),
+            spline_step: Some(spline_step()),
+            text_mode: Some(text_mode()),
+            info: Some(info_flag()),
+            ..Preset::default()
+        };
+        let desktop = DesktopSettings {
+            verbose: Some(verbose()),
+            ratio_px: Some(px_per_mm_px()),
+            ratio_mm: Some(px_per_mm_mm()),
+            last_output_dir: last_output_dir().map(PathBuf::from),
+            ..desktop_state()
+        };
+        if first_run.replace(false) {
+            return;
+        }
+        if let Err(e) = save_settings(name, &values, desktop) {
+            status.set(format!("Error guardando la configuración: {e:#}"));
+        }
+    });
+
+    // Abre un DXF: resumen de entidades, capas y vista previa
+    let mut open_dxf = move |path: PathBuf| {
+        // Al cambiar de archivo dejamos de vigilar el anterior
+        if let Some(stop) = watching() {
+            stop.store(true, Ordering::Relaxed);
+            watching.set(None);
+        }
+        watch_log.set(Vec::new());
+        selected_path.set(Some(path.display().to_string()));
+        status.set(String::new());
+        preview_stats.set(None);
+        layers.set(Vec::new());
+        unchecked_layers.set(HashSet::new());
+        blocks.set(Vec::new());
+        unchecked_blocks.set(HashSet::new());
+        unsupported_types.set(BTreeMap::new());
+        meta_dirty.set(false);
+        selected_terminal.set(None);
+        conversion_xml.set(None);
+        conversion_log.set(Vec::new());
+        highlight.set(None);
+        match initial_metadata(&path, &preset.read()) {
+            Ok(metadata) => meta_form.set(metadata.into()),
+            Err(e) => {
+                meta_form.set(MetadataForm::default());
+                status.set(format!("Error leyendo metadatos: {e}"));
+            }
+        }
+        source_drawing.set(None);
+        preview_zoom.set(1.0);
+        preview_pan.set((0.0, 0.0));
+        // Cargar y mostrar resumen de entidades (previo a convertir)
+        let path_for_preview = path.clone();
+        dioxus::core::spawn(async move {
+            use dxf::entities::EntityType;
+            let res = std::thread::spawn(move || {
+                let drawing = dxf2elmt::load_drawing(&path_for_preview).map_err(|e| format!("{e:#}"))?;
+                let mut circles = 0u32;
+                let mut lines = 0u32;
+                let mut arcs = 0u32;
+                let mut splines = 0u32;
+                let mut texts = 0u32;
+                let mut ellipses = 0u32;
+                let mut polylines = 0u32;
+                let mut lwpolylines = 0u32;
+                let mut solids = 0u32;
+                let mut blocks = 0u32;
+                let mut unsupported = 0u32;
+                drawing.entities().for_each(|e| match e.specific {
+                    EntityType::Circle(_) => circles += 1,
+                    EntityType::Line(_) => lines += 1,
+                    EntityType::Arc(_) => arcs += 1,
+                    EntityType::Spline(_) => splines += 1,
+                    EntityType::Text(_) => texts += 1,
+                    EntityType::Ellipse(_) => ellipses += 1,
+                    EntityType::Polyline(_) => polylines += 1,
+                    EntityType::LwPolyline(_) => lwpolylines += 1,
+                    EntityType::Solid(_) => solids += 1,
+                    EntityType::Insert(_) => blocks += 1,
+                    _ => unsupported += 1,
+                });
+                let stats = dxf2elmt::ConversionStats {
+                    circles, lines, arcs, splines, texts, ellipses,
+                    polylines, lwpolylines, solids, blocks, unsupported,
+                    elapsed_ms: 0,
+                    grid_displacement: 0.0,
+                };
+                let report = DrawingReport::new(&drawing);
+                Ok::<_, String>((stats, report, drawing))
+            }).join();
+            match res {
+                Ok(Ok((stats, report, drawing))) => {
+                    preview_stats.set(Some(stats));
+                    source_drawing.set(Some(Arc::new(drawing)));
+                    unchecked_layers.set(default_unchecked_layers(&report.layers, &preset.read()));
+                    unchecked_blocks.set(default_unchecked_blocks(&report.blocks, &preset.read()));
+                    layers.set(report.layers);
+                    blocks.set(report.blocks);
+                    unsupported_types.set(report.unsupported);
+                }
+                Ok(Err(e)) => status.set(format!("Error leyendo DXF: {e}")),
+                Err(_) => status.set("Error: fallo interno leyendo DXF".to_string()),
+            }
+        });
+        desktop_state.with_mut(|d| {
+            d.last_input_dir = path.parent().map(Path::to_path_buf);
+            d.add_recent(&path);
+        });
+    };
 
     rsx! {
+        style { ".preview svg {{ width: 100%; height: 100%; }}" }
         div {
             style: "max-width: 850px; min-height: 100vh; margin: 0 auto; padding: 20px; font-family: system-ui, -apple-system, sans-serif; box-sizing: border-box;",
+            // Los archivos y carpetas arrastrados a la ventana van a la cola
+            ondragover: move |e: DragEvent| e.prevent_default(),
+            ondrop: move |e: DragEvent| {
+                e.prevent_default();
+                let paths: Vec<PathBuf> = e.files().iter().map(|f| f.path()).collect();
+                match dxf_inputs(&paths) {
+                    Ok(files) => queue.with_mut(|q| enqueue(q, files)),
+                    Err(e) => queue_status.set(format!("Error: {e}")),
+                }
+            },
             h1 { style: "color: #2563eb; margin-bottom: 10px;", "DXF to ELMT Converter" }
 
             div {
@@ -37,61 +597,33 @@ fn App() -> Element {
                     style: "background: #2563eb; color: white; border: none; padding: 10px 16px; border-radius: 6px; cursor: pointer; width: fit-content;",
                     onclick: move |_| {
                         if is_processing() { return; }
-                        let file = rfd::FileDialog::new()
+                        let mut dialog = rfd::FileDialog::new()
                             .add_filter("DXF", &["dxf"])
-                            .set_title("Selecciona un archivo DXF")
-                            .pick_file();
-                        if let Some(path) = file {
-                            selected_path.set(Some(path.display().to_string()));
-                            status.set(String::new());
-                            preview_stats.set(None);
-                            // Cargar y mostrar resumen de entidades (previo a convertir)
-                            let path_for_preview = path.clone();
-                            dioxus::core::spawn(async move {
-                                use dxf::Drawing;
-                                use dxf::entities::EntityType;
-                                let res = std::thread::spawn(move || {
-                                    let drawing = Drawing::load_file(&path_for_preview).map_err(|e| e.to_string())?;
-                                    let mut circles = 0u32;
-                                    let mut lines = 0u32;
-                                    let mut arcs = 0u32;
-                                    let mut splines = 0u32;
-                                    let mut texts = 0u32;
-                                    let mut ellipses = 0u32;
-                                    let mut polylines = 0u32;
-                                    let mut lwpolylines = 0u32;
-                                    let mut solids = 0u32;
-                                    let mut blocks = 0u32;
-                                    let mut unsupported = 0u32;
-                                    drawing.entities().for_each(|e| match e.specific {
-                                        EntityType::Circle(_) => circles += 1,
-                                        EntityType::Line(_) => lines += 1,
-                                        EntityType::Arc(_) => arcs += 1,
-                                        EntityType::Spline(_) => splines += 1,
-                                        EntityType::Text(_) => texts += 1,
-                                        EntityType::Ellipse(_) => ellipses += 1,
-                                        EntityType::Polyline(_) => polylines += 1,
-                                        EntityType::LwPolyline(_) => lwpolylines += 1,
-                                        EntityType::Solid(_) => solids += 1,
-                                        EntityType::Insert(_) => blocks += 1,
-                                        _ => unsupported += 1,
-                                    });
-                                    Ok::<_, String>(dxf2elmt::ConversionStats {
-                                        circles, lines, arcs, splines, texts, ellipses,
-                                        polylines, lwpolylines, solids, blocks, unsupported,
-                                        elapsed_ms: 0,
-                                    })
-                                }).join();
-                                match res {
-                                    Ok(Ok(stats)) => preview_stats.set(Some(stats)),
-                                    Ok(Err(e)) => status.set(format!("Error leyendo DXF: {e}")),
-                                    Err(_) => status.set("Error: fallo interno leyendo DXF".to_string()),
-                                }
-                            });
+                            .set_title("Selecciona un archivo DXF");
+                        if let Some(dir) = desktop_state.read().last_input_dir.clone() {
+                            dialog = dialog.set_directory(dir);
+                        }
+                        if let Some(path) = dialog.pick_file() {
+                            open_dxf(path);
                         }
                     },
                     "Seleccionar DXF..."
                 }
+                if !desktop_state.read().recent_files.is_empty() {
+                    select {
+                        style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px; width: fit-content; max-width: 100%;",
+                        disabled: is_processing(),
+                        onchange: move |e: Event<FormData>| {
+                            if !e.value().is_empty() {
+                                open_dxf(PathBuf::from(e.value()));
+                            }
+                        },
+                        option { value: "", selected: true, "Archivos recientes..." }
+                        for file in desktop_state.read().recent_files.clone() {
+                            option { value: "{file.display()}", "{file.display()}" }
+                        }
+                    }
+                }
                 if let Some(path) = selected_path() {
                     div { "Seleccionado: {path}" }
                 }
@@ -114,9 +646,575 @@ fn App() -> Element {
                         }
                     }
                 }
+                if !layers().is_empty() {
+                    div {
+                        style: "background: #f9fafb; border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px; display: flex; flex-direction: column; gap: 8px;",
+                        h3 { style: "margin: 0; color: #1e3a8a;", "Capas a convertir" }
+                        table {
+                            style: "width: 100%; border-collapse: collapse; font-size: 14px;",
+                            thead {
+                                tr {
+                                    style: "text-align: left; border-bottom: 1px solid #d1d5db;",
+                                    th {}
+                                    th { "Capa" }
+                                    th { "Color" }
+                                    th { "Tipo de línea" }
+                                    th { "Visible" }
+                                    th { "Entidades" }
+                                }
+                            }
+                            tbody {
+                                for layer in layers() {
+                                    tr {
+                                        key: "{layer.name}",
+                                        style: if layer.is_on && layer.is_plotted { "border-bottom: 1px solid #e5e7eb;" } else { "border-bottom: 1px solid #e5e7eb; color: #6b7280;" },
+                                        td {
+                                            input {
+                                                r#type: "checkbox",
+                                                checked: !unchecked_layers().contains(&layer.name),
+                                                oninput: {
+                                                    let name = layer.name.clone();
+                                                    move |e: Event<FormData>| {
+                                                        let name = name.clone();
+                                                        unchecked_layers.with_mut(|set| {
+                                                            if e.value() == "on" { set.remove(&name); } else { set.insert(name); }
+                                                        });
+                                                    }
+                                                }
+                                            }
+                                        }
+                                        td { "{layer.name}" }
+                                        td {
+                                            if let Some(css) = layer.color.and_then(aci_css) {
+                                                span { style: "display: inline-block; width: 10px; height: 10px; margin-right: 4px; border: 1px solid #9ca3af; background: {css};" }
+                                            }
+                                            {layer.color.map_or_else(|| "-".to_string(), |c| c.to_string())}
+                                        }
+                                        td { "{layer.line_type}" }
+                                        td {
+                                            if !layer.is_on { "apagada o congelada" } else if !layer.is_plotted { "no se imprime" } else { "sí" }
+                                        }
+                                        td { "{layer.entities}" }
+                                    }
+                                }
+                            }
+                        }
+                        if !blocks().is_empty() {
+                            h3 { style: "margin: 0; color: #1e3a8a;", "Bloques" }
+                            table {
+                                style: "width: 100%; border-collapse: collapse; font-size: 14px;",
+                                thead {
+                                    tr {
+                                        style: "text-align: left; border-bottom: 1px solid #d1d5db;",
+                                        th {}
+                                        th { "Bloque" }
+                                        th { "Entidades" }
+                                        th { title: "Niveles de bloques insertados dentro de este", "Anidamiento" }
+                                        th { "Inserciones" }
+                                    }
+                                }
+                                tbody {
+                                    for block in blocks() {
+                                        tr {
+                                            key: "{block.name}",
+                                            style: "border-bottom: 1px solid #e5e7eb;",
+                                            td {
+                                                input {
+                                                    r#type: "checkbox",
+                                                    title: "Desmarcado, se ignoran todas sus inserciones",
+                                                    checked: !unchecked_blocks().contains(&block.name),
+                                                    oninput: {
+                                                        let name = block.name.clone();
+                                                        move |e: Event<FormData>| {
+                                                            let name = name.clone();
+                                                            unchecked_blocks.with_mut(|set| {
+                                                                if e.value() == "on" { set.remove(&name); } else { set.insert(name); }
+                                                            });
+                                                        }
+                                                    }
+                                                }
+                                            }
+                                            td { "{block.name}" }
+                                            td { "{block.entities}" }
+                                            td { "{block.depth}" }
+                                            td { "{block.inserts}" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        if !unsupported_types().is_empty() {
+                            h3 { style: "margin: 0; color: #1e3a8a;", "Entidades no soportadas" }
+                            div {
+                                style: "color: #b45309;",
+                                {unsupported_types().iter().map(|(name, count)| format!("{name} ({count})")).collect::<Vec<_>>().join(", ")}
+                            }
+                        }
+                    }
+                }
+
+                if let Some(Some(data)) = preview() {
+                    div {
+                        style: "background: #f9fafb; border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px;",
+                        div {
+                            style: "display: flex; align-items: center; gap: 12px; margin-bottom: 8px;",
+                            h3 { style: "margin: 0; color: #1e3a8a;", "Vista previa" }
+                            span { style: "color: #6b7280;", "Rueda para ampliar, arrastrar para mover" }
+                            label {
+                                title: "Dibuja el DXF en rojo sobre el elemento, con la escala de la conversión",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: overlay_preview(),
+                                    oninput: move |e| overlay_preview.set(e.value() == "on")
+                                }
+                                span { " superponer DXF" }
+                            }
+                            button {
+                                style: "padding: 4px 10px; border: 1px solid #d1d5db; border-radius: 4px; background: white; cursor: pointer;",
+                                onclick: move |_| {
+                                    preview_zoom.set(1.0);
+                                    preview_pan.set((0.0, 0.0));
+                                },
+                                "Ajustar"
+                            }
+                            label {
+                                title: "Clic en el elemento para añadir un borne o mover el seleccionado, clic sobre un borne para seleccionarlo",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: edit_terminals(),
+                                    oninput: move |e| {
+                                        edit_terminals.set(e.value() == "on");
+                                        selected_terminal.set(None);
+                                    }
+                                }
+                                span { " editar bornes" }
+                            }
+                        }
+                        div {
+                            style: "display: flex; gap: 8px;",
+                            // Los dos paneles comparten zoom y desplazamiento para poder compararlos
+                            for (title, svg) in [("DXF", data.source_svg.clone()), ("ELMT", data.elmt_svg.clone())] {
+                                div {
+                                    key: "{title}",
+                                    class: "preview",
+                                    style: if title == "ELMT" && edit_terminals() {
+                                        "flex: 1; height: 320px; overflow: hidden; position: relative; background: white; border: 1px solid #d1d5db; border-radius: 4px; cursor: crosshair;"
+                                    } else {
+                                        "flex: 1; height: 320px; overflow: hidden; position: relative; background: white; border: 1px solid #d1d5db; border-radius: 4px; cursor: grab;"
+                                    },
+                                    onmounted: move |e: MountedEvent| {
+                                        if title == "ELMT" {
+                                            elmt_pane.set(Some(e.data()));
+                                        }
+                                    },
+                                    onwheel: move |e: WheelEvent| {
+                                        let factor = if e.delta().strip_units().y < 0.0 { 1.2 } else { 1.0 / 1.2 };
+                                        preview_zoom.with_mut(|z| *z = (*z * factor).clamp(0.1, 50.0));
+                                    },
+                                    onmousedown: move |e: MouseEvent| {
+                                        let p = e.client_coordinates();
+                                        drag_from.set(Some((p.x, p.y)));
+                                        dragged.set(false);
+                                    },
+                                    onmousemove: move |e: MouseEvent| {
+                                        if let Some((x0, y0)) = drag_from() {
+                                            let p = e.client_coordinates();
+                                            dragged.set(true);
+                                            preview_pan.with_mut(|(x, y)| {
+                                                *x += p.x - x0;
+                                                *y += p.y - y0;
+                                            });
+                                            drag_from.set(Some((p.x, p.y)));
+                                        }
+                                    },
+                                    onmouseup: move |_| drag_from.set(None),
+                                    onmouseleave: move |_| drag_from.set(None),
+                                    onclick: move |e: MouseEvent| {
+                                        // Un arrastre para mover la vista no coloca bornes
+                                        if title != "ELMT" || !edit_terminals() || dragged() {
+                                            return;
+                                        }
+                                        let Some(pane) = elmt_pane() else { return };
+                                        let client = e.client_coordinates();
+                                        dioxus::core::spawn(async move {
+                                            let Ok(rect) = pane.get_client_rect().await else { return };
+                                            let Some(Some(data)) = preview() else { return };
+                                            let (x, y) = element_point(
+                                                (client.x, client.y),
+                                                (rect.origin.x, rect.origin.y, rect.size.width, rect.size.height),
+                                                preview_pan(),
+                                                preview_zoom(),
+                                                data.view_box,
+                                            );
+                                            // Clic sobre un borne: seleccionarlo
+                                            if let Some(i) = data.terminals.iter().position(|t| (t.x - x).hypot(t.y - y) <= GRID_STEP / 2.0) {
+                                                selected_terminal.set(Some(i));
+                                                return;
+                                            }
+                                            let (x, y) = data.placement.to_drawing(snap(x), snap(y));
+                                            let selected = selected_terminal();
+                                            meta_form.with_mut(|f| {
+                                                let terminals = f.placed_terminals(&data.detected);
+                                                match selected.and_then(|i| terminals.get_mut(i)) {
+                                                    Some(term) => (term.x, term.y) = (x, y),
+                                                    None => {
+                                                        terminals.push(TerminalPlacement::new(x, y, TermOrient::North));
+                                                        selected_terminal.set(Some(terminals.len() - 1));
+                                                    }
+                                                }
+                                            });
+                                            meta_dirty.set(true);
+                                        });
+                                    },
+                                    span {
+                                        style: "position: absolute; top: 4px; left: 6px; color: #6b7280; font-size: 12px; z-index: 1;",
+                                        "{title}"
+                                    }
+                                    div {
+                                        style: "position: relative; width: 100%; height: 100%; transform-origin: 50% 50%; transform: translate({preview_pan().0}px, {preview_pan().1}px) scale({preview_zoom()});",
+                                        div {
+                                            style: "width: 100%; height: 100%;",
+                                            dangerous_inner_html: "{svg}"
+                                        }
+                                        // Objeto elegido en el registro, encima del elemento con el mismo viewBox
+                                        if let Some(bounds) = highlight().filter(|_| title == "ELMT") {
+                                            div {
+                                                style: "position: absolute; inset: 0; pointer-events: none;",
+                                                dangerous_inner_html: "{highlight_svg(data.view_box, bounds)}"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        if edit_terminals() {
+                            div {
+                                style: "display: flex; flex-direction: column; gap: 6px; margin-top: 8px;",
+                                span {
+                                    style: "color: #6b7280; font-size: 13px;",
+                                    if meta_form.read().terminals.is_some() {
+                                        "Bornes colocados a mano, se guardan con los metadatos y sustituyen a los detectados. Rejilla de {GRID_STEP} px."
+                                    } else {
+                                        "Bornes detectados por la conversión, al editarlos pasan a guardarse con los metadatos. Rejilla de {GRID_STEP} px."
+                                    }
+                                }
+                                table {
+                                    style: "border-collapse: collapse; font-size: 13px;",
+                                    thead {
+                                        tr {
+                                            th { style: "text-align: left;", "#" }
+                                            th { style: "text-align: left;", "X" }
+                                            th { style: "text-align: left;", "Y" }
+                                            th { style: "text-align: left;", "Orientación" }
+                                            th { style: "text-align: left;", "Nombre" }
+                                            th {}
+                                        }
+                                    }
+                                    tbody {
+                                        for (i, term) in data.terminals.iter().cloned().enumerate() {
+                                            tr {
+                                                key: "{term.uuid}",
+                                                style: if selected_terminal() == Some(i) { "background: #dbeafe;" } else { "" },
+                                                onclick: move |_| selected_terminal.set(Some(i)),
+                                                td { "{i + 1}" }
+                                                for (axis, value, at) in [("x", term.x, (term.x, term.y)), ("y", term.y, (term.x, term.y))] {
+                                                    td {
+                                                        key: "{axis}",
+                                                        input {
+                                                            r#type: "number",
+                                                            step: "{GRID_STEP}",
+                                                            style: "width: 70px; padding: 2px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                                            value: "{value}",
+                                                            onchange: move |e: Event<FormData>| {
+                                                                let Ok(v) = e.value().parse::<f64>() else { return };
+                                                                let Some(data) = preview().flatten() else { return };
+                                                                // El otro eje se queda donde está en el elemento
+                                                                let (x, y) = if axis == "x" { (snap(v), at.1) } else { (at.0, snap(v)) };
+                                                                let (x, y) = data.placement.to_drawing(x, y);
+                                                                meta_form.with_mut(|f| {
+                                                                    if let Some(term) = f.placed_terminals(&data.detected).get_mut(i) {
+                                                                        (term.x, term.y) = (x, y);
+                                                                    }
+                                                                });
+                                                                meta_dirty.set(true);
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                                td {
+                                                    select {
+                                                        style: "padding: 2px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                                        onchange: move |e: Event<FormData>| {
+                                                            let Ok(orientation) = e.value().parse::<TermOrient>() else { return };
+                                                            let detected = preview().flatten().map(|d| d.detected).unwrap_or_default();
+                                                            meta_form.with_mut(|f| {
+                                                                if let Some(term) = f.placed_terminals(&detected).get_mut(i) {
+                                                                    term.orientation = orientation;
+                                                                }
+                                                            });
+                                                            meta_dirty.set(true);
+                                                        },
+                                                        for (orientation, label) in [(TermOrient::North, "Norte"), (TermOrient::East, "Este"), (TermOrient::South, "Sur"), (TermOrient::West, "Oeste")] {
+                                                            option {
+                                                                value: "{orientation}",
+                                                                selected: term.orientation == orientation,
+                                                                "{label}"
+                                                            }
+                                                        }
+                                                    }
+                                                }
+                                                td {
+                                                    input {
+                                                        style: "width: 90px; padding: 2px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                                        value: "{term.name}",
+                                                        onchange: move |e: Event<FormData>| {
+                                                            let detected = preview().flatten().map(|d| d.detected).unwrap_or_default();
+                                                            meta_form.with_mut(|f| {
+                                                                if let Some(term) = f.placed_terminals(&detected).get_mut(i) {
+                                                                    term.name = e.value();
+                                                                }
+                                                            });
+                                                            meta_dirty.set(true);
+                                                        }
+                                                    }
+                                                }
+                                                td {
+                                                    button {
+                                                        onclick: move |_| {
+                                                            let detected = preview().flatten().map(|d| d.detected).unwrap_or_default();
+                                                            meta_form.with_mut(|f| {
+                                                                if let Some(term) = f.placed_terminals(&detected).get_mut(i) {
+                                                                    term.orientation = term.orientation.rotated();
+                                                                }
+                                                            });
+                                                            meta_dirty.set(true);
+                                                        },
+                                                        "Girar"
+                                                    }
+                                                    button {
+                                                        onclick: move |e: MouseEvent| {
+                                                            e.stop_propagation();
+                                                            let detected = preview().flatten().map(|d| d.detected).unwrap_or_default();
+                                                            meta_form.with_mut(|f| {
+                                                                let terminals = f.placed_terminals(&detected);
+                                                                if i < terminals.len() {
+                                                                    terminals.remove(i);
+                                                                }
+                                                            });
+                                                            selected_terminal.set(None);
+                                                            meta_dirty.set(true);
+                                                        },
+                                                        "Borrar"
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                                div {
+                                    style: "display: flex; gap: 8px;",
+                                    button {
+                                        disabled: selected_terminal().is_none(),
+                                        onclick: move |_| selected_terminal.set(None),
+                                        "Deseleccionar (el próximo clic añade un borne)"
+                                    }
+                                    button {
+                                        disabled: meta_form.read().terminals.is_none(),
+                                        onclick: move |_| {
+                                            meta_form.with_mut(|f| f.terminals = None);
+                                            selected_terminal.set(None);
+                                            meta_dirty.set(true);
+                                        },
+                                        "Restablecer detección automática"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if let Some(path) = selected_path() {
+                    div {
+                        style: "background: #f9fafb; border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px; display: flex; flex-direction: column; gap: 8px;",
+                        h3 { style: "margin: 0; color: #1e3a8a;", "Metadatos del elemento" }
+                        span { style: "color: #6b7280; font-size: 13px;", "Se guardan junto al DXF y se aplican cada vez que se convierte. Sin nombres se usa el nombre del archivo." }
+                        label { "Nombres:" }
+                        for (i, (lang, name)) in meta_form().names.into_iter().enumerate() {
+                            div {
+                                key: "name-{i}",
+                                style: "display: flex; gap: 6px;",
+                                input {
+                                    style: "width: 50px; padding: 4px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                    placeholder: "en",
+                                    value: "{lang}",
+                                    oninput: move |e| {
+                                        meta_form.with_mut(|f| f.names[i].0 = e.value());
+                                        meta_dirty.set(true);
+                                    }
+                                }
+                                input {
+                                    style: "flex: 1; padding: 4px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                    value: "{name}",
+                                    oninput: move |e| {
+                                        meta_form.with_mut(|f| f.names[i].1 = e.value());
+                                        meta_dirty.set(true);
+                                    }
+                                }
+                                button {
+                                    onclick: move |_| {
+                                        meta_form.with_mut(|f| { f.names.remove(i); });
+                                        meta_dirty.set(true);
+                                    },
+                                    "Quitar"
+                                }
+                            }
+                        }
+                        button {
+                            style: "width: fit-content;",
+                            onclick: move |_| meta_form.with_mut(|f| f.names.push((String::new(), String::new()))),
+                            "Añadir idioma"
+                        }
+                        label { "Informaciones:" }
+                        textarea {
+                            style: "padding: 4px; border: 1px solid #d1d5db; border-radius: 4px; min-height: 48px;",
+                            value: "{meta_form.read().informations}",
+                            oninput: move |e| {
+                                meta_form.with_mut(|f| f.informations = e.value());
+                                meta_dirty.set(true);
+                            }
+                        }
+                        div {
+                            style: "display: flex; align-items: center; gap: 8px;",
+                            label { "Tipo de enlace:" }
+                            select {
+                                style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                onchange: move |e: Event<FormData>| {
+                                    meta_form.with_mut(|f| f.link_type = e.value().parse().ok());
+                                    meta_dirty.set(true);
+                                },
+                                option { value: "", selected: meta_form.read().link_type.is_none(), "(por defecto: simple)" }
+                                for link_type in LinkType::ALL {
+                                    option {
+                                        value: "{link_type}",
+                                        selected: meta_form.read().link_type == Some(link_type),
+                                        "{link_type}"
+                                    }
+                                }
+                            }
+                        }
+                        label { "Informaciones del elemento:" }
+                        datalist {
+                            id: "element-info-names",
+                            for info_name in ELEMENT_INFO_NAMES {
+                                option { value: "{info_name}" }
+                            }
+                        }
+                        for (i, (info_name, value)) in meta_form().infos.into_iter().enumerate() {
+                            div {
+                                key: "info-{i}",
+                                style: "display: flex; gap: 6px;",
+                                input {
+                                    style: "width: 220px; padding: 4px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                    list: "element-info-names",
+                                    placeholder: "manufacturer",
+                                    value: "{info_name}",
+                                    oninput: move |e| {
+                                        meta_form.with_mut(|f| f.infos[i].0 = e.value());
+                                        meta_dirty.set(true);
+                                    }
+                                }
+                                input {
+                                    style: "flex: 1; padding: 4px; border: 1px solid #d1d5db; border-radius: 4px;",
+                                    value: "{value}",
+                                    oninput: move |e| {
+                                        meta_form.with_mut(|f| f.infos[i].1 = e.value());
+                                        meta_dirty.set(true);
+                                    }
+                                }
+                                button {
+                                    onclick: move |_| {
+                                        meta_form.with_mut(|f| { f.infos.remove(i); });
+                                        meta_dirty.set(true);
+                                    },
+                                    "Quitar"
+                                }
+                            }
+                        }
+                        button {
+                            style: "width: fit-content;",
+                            onclick: move |_| meta_form.with_mut(|f| f.infos.push((String::new(), String::new()))),
+                            "Añadir información"
+                        }
+                        button {
+                            disabled: !meta_dirty(),
+                            style: "background: #374151; color: white; border: none; padding: 8px 14px; border-radius: 6px; cursor: pointer; width: fit-content;",
+                            onclick: move |_| {
+                                match meta_form.read().to_metadata().save(Path::new(&path)) {
+                                    Ok(()) => {
+                                        meta_dirty.set(false);
+                                        status.set("Metadatos guardados".to_string());
+                                    }
+                                    Err(e) => status.set(format!("Error: {e:#}")),
+                                }
+                            },
+                            "Guardar metadatos"
+                        }
+                    }
+                }
 
                 div {
                     style: "display: flex; flex-direction: column; gap: 12px;",
+                    div {
+                        style: "display: flex; align-items: center; gap: 8px; flex-wrap: nowrap;",
+                        label { style: "white-space: nowrap;", "Preset:" }
+                        select {
+                            title: "Presets definidos en dxf2elmt.toml o en la configuración del usuario",
+                            disabled: config.presets.is_empty(),
+                            style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;",
+                            onchange: {
+                                let config = config.clone();
+                                move |e: Event<FormData>| {
+                                    let name = Some(e.value()).filter(|n| !n.is_empty());
+                                    match config.resolve(name.as_deref()) {
+                                        Ok(p) => {
+                                            let defaults = ConversionOptions::default();
+                                            spline_step.set(p.spline_step.unwrap_or(defaults.spline_step));
+                                            px_per_mm_px.set(p.px_per_mm.unwrap_or(defaults.px_per_mm));
+                                            px_per_mm_mm.set(1.0);
+                                            scale_mode.set(p.scale_mode.unwrap_or_default());
+                                            grid_anchor.set(p.grid_anchor);
+                                            grid_snap.set(p.grid_snap.clone().unwrap_or_default());
+                                            text_mode.set(p.text_mode.unwrap_or_default());
+                                            info_flag.set(p.info.unwrap_or(false));
+                                            unchecked_layers.set(default_unchecked_layers(&layers.read(), &p));
+                                            unchecked_blocks.set(default_unchecked_blocks(&blocks.read(), &p));
+                                            preset.set(p);
+                                            preset_name.set(name);
+                                        }
+                                        Err(e) => status.set(format!("Error: {e}")),
+                                    }
+                                }
+                            },
+                            option { value: "", selected: preset_name().is_none(), "(ninguno)" }
+                            for name in config.preset_names() {
+                                option {
+                                    value: "{name}",
+                                    selected: preset_name().as_deref() == Some(name),
+                                    "{name}"
+                                }
+                            }
+                        }
+                        label { style: "white-space: nowrap;", "Textos:" }
+                        select {
+                            title: "Los textos dinámicos se pueden editar en el editor de elementos de QET",
+                            style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;",
+                            onchange: move |e| {
+                                if let Ok(mode) = e.value().parse::<TextMode>() { text_mode.set(mode); }
+                            },
+                            option { value: "dynamic", selected: text_mode() == TextMode::Dynamic, "dinámicos" }
+                            option { value: "static", selected: text_mode() == TextMode::Static, "estáticos" }
+                        }
+                    }
                     div {
                         style: "display: flex; align-items: center; gap: 8px; flex-wrap: nowrap;",
                         label { style: "white-space: nowrap;", "Spline step:" }
@@ -167,6 +1265,130 @@ fn App() -> Element {
                         }
                         span { style: "white-space: nowrap;", "mm" }
                     }
+                    div {
+                        style: "display: flex; align-items: center; gap: 8px; flex-wrap: nowrap;",
+                        label { style: "white-space: nowrap;", "Tamaño:" }
+                        select {
+                            title: "Cómo se dimensiona el elemento. Si no hay nada que medir se usa la relación px/mm",
+                            style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;",
+                            onchange: move |e: Event<FormData>| {
+                                // Al cambiar de modo se parte de valores razonables para QET
+                                scale_mode.set(match e.value().as_str() {
+                                    "width" => ScaleMode::Width(100.0),
+                                    "height" => ScaleMode::Height(100.0),
+                                    "fit" => ScaleMode::Fit(100.0, 100.0),
+                                    "pitch" => ScaleMode::Pitch(2.0),
+                                    _ => ScaleMode::Ratio,
+                                });
+                            },
+                            option { value: "ratio", selected: scale_mode() == ScaleMode::Ratio, "relación px/mm" }
+                            option { value: "width", selected: matches!(scale_mode(), ScaleMode::Width(_)), "ancho" }
+                            option { value: "height", selected: matches!(scale_mode(), ScaleMode::Height(_)), "alto" }
+                            option { value: "fit", selected: matches!(scale_mode(), ScaleMode::Fit(..)), "caja (mantiene proporción)" }
+                            option { value: "pitch", selected: matches!(scale_mode(), ScaleMode::Pitch(_)), "separación de bornes" }
+                        }
+                        // Valores del modo: píxeles, o pasos de rejilla entre los dos bornes más cercanos
+                        {match scale_mode() {
+                            ScaleMode::Ratio => rsx! {},
+                            ScaleMode::Width(v) | ScaleMode::Height(v) => rsx! {
+                                input {
+                                    r#type: "number",
+                                    min: "1",
+                                    step: "10",
+                                    value: "{v}",
+                                    oninput: move |e| {
+                                        if let Ok(v) = e.value().parse::<f64>() {
+                                            if v > 0.0 {
+                                                scale_mode.with_mut(|m| match m {
+                                                    ScaleMode::Width(w) | ScaleMode::Height(w) => *w = v,
+                                                    _ => {}
+                                                });
+                                            }
+                                        }
+                                    },
+                                    style: "width: 70px; padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;"
+                                }
+                                span { "px" }
+                            },
+                            ScaleMode::Fit(w, h) => rsx! {
+                                for (i, v) in [w, h].into_iter().enumerate() {
+                                    input {
+                                        key: "{i}",
+                                        r#type: "number",
+                                        min: "1",
+                                        step: "10",
+                                        value: "{v}",
+                                        title: if i == 0 { "Ancho de la caja en píxeles" } else { "Alto de la caja en píxeles" },
+                                        oninput: move |e| {
+                                            if let Ok(v) = e.value().parse::<f64>() {
+                                                if v > 0.0 {
+                                                    scale_mode.with_mut(|m| {
+                                                        if let ScaleMode::Fit(w, h) = m {
+                                                            if i == 0 { *w = v; } else { *h = v; }
+                                                        }
+                                                    });
+                                                }
+                                            }
+                                        },
+                                        style: "width: 70px; padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;"
+                                    }
+                                }
+                                span { "px" }
+                            },
+                            ScaleMode::Pitch(steps) => rsx! {
+                                input {
+                                    r#type: "number",
+                                    min: "1",
+                                    step: "1",
+                                    value: "{steps}",
+                                    oninput: move |e| {
+                                        if let Ok(v) = e.value().parse::<f64>() {
+                                            if v > 0.0 { scale_mode.set(ScaleMode::Pitch(v)); }
+                                        }
+                                    },
+                                    style: "width: 60px; padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;"
+                                }
+                                span { style: "white-space: nowrap;", "pasos de {GRID_STEP} px entre bornes" }
+                            },
+                        }}
+                    }
+                    div {
+                        style: "display: flex; align-items: center; gap: 8px; flex-wrap: wrap;",
+                        label { style: "white-space: nowrap;", "Rejilla:" }
+                        select {
+                            title: "Mueve todo el elemento para que este punto caiga en la rejilla de {GRID_STEP} px de QET",
+                            style: "padding: 6px; border: 1px solid #d1d5db; border-radius: 4px;",
+                            onchange: move |e: Event<FormData>| grid_anchor.set(e.value().parse().ok()),
+                            option { value: "", selected: grid_anchor().is_none(), "sin alinear" }
+                            for (anchor, label) in [(GridAnchor::Hotspot, "punto de anclaje"), (GridAnchor::Terminal, "primer borne"), (GridAnchor::Corner, "esquina superior izquierda")] {
+                                option {
+                                    value: "{anchor}",
+                                    selected: grid_anchor() == Some(anchor),
+                                    "{label}"
+                                }
+                            }
+                        }
+                        span { style: "white-space: nowrap;", "redondear:" }
+                        for (snap, label) in [(GridSnap::Terminals, "bornes"), (GridSnap::Lines, "líneas"), (GridSnap::Polygons, "polígonos"), (GridSnap::Arcs, "arcos"), (GridSnap::Ellipses, "elipses"), (GridSnap::Texts, "textos")] {
+                            label {
+                                key: "{snap}",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: grid_snap.read().contains(&snap),
+                                    oninput: move |e| {
+                                        grid_snap.with_mut(|kinds| {
+                                            kinds.retain(|k| *k != snap);
+                                            if e.value() == "on" { kinds.push(snap); }
+                                        });
+                                    }
+                                }
+                                span { " {label}" }
+                            }
+                        }
+                        if let Some(shift) = preview().flatten().map(|d| d.grid_displacement).filter(|d| *d > 0.0) {
+                            span { style: "color: #6b7280;", "desplazamiento máximo: {shift:.2} px" }
+                        }
+                    }
                     div {
                         style: "display: flex; align-items: center; gap: 12px;",
                         label {
@@ -185,6 +1407,63 @@ fn App() -> Element {
                             }
                             span { " info (estadísticas)" }
                         }
+                        label {
+                            title: "Reconvierte el DXF cada vez que se guarda, con las opciones actuales",
+                            input {
+                                r#type: "checkbox",
+                                disabled: selected_path().is_none(),
+                                checked: watching().is_some(),
+                                oninput: move |e| {
+                                    if let Some(stop) = watching() {
+                                        stop.store(true, Ordering::Relaxed);
+                                        watching.set(None);
+                                    }
+                                    if e.value() != "on" { return; }
+                                    let Some(path_str) = selected_path() else { return; };
+
+                                    let stop = Arc::new(AtomicBool::new(false));
+                                    watching.set(Some(stop.clone()));
+                                    let opts = watch_options.clone();
+                                    let (tx, mut rx) = futures_channel::mpsc::unbounded::<String>();
+                                    std::thread::spawn(move || {
+                                        use dxf2elmt::watch::{DxfWatcher, WatchOptions};
+                                        use std::time::Duration;
+                                        let paths = [std::path::PathBuf::from(path_str)];
+                                        let mut watcher = match DxfWatcher::new(&paths, &WatchOptions::default()) {
+                                            Ok(w) => w,
+                                            Err(e) => {
+                                                let _ = tx.unbounded_send(format!("Error vigilando: {e}"));
+                                                return;
+                                            }
+                                        };
+                                        while !stop.load(Ordering::Relaxed) {
+                                            let Some(files) = watcher.changed_files(Duration::from_millis(250)) else { break; };
+                                            for file in files {
+                                                let Ok(current) = opts.read().map(|o| o.clone()) else { break; };
+                                                let rep = convert_file(&file, &current);
+                                                let msg = match rep.status {
+                                                    FileStatus::Success => format!("OK ({} ms): {}", rep.elapsed_ms, rep.message),
+                                                    FileStatus::Failed => format!("Error: {}", rep.message),
+                                                };
+                                                let _ = tx.unbounded_send(msg);
+                                            }
+                                        }
+                                    });
+                                    // Recibe los resultados del hilo sin bloquear la interfaz
+                                    dioxus::core::spawn(async move {
+                                        use futures_util::StreamExt;
+                                        while let Some(msg) = rx.next().await {
+                                            watch_log.with_mut(|log| {
+                                                log.push(msg);
+                                                let len = log.len();
+                                                if len > 10 { log.drain(..len - 10); }
+                                            });
+                                        }
+                                    });
+                                }
+                            }
+                            span { " vigilar cambios" }
+                        }
                     }
                 }
 
@@ -194,6 +1473,14 @@ fn App() -> Element {
                     onclick: move |_| {
                         if is_processing() { return; }
                         if let Some(path_str) = selected_path() {
+                            // Los metadatos sin guardar se guardan antes, la conversión los lee del archivo auxiliar
+                            if meta_dirty() {
+                                if let Err(e) = meta_form.read().to_metadata().save(Path::new(&path_str)) {
+                                    status.set(format!("Error: {e:#}"));
+                                    return;
+                                }
+                                meta_dirty.set(false);
+                            }
                             is_processing.set(true);
                             status.set("Convirtiendo...".to_string());
                             let path_owned = path_str.clone();
@@ -203,25 +1490,51 @@ fn App() -> Element {
                             let px = px_per_mm_px();
                             let mm = px_per_mm_mm();
                             let px_per_mm = px / mm;
+                            let layers_known = !layers.read().is_empty();
+                            let mut opts = conversion_options(&preset.read(), step, px_per_mm, scale_mode(), (grid_anchor(), &grid_snap.read()), text_mode(), v, i, Some((&unchecked_layers(), &unchecked_blocks())).filter(|_| layers_known));
+                            let token = CancelToken::new();
+                            conversion_cancel.set(Some(token.clone()));
+                            let (tx, mut rx) = futures_channel::mpsc::unbounded::<Progress>();
+                            opts.progress = ProgressHooks::new(move |p| {
+                                let _ = tx.unbounded_send(p);
+                            })
+                            .with_cancel(token);
                             dioxus::core::spawn(async move {
-                                use dxf2elmt::{convert_dxf_file, ConversionOptions};
-                                use std::path::PathBuf;
-                                use std::path::Path;
-                                let result = std::thread::spawn(move || {
-                                    let pb = PathBuf::from(path_owned);
-                                    let opts = ConversionOptions { spline_step: step, verbose: v, info: i, px_per_mm };
-                                    convert_dxf_file(&pb, &opts)
-                                }).join();
+                                use dxf2elmt::convert_dxf_file;
+                                use futures_util::StreamExt;
+                                let pb = PathBuf::from(path_owned);
+                                let out_path = opts.output_path(&pb);
+                                let (done_tx, done_rx) = futures_channel::oneshot::channel();
+                                let output_path = out_path.clone();
+                                std::thread::spawn(move || {
+                                    let result = convert_dxf_file(&pb, &opts).map(|conv| {
+                                        let output = conversion_output(&conv, &output_path);
+                                        (conv, output)
+                                    });
+                                    let _ = done_tx.send(result);
+                                });
+                                // Los avisos llegan hasta que el hilo suelta las opciones, al terminar
+                                while let Some(p) = rx.next().await {
+                                    conversion_progress.set(Some(progress_status(p)));
+                                }
+                                let result = done_rx.await;
+                                conversion_progress.set(None);
+                                conversion_cancel.set(None);
+                                highlight.set(None);
                                 match result {
-                                    Ok(Ok(conv)) => {
+                                    Ok(Ok((conv, output))) => {
+                                        let (xml, log) = output.unzip();
+                                        conversion_xml.set(xml);
+                                        conversion_log.set(log.unwrap_or_default());
                                         // Guardamos la carpeta de salida si no es verbose (se escribe archivo)
                                         if !v {
-                                            if let Some(parent) = Path::new(&selected_path().unwrap_or_default()).parent() {
+                                            if let Some(parent) = out_path.parent() {
                                                 last_output_dir.set(Some(parent.display().to_string()));
                                             }
                                         }
                                         status.set(format!("OK: {}", conv.message));
                                     }
+                                    Ok(Err(e)) if e.is::<Cancelled>() => status.set("Conversión cancelada".to_string()),
                                     Ok(Err(e)) => status.set(format!("Error: {e}")),
                                     Err(_) => status.set("Error: fallo interno al convertir".to_string()),
                                 }
@@ -231,9 +1544,144 @@ fn App() -> Element {
                     },
                     if is_processing() { "Convirtiendo..." } else { "Convertir a ELMT" }
                 }
+                if let Some((fraction, text)) = conversion_progress() {
+                    div {
+                        style: "display: flex; align-items: center; gap: 8px;",
+                        progress { style: "flex: 1;", max: "1", value: "{fraction}" }
+                        span { style: "white-space: nowrap; color: #374151;", "{text}" }
+                        button {
+                            disabled: conversion_cancel().is_none(),
+                            style: "padding: 4px 10px; border: 1px solid #d1d5db; border-radius: 4px; background: white; cursor: pointer;",
+                            onclick: move |_| {
+                                if let Some(token) = conversion_cancel() {
+                                    token.cancel();
+                                }
+                            },
+                            "Cancelar"
+                        }
+                    }
+                }
                 if !status().is_empty() {
                     div { style: "color: #111827;", "{status()}" }
                 }
+                if let Some(xml) = conversion_xml() {
+                    div {
+                        style: "background: #f9fafb; border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px; display: flex; flex-direction: column; gap: 8px;",
+                        div {
+                            style: "display: flex; gap: 4px; border-bottom: 1px solid #d1d5db;",
+                            for (tab, label) in [(OutputTab::Log, format!("Registro ({})", conversion_log.read().len())), (OutputTab::Xml, "XML".to_string())] {
+                                button {
+                                    key: "{label}",
+                                    style: if output_tab() == tab {
+                                        "padding: 6px 12px; border: 1px solid #d1d5db; border-bottom: none; border-radius: 4px 4px 0 0; background: white; cursor: pointer;"
+                                    } else {
+                                        "padding: 6px 12px; border: none; background: transparent; color: #6b7280; cursor: pointer;"
+                                    },
+                                    onclick: move |_| output_tab.set(tab),
+                                    "{label}"
+                                }
+                            }
+                        }
+                        if output_tab() == OutputTab::Xml {
+                            div {
+                                style: "display: flex; gap: 8px;",
+                                button {
+                                    onclick: move |_| {
+                                        if let Some(xml) = conversion_xml() {
+                                            let text = serde_json::to_string(&xml).unwrap_or_default();
+                                            let _ = document::eval(&format!("navigator.clipboard.writeText({text});"));
+                                            status.set("XML copiado al portapapeles".to_string());
+                                        }
+                                    },
+                                    "Copiar"
+                                }
+                                button {
+                                    onclick: move |_| {
+                                        let Some(xml) = conversion_xml() else { return };
+                                        let mut dialog = rfd::FileDialog::new()
+                                            .add_filter("ELMT", &["elmt"])
+                                            .set_title("Guardar XML como");
+                                        if let Some(name) = selected_path().as_deref().map(Path::new).and_then(Path::file_stem) {
+                                            dialog = dialog.set_file_name(format!("{}.elmt", name.to_string_lossy()));
+                                        }
+                                        if let Some(dir) = last_output_dir() {
+                                            dialog = dialog.set_directory(dir);
+                                        }
+                                        if let Some(dest) = dialog.save_file() {
+                                            match std::fs::write(&dest, xml) {
+                                                Ok(()) => status.set(format!("XML guardado en {}", dest.display())),
+                                                Err(e) => status.set(format!("Error: {e}")),
+                                            }
+                                        }
+                                    },
+                                    "Guardar como..."
+                                }
+                            }
+                            pre {
+                                style: "max-height: 360px; overflow: auto; margin: 0; padding: 8px; background: white; border: 1px solid #d1d5db; border-radius: 4px; font-size: 12px;",
+                                "{xml}"
+                            }
+                        } else {
+                            div {
+                                style: "display: flex; gap: 12px;",
+                                for severity in [Severity::Error, Severity::Warning, Severity::Info] {
+                                    label {
+                                        key: "{severity}",
+                                        input {
+                                            r#type: "checkbox",
+                                            checked: shown_severities.read().contains(&severity),
+                                            oninput: move |e| {
+                                                shown_severities.with_mut(|set| {
+                                                    if e.value() == "on" { set.insert(severity); } else { set.remove(&severity); }
+                                                });
+                                            }
+                                        }
+                                        span {
+                                            style: "color: {severity_label(severity).1};",
+                                            " {severity_label(severity).0} ({conversion_log.read().iter().filter(|d| d.severity == severity).count()})"
+                                        }
+                                    }
+                                }
+                            }
+                            span { style: "color: #6b7280; font-size: 13px;", "Clic en una entrada para resaltar el objeto en la vista previa" }
+                            ul {
+                                style: "max-height: 240px; overflow: auto; margin: 0; padding-left: 20px; font-size: 13px;",
+                                for (i, diagnostic) in conversion_log().into_iter().enumerate().filter(|(_, d)| shown_severities.read().contains(&d.severity)) {
+                                    li {
+                                        key: "{i}",
+                                        style: if diagnostic.bounds.is_some() && highlight() == diagnostic.bounds {
+                                            format!("color: {}; cursor: pointer; background: #fef3c7;", severity_label(diagnostic.severity).1)
+                                        } else if diagnostic.bounds.is_some() {
+                                            format!("color: {}; cursor: pointer;", severity_label(diagnostic.severity).1)
+                                        } else {
+                                            format!("color: {};", severity_label(diagnostic.severity).1)
+                                        },
+                                        onclick: move |_| {
+                                            if diagnostic.bounds.is_some() {
+                                                highlight.set(diagnostic.bounds);
+                                            }
+                                        },
+                                        "{diagnostic}"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                if watching().is_some() || !watch_log().is_empty() {
+                    div {
+                        style: "background: #f0fdf4; border: 1px solid #bbf7d0; border-radius: 8px; padding: 12px;",
+                        h3 { style: "margin: 0 0 8px 0; color: #166534;", "Conversiones automáticas" }
+                        if watch_log().is_empty() {
+                            div { "Esperando cambios en el DXF..." }
+                        }
+                        ul {
+                            for entry in watch_log().into_iter().rev() {
+                                li { "{entry}" }
+                            }
+                        }
+                    }
+                }
                 button {
                     disabled: last_output_dir().is_none(),
                     style: "background: #374151; color: white; border: none; padding: 10px 16px; border-radius: 6px; cursor: pointer; width: fit-content;",
@@ -255,6 +1703,198 @@ fn App() -> Element {
                     },
                     "Abrir carpeta"
                 }
+
+                div {
+                    style: "background: #f9fafb; border: 1px solid #e5e7eb; border-radius: 8px; padding: 12px; display: flex; flex-direction: column; gap: 8px;",
+                    h3 { style: "margin: 0; color: #1e3a8a;", "Cola de conversión" }
+                    div {
+                        style: "display: flex; align-items: center; gap: 8px; flex-wrap: wrap;",
+                        button {
+                            style: "padding: 6px 12px; border: 1px solid #d1d5db; border-radius: 4px; background: white; cursor: pointer;",
+                            onclick: move |_| {
+                                let mut dialog = rfd::FileDialog::new()
+                                    .add_filter("DXF", &["dxf"])
+                                    .set_title("Selecciona archivos DXF");
+                                if let Some(dir) = desktop_state.read().last_input_dir.clone() {
+                                    dialog = dialog.set_directory(dir);
+                                }
+                                if let Some(files) = dialog.pick_files() {
+                                    desktop_state.with_mut(|d| d.last_input_dir = files.first().and_then(|f| f.parent()).map(Path::to_path_buf));
+                                    queue.with_mut(|q| enqueue(q, files));
+                                }
+                            },
+                            "Añadir archivos..."
+                        }
+                        button {
+                            style: "padding: 6px 12px; border: 1px solid #d1d5db; border-radius: 4px; background: white; cursor: pointer;",
+                            onclick: move |_| {
+                                let mut dialog = rfd::FileDialog::new().set_title("Selecciona una carpeta con DXF");
+                                if let Some(dir) = desktop_state.read().last_input_dir.clone() {
+                                    dialog = dialog.set_directory(dir);
+                                }
+                                let Some(dir) = dialog.pick_folder() else { return; };
+                                desktop_state.with_mut(|d| d.last_input_dir = Some(dir.clone()));
+                                match dxf_inputs(&[dir]) {
+                                    Ok(files) => queue.with_mut(|q| enqueue(q, files)),
+                                    Err(e) => queue_status.set(format!("Error: {e}")),
+                                }
+                            },
+                            "Añadir carpeta..."
+                        }
+                        button {
+                            disabled: queue_running() || queue.read().iter().all(|e| e.state == QueueState::Pending),
+                            title: "Quita de la cola los archivos ya convertidos, fallidos o cancelados",
+                            style: "padding: 6px 12px; border: 1px solid #d1d5db; border-radius: 4px; background: white; cursor: pointer;",
+                            onclick: move |_| queue.with_mut(|q| q.retain(|e| e.state == QueueState::Pending)),
+                            "Limpiar terminados"
+                        }
+                        span { style: "color: #6b7280;", "o arrastra archivos y carpetas a la ventana" }
+                    }
+                    if !queue.read().is_empty() {
+                        table {
+                            style: "width: 100%; border-collapse: collapse; font-size: 14px;",
+                            thead {
+                                tr {
+                                    style: "text-align: left; border-bottom: 1px solid #d1d5db;",
+                                    th { "Archivo" }
+                                    th { "Estado" }
+                                    th { "Avisos" }
+                                    th { "Salida" }
+                                    th {}
+                                }
+                            }
+                            tbody {
+                                for entry in queue() {
+                                    tr {
+                                        key: "{entry.path.display()}",
+                                        style: "border-bottom: 1px solid #e5e7eb; vertical-align: top;",
+                                        td { title: "{entry.path.display()}", {entry.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()} }
+                                        td {
+                                            style: match entry.state {
+                                                QueueState::Done => "color: #166534;",
+                                                QueueState::Failed => "color: #b91c1c;",
+                                                _ => "color: #374151;",
+                                            },
+                                            title: entry.report.as_ref().map(|r| r.message.clone()).unwrap_or_default(),
+                                            "{entry.state.label()}"
+                                        }
+                                        td {
+                                            for warning in entry.report.as_ref().map(|r| r.warnings.clone()).unwrap_or_default() {
+                                                div { "{warning}" }
+                                            }
+                                        }
+                                        td {
+                                            {entry.report.as_ref().and_then(|r| r.output.as_ref()).map(|o| o.display().to_string()).unwrap_or_default()}
+                                        }
+                                        td {
+                                            if matches!(entry.state, QueueState::Pending | QueueState::Running) && queue_running() {
+                                                button {
+                                                    onclick: {
+                                                        let path = entry.path.clone();
+                                                        move |_| {
+                                                            queue.with_mut(|q| {
+                                                                if let Some(e) = q.iter_mut().find(|e| e.path == path) {
+                                                                    e.cancel.cancel();
+                                                                    e.state = QueueState::Cancelled;
+                                                                }
+                                                            });
+                                                        }
+                                                    },
+                                                    "Cancelar"
+                                                }
+                                            } else if entry.state != QueueState::Running {
+                                                button {
+                                                    onclick: {
+                                                        let path = entry.path.clone();
+                                                        move |_| queue.with_mut(|q| q.retain(|e| e.path != path))
+                                                    },
+                                                    "Quitar"
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    button {
+                        disabled: queue_running() || !queue.read().iter().any(|e| e.state == QueueState::Pending),
+                        style: "background: #16a34a; color: white; border: none; padding: 10px 16px; border-radius: 6px; cursor: pointer; width: fit-content;",
+                        onclick: move |_| {
+                            if queue_running() { return; }
+                            let files: Vec<(PathBuf, CancelToken)> = queue
+                                .read()
+                                .iter()
+                                .filter(|e| e.state == QueueState::Pending)
+                                .map(|e| (e.path.clone(), e.cancel.clone()))
+                                .collect();
+                            // Cada archivo con sus capas, las casillas de capas son solo del DXF seleccionado
+                            let opts = conversion_options(&preset.read(), spline_step(), px_per_mm_px() / px_per_mm_mm(), scale_mode(), (grid_anchor(), &grid_snap.read()), text_mode(), false, info_flag(), None);
+                            queue_running.set(true);
+                            queue_status.set(format!("Convirtiendo {} archivos...", files.len()));
+
+                            let (tx, mut rx) = futures_channel::mpsc::unbounded::<QueueEvent>();
+                            std::thread::spawn(move || {
+                                let paths: Vec<PathBuf> = files.iter().map(|(p, _)| p.clone()).collect();
+                                let summary = run_batch(&paths, &BatchOptions::default(), |file| {
+                                    let mut file_opts = opts.clone();
+                                    if let Some((_, token)) = files.iter().find(|(p, _)| p == file) {
+                                        file_opts.progress.cancel = token.clone();
+                                    }
+                                    if file_opts.progress.is_cancelled() {
+                                        return FileReport::new(file, None, std::time::Instant::now(), Err(Cancelled.into()));
+                                    }
+                                    let _ = tx.unbounded_send(QueueEvent::Started(file.to_path_buf()));
+                                    let report = convert_file(file, &file_opts);
+                                    let _ = tx.unbounded_send(QueueEvent::Finished(report.clone()));
+                                    report
+                                });
+                                if let Err(e) = summary {
+                                    let _ = tx.unbounded_send(QueueEvent::Finished(FileReport::new(Path::new(""), None, std::time::Instant::now(), Err(e))));
+                                }
+                            });
+                            // Actualiza la tabla según avanza el hilo, sin bloquear la interfaz
+                            dioxus::core::spawn(async move {
+                                use futures_util::StreamExt;
+                                while let Some(event) = rx.next().await {
+                                    queue.with_mut(|q| {
+                                        let (path, state, report) = match event {
+                                            QueueEvent::Started(path) => (path, QueueState::Running, None),
+                                            QueueEvent::Finished(report) => {
+                                                let state = if report.status == FileStatus::Success { QueueState::Done } else { QueueState::Failed };
+                                                (report.input.clone(), state, Some(report))
+                                            }
+                                        };
+                                        match q.iter_mut().find(|e| e.path == path) {
+                                            // Lo cancelado se queda cancelado, aunque el hilo aún mande su informe de error
+                                            Some(e) if e.state != QueueState::Cancelled => {
+                                                e.state = state;
+                                                e.report = report;
+                                            }
+                                            Some(_) => {}
+                                            None => {
+                                                if let Some(r) = report {
+                                                    queue_status.set(format!("Error: {}", r.message));
+                                                }
+                                            }
+                                        }
+                                    });
+                                }
+                                let (done, failed) = queue.read().iter().fold((0, 0), |(d, f), e| match e.state {
+                                    QueueState::Done => (d + 1, f),
+                                    QueueState::Failed => (d, f + 1),
+                                    _ => (d, f),
+                                });
+                                queue_status.set(format!("Cola terminada: {done} convertidos, {failed} con error"));
+                                queue_running.set(false);
+                            });
+                        },
+                        if queue_running() { "Convirtiendo..." } else { "Convertir todo" }
+                    }
+                    if !queue_status().is_empty() {
+                        div { style: "color: #111827;", "{queue_status()}" }
+                    }
+                }
             }
         }
     }
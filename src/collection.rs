@@ -0,0 +1,192 @@
+use crate::batch::{collect_inputs, convert_batch_with, BatchOptions, BatchSummary};
+use crate::ConversionOptions;
+use anyhow::{Context, Result};
+use simple_xml_builder::XMLElement;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+
+/// File QET reads the names of a category (a folder of an element collection) from
+pub const QET_DIRECTORY_FILE: &str = "qet_directory";
+
+/// Language used for the category names derived from the folder names
+const DEFAULT_LANG: &str = "en";
+
+/// Names of the categories per language, read from a TOML file such as:
+///
+/// ```toml
+/// ["."]                 # the root of the collection
+/// en = "Vendor symbols"
+///
+/// [relays]              # matches the relays folder, wherever it is
+/// en = "Relays"
+/// fr = "Relais"
+///
+/// ["relays/safety"]     # matches only this folder
+/// en = "Safety relays"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CategoryNames(BTreeMap<String, BTreeMap<String, String>>);
+
+impl CategoryNames {
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid mapping file.
+    pub fn load_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read category names file: {}", path.display()))?;
+        toml::from_str(&content)
+            .context(format!("Invalid category names file: {}", path.display()))
+    }
+
+    /// The names of the category at `rel_dir`, relative to the collection root. The
+    /// folder name is used when the mapping doesn't name it.
+    #[must_use]
+    pub fn names_for(&self, rel_dir: &Path, root: &Path) -> BTreeMap<String, String> {
+        let key = slash_path(rel_dir);
+        let folder = if key == "." {
+            root.file_name()
+        } else {
+            rel_dir.file_name()
+        }
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+        let mapped = self.0.get(&key).or_else(|| self.0.get(&folder));
+        match mapped {
+            Some(names) if !names.is_empty() => names.clone(),
+            _ => BTreeMap::from([(DEFAULT_LANG.to_string(), folder.replace(['_', '-'], " "))]),
+        }
+    }
+}
+
+//the relative folder with / separators on every platform, "." for the root
+fn slash_path(rel_dir: &Path) -> String {
+    let parts: Vec<_> = rel_dir
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+/// Where a batch conversion writes a QET element collection
+#[derive(Debug, Default, Clone)]
+pub struct CollectionOptions {
+    /// Root folder of the collection, the input folders are mirrored below it
+    pub root: PathBuf,
+    pub names: CategoryNames,
+}
+
+/// Expands the inputs like [`collect_inputs`], pairing every .dxf file with the folder of
+/// the collection it goes into, relative to the root. Files found in a given directory keep
+/// their sub folder, files given explicitly go into the root.
+///
+/// # Errors
+///
+/// Returns an error if a glob pattern is invalid or a directory can't be read.
+pub fn collection_inputs(
+    paths: &[PathBuf],
+    options: &BatchOptions,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = BTreeMap::new();
+    for path in paths {
+        for file in collect_inputs(std::slice::from_ref(path), options)? {
+            let rel_dir = if path.is_dir() {
+                file.parent()
+                    .and_then(|dir| dir.strip_prefix(path).ok())
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default()
+            } else {
+                PathBuf::new()
+            };
+            files.entry(file).or_insert(rel_dir);
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+impl CollectionOptions {
+    /// Writes the `qet_directory` file of the root and of every folder leading to `rel_dirs`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a folder or a file can't be written.
+    pub fn write_categories<'a>(&self, rel_dirs: impl Iterator<Item = &'a Path>) -> Result<()> {
+        let mut dirs = BTreeSet::from([PathBuf::new()]);
+        for rel_dir in rel_dirs {
+            dirs.extend(rel_dir.ancestors().map(Path::to_path_buf));
+        }
+
+        for rel_dir in dirs {
+            let dir = self.root.join(&rel_dir);
+            std::fs::create_dir_all(&dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+
+            let mut names_xml = XMLElement::new("names");
+            for (lang, name) in self.names.names_for(&rel_dir, &self.root) {
+                let mut translation = XMLElement::new("name");
+                translation.add_attribute("lang", lang);
+                translation.add_text(name);
+                names_xml.add_child(translation);
+            }
+            let mut dir_xml = XMLElement::new("qet-directory");
+            dir_xml.add_child(names_xml);
+
+            let path = dir.join(QET_DIRECTORY_FILE);
+            let file = File::create(&path)
+                .context(format!("Failed to create {}", path.display()))?;
+            dir_xml
+                .write(file)
+                .context(format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts the inputs into a QET element collection below `collection.root`, mirroring
+/// the folders of the inputs and writing a `qet_directory` file in each of them.
+///
+/// # Errors
+///
+/// Returns an error if the inputs can't be listed, the collection folders can't be
+/// written or the thread pool can't be created. Files that fail to convert are reported
+/// in the summary instead.
+pub fn convert_collection(
+    paths: &[PathBuf],
+    options: &ConversionOptions,
+    batch_options: &BatchOptions,
+    collection: &CollectionOptions,
+) -> Result<BatchSummary> {
+    //relative output folders are resolved against each input file, the collection root
+    //is relative to where we are started from instead
+    let collection = CollectionOptions {
+        root: std::path::absolute(&collection.root).context(format!(
+            "Invalid collection directory: {}",
+            collection.root.display()
+        ))?,
+        names: collection.names.clone(),
+    };
+
+    let inputs = collection_inputs(paths, batch_options)?;
+    collection.write_categories(inputs.iter().map(|(_, rel_dir)| rel_dir.as_path()))?;
+
+    let files: Vec<PathBuf> = inputs.iter().map(|(file, _)| file.clone()).collect();
+    let rel_dirs: HashMap<&Path, &Path> = inputs
+        .iter()
+        .map(|(file, rel_dir)| (file.as_path(), rel_dir.as_path()))
+        .collect();
+    convert_batch_with(&files, batch_options, |file| {
+        let mut options = options.clone();
+        let rel_dir = rel_dirs.get(file).copied().unwrap_or(Path::new(""));
+        options.output_dir = Some(collection.root.join(rel_dir));
+        Cow::Owned(options)
+    })
+}
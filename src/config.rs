@@ -0,0 +1,462 @@
+use crate::qelmt::{GridAnchor, GridSnap, ScaleMode, TextMode};
+use crate::split::SplitMode;
+use crate::ConversionOptions;
+use anyhow::{bail, Context, Result};
+use globset::Glob;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the project level config file, looked up from the current directory upwards
+pub const PROJECT_CONFIG_FILE: &str = "dxf2elmt.toml";
+
+/// A set of conversion settings, every field is optional so presets can be layered
+/// on top of each other and only override what they set
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Preset {
+    pub px_per_mm: Option<f64>,
+    /// `ratio`, `width:<px>`, `height:<px>`, `fit:<width>x<height>` or `pitch:<grid steps>`
+    pub scale_mode: Option<ScaleMode>,
+    /// Point moved onto the 10 px grid: `hotspot`, `terminal` or `corner`
+    pub grid_anchor: Option<GridAnchor>,
+    /// Primitives whose coordinates are snapped to the grid
+    pub grid_snap: Option<Vec<GridSnap>>,
+    pub spline_step: Option<u32>,
+    pub text_mode: Option<TextMode>,
+    pub info: Option<bool>,
+    pub include_layers: Option<Vec<String>>,
+    pub exclude_layers: Option<Vec<String>>,
+    /// Skip layers that are off, frozen or not plotted
+    pub skip_hidden_layers: Option<bool>,
+    /// Layer glob pattern -> QET style string
+    pub layer_styles: Option<BTreeMap<String, String>>,
+    pub terminal_layers: Option<Vec<String>>,
+    pub terminal_blocks: Option<Vec<String>>,
+    /// Block glob patterns whose inserts are skipped
+    pub exclude_blocks: Option<Vec<String>>,
+    /// Output directory, relative paths are resolved against the folder of each input file
+    pub output_dir: Option<PathBuf>,
+    /// `none`, `blocks`, `layers` or `clusters`, how the drawing is turned into elements
+    pub split: Option<SplitMode>,
+    /// Block glob patterns to convert when splitting by blocks
+    pub blocks: Option<Vec<String>>,
+    /// Maximum distance in drawing units between entities of the same cluster
+    pub cluster_gap: Option<f64>,
+    /// Only replace the graphics of .elmt files that already exist
+    pub update: Option<bool>,
+    /// Move the new graphics so the top left corner of their bounding box matches the one of
+    /// the updated element. `align-hotspot`, its old name, is still read
+    #[serde(alias = "align-hotspot")]
+    pub align_corner: Option<bool>,
+}
+
+/// How many files the desktop app lists as recently opened
+pub const RECENT_FILES: usize = 10;
+
+/// State of the desktop app kept between sessions, the CLI ignores it
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DesktopSettings {
+    pub verbose: Option<bool>,
+    /// The px/mm ratio as typed in the window, `px-per-mm` keeps its value for the CLI
+    pub ratio_px: Option<f64>,
+    pub ratio_mm: Option<f64>,
+    pub last_input_dir: Option<PathBuf>,
+    pub last_output_dir: Option<PathBuf>,
+    /// Most recent first
+    pub recent_files: Vec<PathBuf>,
+}
+
+/// Contents of a config file: the base settings, plus any number of named presets
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Preset used when none is asked for explicitly
+    pub default_preset: Option<String>,
+    #[serde(flatten)]
+    pub base: Preset,
+    pub presets: BTreeMap<String, Preset>,
+    #[serde(skip_serializing_if = "DesktopSettings::is_empty")]
+    pub desktop: DesktopSettings,
+}
+
+impl DesktopSettings {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Moves `path` to the top of the recent files, dropping the oldest past [`RECENT_FILES`]
+    pub fn add_recent(&mut self, path: &Path) {
+        self.recent_files.retain(|p| p != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES);
+    }
+}
+
+impl Preset {
+    /// Overrides the settings of `self` with the ones set in `other`
+    pub fn merge(&mut self, other: &Preset) {
+        fn set<T: Clone>(dst: &mut Option<T>, src: Option<&T>) {
+            if let Some(src) = src {
+                *dst = Some(src.clone());
+            }
+        }
+
+        set(&mut self.px_per_mm, other.px_per_mm.as_ref());
+        set(&mut self.scale_mode, other.scale_mode.as_ref());
+        set(&mut self.grid_anchor, other.grid_anchor.as_ref());
+        set(&mut self.grid_snap, other.grid_snap.as_ref());
+        set(&mut self.spline_step, other.spline_step.as_ref());
+        set(&mut self.text_mode, other.text_mode.as_ref());
+        set(&mut self.info, other.info.as_ref());
+        set(&mut self.include_layers, other.include_layers.as_ref());
+        set(&mut self.exclude_layers, other.exclude_layers.as_ref());
+        set(&mut self.skip_hidden_layers, other.skip_hidden_layers.as_ref());
+        set(&mut self.layer_styles, other.layer_styles.as_ref());
+        set(&mut self.terminal_layers, other.terminal_layers.as_ref());
+        set(&mut self.terminal_blocks, other.terminal_blocks.as_ref());
+        set(&mut self.exclude_blocks, other.exclude_blocks.as_ref());
+        set(&mut self.output_dir, other.output_dir.as_ref());
+        set(&mut self.split, other.split.as_ref());
+        set(&mut self.blocks, other.blocks.as_ref());
+        set(&mut self.cluster_gap, other.cluster_gap.as_ref());
+        set(&mut self.update, other.update.as_ref());
+        set(&mut self.align_corner, other.align_corner.as_ref());
+    }
+
+    /// Writes the settings that are set into `options`
+    pub fn apply(&self, options: &mut ConversionOptions) {
+        if let Some(px_per_mm) = self.px_per_mm {
+            options.px_per_mm = px_per_mm;
+        }
+        if let Some(scale_mode) = self.scale_mode {
+            options.scale_mode = scale_mode;
+        }
+        if let Some(anchor) = self.grid_anchor {
+            options.grid_anchor = Some(anchor);
+        }
+        if let Some(snap) = &self.grid_snap {
+            options.grid_snap.clone_from(snap);
+        }
+        if let Some(spline_step) = self.spline_step {
+            options.spline_step = spline_step;
+        }
+        if let Some(text_mode) = self.text_mode {
+            options.text_mode = text_mode;
+        }
+        if let Some(info) = self.info {
+            options.info = info;
+        }
+        if let Some(layers) = &self.include_layers {
+            options.include_layers.clone_from(layers);
+        }
+        if let Some(layers) = &self.exclude_layers {
+            options.exclude_layers.clone_from(layers);
+        }
+        if let Some(skip) = self.skip_hidden_layers {
+            options.skip_hidden_layers = skip;
+        }
+        if let Some(styles) = &self.layer_styles {
+            options.layer_styles.clone_from(styles);
+        }
+        if let Some(layers) = &self.terminal_layers {
+            options.terminal_layers.clone_from(layers);
+        }
+        if let Some(blocks) = &self.terminal_blocks {
+            options.terminal_blocks.clone_from(blocks);
+        }
+        if let Some(blocks) = &self.exclude_blocks {
+            options.exclude_blocks.clone_from(blocks);
+        }
+        if let Some(dir) = &self.output_dir {
+            options.output_dir = Some(dir.clone());
+        }
+        if let Some(split) = self.split {
+            options.split = split;
+        }
+        if let Some(blocks) = &self.blocks {
+            options.block_filter.clone_from(blocks);
+        }
+        if let Some(gap) = self.cluster_gap {
+            options.cluster_gap = gap;
+        }
+        if let Some(update) = self.update {
+            options.update = update;
+        }
+        if let Some(align) = self.align_corner {
+            options.align_corner = align;
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if a value is out of range, a pattern isn't a valid glob or a
+    /// setting only used by another split mode than the one set.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(px_per_mm) = self.px_per_mm {
+            if !px_per_mm.is_finite() || px_per_mm <= 0.0 {
+                bail!("px-per-mm must be a positive number, got {px_per_mm}");
+            }
+        }
+        if let Some(mode) = self.scale_mode {
+            //presets built in code skip the parsing that checks the sizes of a config file
+            mode.to_string()
+                .parse::<ScaleMode>()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid scale-mode")?;
+        }
+        if let Some(snap) = &self.grid_snap {
+            if let Some((_, dup)) = snap.iter().enumerate().find(|(i, kind)| snap[..*i].contains(kind)) {
+                bail!("grid-snap lists {dup} more than once");
+            }
+        }
+        if self.spline_step == Some(0) {
+            bail!("spline-step must be at least 1");
+        }
+        if let Some(gap) = self.cluster_gap {
+            if !gap.is_finite() || gap < 0.0 {
+                bail!("cluster-gap can't be negative, got {gap}");
+            }
+        }
+        if let Some(split) = self.split {
+            if self.blocks.is_some() && split != SplitMode::Blocks {
+                bail!("blocks is only used with split = \"blocks\", got split = \"{split}\"");
+            }
+            if self.cluster_gap.is_some() && split != SplitMode::Clusters {
+                bail!("cluster-gap is only used with split = \"clusters\", got split = \"{split}\"");
+            }
+        }
+
+        let patterns = [
+            &self.include_layers,
+            &self.exclude_layers,
+            &self.terminal_layers,
+            &self.terminal_blocks,
+            &self.exclude_blocks,
+            &self.blocks,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .chain(self.layer_styles.iter().flat_map(BTreeMap::keys));
+        for pat in patterns {
+            Glob::new(pat).context(format!("Invalid glob pattern: {pat}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, isn't valid TOML or has invalid settings.
+    pub fn load_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read config file: {}", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .context(format!("Invalid config file: {}", path.display()))?;
+
+        config
+            .base
+            .validate()
+            .context(format!("Invalid config file: {}", path.display()))?;
+        for (name, preset) in &config.presets {
+            preset.validate().context(format!(
+                "Invalid preset {name} in config file: {}",
+                path.display()
+            ))?;
+        }
+        Ok(config)
+    }
+
+    /// Loads the user config and then the project config found from the current directory
+    /// (or `path` instead of the project config when given), the later one overriding the first.
+    /// Missing files are simply skipped, except for an explicit `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the files exists but can't be loaded.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+
+        if let Some(user) = user_config_path().filter(|p| p.is_file()) {
+            config.merge(Config::load_file(&user)?);
+        }
+
+        let project = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|dir| find_project_config(&dir)),
+        };
+        if let Some(project) = project {
+            config.merge(Config::load_file(&project)?);
+        }
+
+        Ok(config)
+    }
+
+    /// Loads the user config alone, to change it and save it back without copying the
+    /// project settings into it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be loaded.
+    pub fn load_user() -> Result<Self> {
+        match user_config_path().filter(|p| p.is_file()) {
+            Some(path) => Config::load_file(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Overrides `self` with everything set in `other`, presets with the same name are merged
+    pub fn merge(&mut self, other: Config) {
+        if other.default_preset.is_some() {
+            self.default_preset = other.default_preset;
+        }
+        self.base.merge(&other.base);
+        for (name, preset) in other.presets {
+            self.presets.entry(name).or_default().merge(&preset);
+        }
+        if !other.desktop.is_empty() {
+            self.desktop = other.desktop;
+        }
+    }
+
+    #[must_use]
+    pub fn preset_names(&self) -> Vec<&str> {
+        self.presets.keys().map(String::as_str).collect()
+    }
+
+    /// The base settings with the preset `name` (or the default preset) applied on top.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no preset with that name.
+    pub fn resolve(&self, name: Option<&str>) -> Result<Preset> {
+        let mut settings = self.base.clone();
+
+        if let Some(name) = name.or(self.default_preset.as_deref()) {
+            let Some(preset) = self.presets.get(name) else {
+                let known = self.preset_names().join(", ");
+                bail!("Unknown preset {name}, available presets: {known}");
+            };
+            settings.merge(preset);
+        }
+        Ok(settings)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the directory or the file can't be written.
+    pub fn save_file(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let content = toml::to_string_pretty(self).context("Failed to serialize the config")?;
+        std::fs::write(path, content)
+            .context(format!("Failed to write config file: {}", path.display()))
+    }
+
+    /// Saves into [`user_config_path`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system has no config directory or the file can't be written.
+    pub fn save_user(&self) -> Result<()> {
+        let Some(path) = user_config_path() else {
+            bail!("No config directory found for the current user");
+        };
+        self.save_file(&path)
+    }
+}
+
+/// `<config dir>/dxf2elmt/config.toml`, e.g. `~/.config/dxf2elmt/config.toml` on Linux
+#[must_use]
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("dxf2elmt").join("config.toml"))
+}
+
+/// Looks for a [`PROJECT_CONFIG_FILE`] in `start` and each of its parents
+#[must_use]
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(toml: &str) -> Preset {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn merge_only_overrides_what_the_other_preset_sets() {
+        let mut base = preset("px-per-mm = 2.0\nspline-step = 10\nupdate = true");
+        base.merge(&preset("spline-step = 30\nupdate = false"));
+        assert_eq!(base.px_per_mm, Some(2.0));
+        assert_eq!(base.spline_step, Some(30));
+        assert_eq!(base.update, Some(false));
+    }
+
+    #[test]
+    fn config_merge_merges_presets_with_the_same_name() {
+        let mut config: Config =
+            toml::from_str("default-preset = \"a\"\n[presets.a]\ntext-mode = \"static\"").unwrap();
+        config.merge(toml::from_str("[presets.a]\nsplit = \"Layers\"\n[presets.b]\ninfo = true").unwrap());
+        assert_eq!(config.default_preset.as_deref(), Some("a"));
+        assert_eq!(config.presets["a"].text_mode, Some(TextMode::Static));
+        assert_eq!(config.presets["a"].split, Some(SplitMode::Layers));
+        assert_eq!(config.resolve(Some("b")).unwrap().info, Some(true));
+        assert!(config.resolve(Some("c")).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_settings_of_another_split_mode() {
+        assert!(preset("split = \"blocks\"\nblocks = [\"SYM_*\"]").validate().is_ok());
+        assert!(preset("split = \"clusters\"\ncluster-gap = 2.0").validate().is_ok());
+        assert!(preset("split = \"layers\"\nblocks = [\"SYM_*\"]").validate().is_err());
+        assert!(preset("split = \"blocks\"\ncluster-gap = 2.0").validate().is_err());
+        //the split mode may come from the command line
+        assert!(preset("cluster-gap = 2.0").validate().is_ok());
+        assert!(toml::from_str::<Preset>("split = \"rows\"").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_scale_modes_without_a_positive_size() {
+        assert!(preset("scale-mode = \"fit:100x50\"").validate().is_ok());
+        assert!(toml::from_str::<Preset>("scale-mode = \"width:-3\"").is_err());
+        let built = Preset {
+            scale_mode: Some(ScaleMode::Height(0.0)),
+            ..Preset::default()
+        };
+        assert!(built.validate().is_err());
+    }
+
+    #[test]
+    fn validate_parses_and_checks_the_grid_options() {
+        let grid = preset("grid-anchor = \"Terminal\"\ngrid-snap = [\"terminals\", \"LINES\"]");
+        assert_eq!(grid.grid_anchor, Some(GridAnchor::Terminal));
+        assert_eq!(grid.grid_snap, Some(vec![GridSnap::Terminals, GridSnap::Lines]));
+        assert!(grid.validate().is_ok());
+        assert!(preset("grid-snap = [\"texts\", \"texts\"]").validate().is_err());
+        assert!(toml::from_str::<Preset>("grid-anchor = \"centre\"").is_err());
+        assert!(toml::from_str::<Preset>("grid-snap = [\"circles\"]").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values_and_bad_globs() {
+        assert!(preset("px-per-mm = 0.0").validate().is_err());
+        assert!(preset("spline-step = 0").validate().is_err());
+        assert!(preset("cluster-gap = -1.0").validate().is_err());
+        assert!(preset("exclude-layers = [\"DIM[\"]").validate().is_err());
+        assert!(preset("px-per-mm = 4.0\nexclude-layers = [\"DIM*\"]").validate().is_ok());
+    }
+}
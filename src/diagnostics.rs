@@ -0,0 +1,185 @@
+use crate::qelmt::{Definition, Objects, ScaleEntity, GRID_STEP};
+use crate::validate::{validate_elmt, Severity};
+use crate::ConversionStats;
+use anyhow::Result;
+use serde::Serialize;
+use simple_xml_builder::XMLElement;
+use std::fmt::Display;
+
+/// Left, top, right and bottom of an object in element coordinates
+pub type Bounds = (f64, f64, f64, f64);
+
+/// Something worth knowing about a converted element, tied to the object it is about when
+/// there is one so it can be pointed out in a preview
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub bounds: Option<Bounds>,
+    /// Line of the .elmt file, for the problems found validating it
+    pub line: Option<u32>,
+}
+
+impl Diagnostic {
+    #[must_use]
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            bounds: None,
+            line: None,
+        }
+    }
+
+    #[must_use]
+    fn at(self, obj: &Objects) -> Self {
+        Self {
+            bounds: Some(bounds(obj)),
+            ..self
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}: line {line}: {}", self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+fn bounds(obj: &Objects) -> Bounds {
+    (
+        obj.left_bound(),
+        obj.top_bound(),
+        obj.right_bound(),
+        obj.bot_bound(),
+    )
+}
+
+//the primitives in the order they are written to the .elmt, groups flattened
+fn primitives(definition: &Definition) -> Vec<&Objects> {
+    definition
+        .description
+        .objects
+        .iter()
+        .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+        .filter(|obj| !matches!(obj, Objects::Group(_)))
+        .collect()
+}
+
+fn tag(obj: &Objects) -> &'static str {
+    match obj {
+        Objects::Arc(_) => "arc",
+        Objects::Ellipse(_) => "ellipse",
+        Objects::Polygon(_) => "polygon",
+        Objects::DynamicText(_) => "dynamic_text",
+        Objects::Text(_) => "text",
+        Objects::Line(_) => "line",
+        Objects::Terminal(_) => "terminal",
+        Objects::Group(_) => "group",
+    }
+}
+
+//the primitive a validation issue is about, from its location e.g. `definition/description/line[3]`
+fn located<'a>(prims: &[&'a Objects], location: &str) -> Option<&'a Objects> {
+    let (tag_name, index) = location
+        .strip_prefix("definition/description/")?
+        .strip_suffix(']')?
+        .split_once('[')?;
+    let index: usize = index.parse().ok()?;
+    prims
+        .iter()
+        .filter(|obj| tag(obj) == tag_name)
+        .nth(index.checked_sub(1)?)
+        .copied()
+}
+
+fn off_grid(v: f64) -> bool {
+    (v / GRID_STEP - (v / GRID_STEP).round()).abs() > 1e-6
+}
+
+/// The problems of `definition` found validating its XML, the terminals QET can't connect
+/// wires to, the entities that were skipped and the texts it has, as the log of a conversion
+///
+/// # Errors
+///
+/// Returns an error if the generated XML can't be parsed back, which would be a bug.
+pub fn diagnose(
+    definition: &Definition,
+    stats: Option<&ConversionStats>,
+) -> Result<Vec<Diagnostic>> {
+    let prims = primitives(definition);
+    let mut diagnostics: Vec<Diagnostic> =
+        validate_elmt(&XMLElement::from(definition).to_string())?
+            .into_iter()
+            .map(|issue| {
+                let diagnostic = Diagnostic {
+                    line: Some(issue.line),
+                    ..Diagnostic::new(
+                        issue.severity,
+                        format!("{}: {}", issue.location, issue.message),
+                    )
+                };
+                match located(&prims, &issue.location) {
+                    Some(obj) => diagnostic.at(obj),
+                    None => diagnostic,
+                }
+            })
+            .collect();
+
+    if let Some(stats) = stats.filter(|s| s.unsupported > 0) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            format!("{} unsupported entities were skipped", stats.unsupported),
+        ));
+    }
+    if let Some(stats) = stats.filter(|s| s.grid_displacement > 0.0) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Info,
+            format!(
+                "aligning to the grid moved points up to {:.2} px",
+                stats.grid_displacement
+            ),
+        ));
+    }
+    if definition.terminals().next().is_none() {
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            "element has no terminals, wires can't be connected to it",
+        ));
+    }
+
+    for obj in prims {
+        let diagnostic = match obj {
+            Objects::Terminal(term) if off_grid(term.x) || off_grid(term.y) => Diagnostic::new(
+                Severity::Warning,
+                format!(
+                    "terminal at ({}, {}) is off the {GRID_STEP} px grid",
+                    term.x, term.y
+                ),
+            ),
+            Objects::Text(txt) => Diagnostic::new(
+                Severity::Info,
+                format!(
+                    "text \"{}\" at ({:.1}, {:.1}), {:.1} pt",
+                    txt.value, txt.x, txt.y, txt.font.point_size
+                ),
+            ),
+            Objects::DynamicText(dtxt) => Diagnostic::new(
+                Severity::Info,
+                format!(
+                    "dynamic text \"{}\" at ({:.1}, {:.1}), {:.1} pt",
+                    dtxt.text.replace('\n', "\\n"),
+                    dtxt.x,
+                    dtxt.y,
+                    dtxt.font.point_size
+                ),
+            ),
+            _ => continue,
+        };
+        diagnostics.push(diagnostic.at(obj));
+    }
+    Ok(diagnostics)
+}
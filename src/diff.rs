@@ -0,0 +1,358 @@
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Removed,
+    Added,
+    Changed,
+}
+
+/// A single semantic difference between two .elmt files
+#[derive(Debug, Clone, Serialize)]
+pub struct Difference {
+    pub kind: ChangeKind,
+    /// Where the difference is, e.g. `definition/description/line[3]`
+    pub location: String,
+    pub detail: String,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = match self.kind {
+            ChangeKind::Removed => '-',
+            ChangeKind::Added => '+',
+            ChangeKind::Changed => '~',
+        };
+        write!(f, "{sign} {}: {}", self.location, self.detail)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            //both files are written with rounded coordinates, so only tiny differences are noise
+            (Self::Number(a), Self::Number(b)) => (a - b).abs() <= 1e-6 * a.abs().max(b.abs()).max(1.0),
+            (Self::Text(a), Self::Text(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Text(t) => write!(f, "\"{t}\""),
+        }
+    }
+}
+
+//an xml element with everything that doesn't change the meaning of the element taken out:
+//uuids are dropped, attributes are sorted and numbers are compared by value
+#[derive(Debug, Clone)]
+struct Elem {
+    tag: String,
+    attrs: BTreeMap<String, Value>,
+    text: String,
+    children: Vec<Elem>,
+}
+
+impl PartialEq for Elem {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+            && self.attrs == other.attrs
+            && self.text == other.text
+            && self.children == other.children
+    }
+}
+
+impl From<Node<'_, '_>> for Elem {
+    fn from(node: Node) -> Self {
+        Elem {
+            tag: node.tag_name().name().to_string(),
+            attrs: node
+                .attributes()
+                .filter(|a| a.name() != "uuid")
+                .map(|a| {
+                    let value = match a.value().trim().parse::<f64>() {
+                        Ok(n) => Value::Number(n),
+                        Err(_) => Value::Text(a.value().to_string()),
+                    };
+                    (a.name().to_string(), value)
+                })
+                .collect(),
+            text: node
+                .children()
+                .filter(Node::is_text)
+                .filter_map(|n| n.text())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            children: node.children().filter(Node::is_element).map(Elem::from).collect(),
+        }
+    }
+}
+
+impl Display for Elem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}", self.tag)?;
+        for (name, value) in &self.attrs {
+            write!(f, " {name}={value}")?;
+        }
+        if self.text.is_empty() {
+            write!(f, ">")
+        } else {
+            write!(f, ">{}", self.text)
+        }
+    }
+}
+
+//path step of each element, with the 1 based index among the siblings with the same tag
+//when there is more than one of them, e.g. `names` or `line[3]`
+fn sibling_names(elems: &[Elem]) -> Vec<String> {
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for e in elems {
+        *totals.entry(&e.tag).or_default() += 1;
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    elems
+        .iter()
+        .map(|e| {
+            let count = counts.entry(&e.tag).or_default();
+            *count += 1;
+            if totals[e.tag.as_str()] > 1 {
+                format!("{}[{count}]", e.tag)
+            } else {
+                e.tag.clone()
+            }
+        })
+        .collect()
+}
+
+struct Differ {
+    diffs: Vec<Difference>,
+}
+
+impl Differ {
+    fn push(&mut self, kind: ChangeKind, location: String, detail: String) {
+        self.diffs.push(Difference {
+            kind,
+            location,
+            detail,
+        });
+    }
+
+    fn elem(&mut self, location: &str, a: &Elem, b: &Elem) {
+        let names: BTreeSet<&String> = a.attrs.keys().chain(b.attrs.keys()).collect();
+        for name in names {
+            match (a.attrs.get(name), b.attrs.get(name)) {
+                (Some(va), Some(vb)) if va != vb => self.push(
+                    ChangeKind::Changed,
+                    location.to_string(),
+                    format!("{name}: {va} -> {vb}"),
+                ),
+                (Some(va), None) => self.push(
+                    ChangeKind::Removed,
+                    location.to_string(),
+                    format!("attribute {name}={va}"),
+                ),
+                (None, Some(vb)) => self.push(
+                    ChangeKind::Added,
+                    location.to_string(),
+                    format!("attribute {name}={vb}"),
+                ),
+                _ => {}
+            }
+        }
+
+        if a.text != b.text {
+            self.push(
+                ChangeKind::Changed,
+                location.to_string(),
+                format!("text: \"{}\" -> \"{}\"", a.text, b.text),
+            );
+        }
+
+        self.children(location, &a.children, &b.children);
+    }
+
+    //matches the children with a longest common subsequence so a single added or removed
+    //primitive doesn't show up as every following primitive being changed
+    fn children(&mut self, location: &str, old: &[Elem], new: &[Elem]) {
+        let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let names_old = sibling_names(old);
+        let names_new = sibling_names(new);
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                self.gap(location, old, new, &names_old, &names_new, &mut removed, &mut added);
+                i += 1;
+                j += 1;
+            } else if j >= new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                removed.push(i);
+                i += 1;
+            } else {
+                added.push(j);
+                j += 1;
+            }
+        }
+        self.gap(location, old, new, &names_old, &names_new, &mut removed, &mut added);
+    }
+
+    //elements that are in between two matched elements: the ones with the same tag are
+    //most likely the same element that was modified, the rest was really removed or added
+    #[allow(clippy::too_many_arguments)]
+    fn gap(
+        &mut self,
+        location: &str,
+        a: &[Elem],
+        b: &[Elem],
+        names_a: &[String],
+        names_b: &[String],
+        removed: &mut Vec<usize>,
+        added: &mut Vec<usize>,
+    ) {
+        let mut added_left: Vec<Option<usize>> = added.drain(..).map(Some).collect();
+        for i in removed.drain(..) {
+            let loc_a = format!("{location}/{}", names_a[i]);
+            let pair = added_left
+                .iter_mut()
+                .find(|j| j.is_some_and(|j| b[j].tag == a[i].tag))
+                .and_then(Option::take);
+            match pair {
+                Some(j) => self.elem(&loc_a, &a[i], &b[j]),
+                None => self.push(ChangeKind::Removed, loc_a, a[i].to_string()),
+            }
+        }
+        for j in added_left.into_iter().flatten() {
+            let loc_b = format!("{location}/{}", names_b[j]);
+            self.push(ChangeKind::Added, loc_b, b[j].to_string());
+        }
+    }
+}
+
+/// Compares two .elmt files by meaning: uuids and the order of attributes are ignored and
+/// numbers are compared by value, so `2` and `2.00` are the same.
+///
+/// Returns an empty list when both files describe the same element.
+///
+/// # Errors
+///
+/// Returns an error if one of the files isn't well formed XML.
+pub fn diff_elmt(old: &str, new: &str) -> Result<Vec<Difference>> {
+    let old = Document::parse(old).context("Old file is not a valid XML document")?;
+    let new = Document::parse(new).context("New file is not a valid XML document")?;
+    let old = Elem::from(old.root_element());
+    let new = Elem::from(new.root_element());
+
+    let mut differ = Differ { diffs: Vec::new() };
+    if old.tag == new.tag {
+        differ.elem(&old.tag, &old, &new);
+    } else {
+        differ.push(
+            ChangeKind::Changed,
+            old.tag.clone(),
+            format!("root element: {} -> {}", old.tag, new.tag),
+        );
+    }
+    Ok(differ.diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(description: &str) -> String {
+        format!(
+            r#"<definition type="element" width="20" height="20"><description>{description}</description></definition>"#
+        )
+    }
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        diff_elmt(&element(old), &element(new))
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn uuids_attribute_order_and_number_formats_are_ignored() {
+        let old = r#"<terminal x="2" y="0" orientation="n" uuid="{0b6e0c46-7f5b-4b4c-9a57-6a2f0f0f4b11}"/>"#;
+        let new = r#"<terminal orientation="n" y="0.00" x="2.0" uuid="{8a4ff1ba-1bca-4a3c-a24c-0ee0d8e1e9a1}"/>"#;
+        assert!(diff(old, new).is_empty());
+    }
+
+    #[test]
+    fn numbers_are_compared_with_a_relative_tolerance() {
+        let line = |x: &str| format!(r#"<line x1="{x}" y1="0" x2="0" y2="0"/>"#);
+        //large coordinates get a tolerance relative to their size
+        assert!(diff(&line("1000000"), &line("1000000.5")).is_empty());
+        //small ones an absolute one of 1e-6
+        assert!(diff(&line("0"), &line("0.0000001")).is_empty());
+        assert_eq!(
+            diff(&line("1"), &line("1.00001")),
+            ["~ definition/description/line: x1: 1 -> 1.00001"]
+        );
+    }
+
+    #[test]
+    fn children_are_matched_so_an_insertion_is_a_single_difference() {
+        let lines = |xs: &[u32]| {
+            xs.iter()
+                .map(|x| format!(r#"<line x1="{x}" y1="0" x2="0" y2="0"/>"#))
+                .collect::<Vec<_>>()
+                .concat()
+        };
+        assert_eq!(
+            diff(&lines(&[1, 2, 3]), &lines(&[9, 1, 2, 3])),
+            ["+ definition/description/line[1]: <line x1=9 x2=0 y1=0 y2=0>"]
+        );
+        assert_eq!(
+            diff(&lines(&[1, 2, 3]), &lines(&[1, 3])),
+            ["- definition/description/line[2]: <line x1=2 x2=0 y1=0 y2=0>"]
+        );
+        //an element replaced by one with the same tag is reported as changed
+        assert_eq!(
+            diff(&lines(&[1, 2, 3]), &lines(&[1, 5, 3])),
+            ["~ definition/description/line[2]: x1: 2 -> 5"]
+        );
+    }
+
+    #[test]
+    fn replaced_elements_of_another_tag_are_removed_and_added() {
+        let old = r#"<line x1="0" y1="0" x2="1" y2="0"/><text x="0" y="0">A</text>"#;
+        let new = r#"<line x1="0" y1="0" x2="1" y2="0"/><input x="0" y="0"/>"#;
+        assert_eq!(
+            diff(old, new),
+            [
+                "- definition/description/text: <text x=0 y=0>A",
+                "+ definition/description/input: <input x=0 y=0>",
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::qelmt::{Definition, Objects, ScaleEntity, TermOrient};
+use crate::{friendly_name, ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{Context, Result};
+use dxf::entities::{Entity, EntityType, Insert, Line};
+use dxf::enums::{AcadVersion, Units};
+use dxf::tables::Layer;
+use dxf::{Block, Color, Drawing, Point};
+use std::path::Path;
+use std::time::Instant;
+
+/// Layers the primitives are written on, one per type of primitive
+pub const LINE_LAYER: &str = "QET_LINES";
+pub const ARC_LAYER: &str = "QET_ARCS";
+pub const ELLIPSE_LAYER: &str = "QET_ELLIPSES";
+pub const POLYGON_LAYER: &str = "QET_POLYGONS";
+pub const TEXT_LAYER: &str = "QET_TEXTS";
+pub const DYNAMIC_TEXT_LAYER: &str = "QET_DYNAMIC_TEXTS";
+pub const TERMINAL_LAYER: &str = "QET_TERMINALS";
+
+/// Block inserted for every terminal, pointing up when not rotated. Listing it in the
+/// `terminal-blocks` setting turns the inserts back into terminals.
+pub const TERMINAL_BLOCK: &str = "QET_TERMINAL";
+
+//length of the stub drawn by the terminal block, in mm
+const TERMINAL_LENGTH: f64 = 3.0;
+
+/// Builds a drawing in millimetres out of an element, the reverse of the conversion
+#[must_use]
+pub fn element_to_drawing(mut definition: Definition, px_per_mm: f64) -> Drawing {
+    let mut drawing = Drawing::new();
+    //R12 has no lwpolylines or ellipses
+    drawing.header.version = AcadVersion::R2000;
+    drawing.header.default_drawing_units = Units::Millimeters;
+
+    for (name, color) in [
+        (LINE_LAYER, 7),
+        (ARC_LAYER, 1),
+        (ELLIPSE_LAYER, 2),
+        (POLYGON_LAYER, 3),
+        (TEXT_LAYER, 4),
+        (DYNAMIC_TEXT_LAYER, 5),
+        (TERMINAL_LAYER, 6),
+    ] {
+        drawing.add_layer(Layer {
+            name: name.into(),
+            color: Color::from_index(color),
+            ..Layer::default()
+        });
+    }
+
+    let mut terminal = Block {
+        name: TERMINAL_BLOCK.into(),
+        layer: TERMINAL_LAYER.into(),
+        ..Block::default()
+    };
+    let mut stub = Entity::new(EntityType::Line(Line::new(
+        Point::origin(),
+        Point::new(0.0, TERMINAL_LENGTH, 0.0),
+    )));
+    stub.common.layer = TERMINAL_LAYER.into();
+    terminal.entities.push(stub);
+    drawing.add_block(terminal);
+
+    definition
+        .description
+        .scale(1.0 / px_per_mm, 1.0 / px_per_mm);
+    for obj in &definition.description.objects {
+        for obj in std::iter::once(obj).chain(obj.descendants()) {
+            let (layer, specific) = match obj {
+                Objects::Line(line) => (LINE_LAYER, EntityType::Line(line.into())),
+                Objects::Arc(arc) => (ARC_LAYER, arc.into()),
+                Objects::Ellipse(ell) => (ELLIPSE_LAYER, ell.into()),
+                Objects::Polygon(poly) => (POLYGON_LAYER, EntityType::LwPolyline(poly.into())),
+                Objects::Text(txt) => (TEXT_LAYER, EntityType::Text(txt.into())),
+                Objects::DynamicText(dtxt) => (DYNAMIC_TEXT_LAYER, dtxt.into()),
+                Objects::Terminal(term) => {
+                    let insert = Insert {
+                        name: TERMINAL_BLOCK.into(),
+                        location: Point::new(term.x, -term.y, 0.0),
+                        //the inverse of TermOrient::from_rotation
+                        rotation: match term.orientation {
+                            TermOrient::North => 0.0,
+                            TermOrient::West => 90.0,
+                            TermOrient::South => 180.0,
+                            TermOrient::East => 270.0,
+                        },
+                        ..Insert::default()
+                    };
+                    (TERMINAL_LAYER, EntityType::Insert(insert))
+                }
+                Objects::Group(_) => continue,
+            };
+            let mut ent = Entity::new(specific);
+            ent.common.layer = layer.into();
+            drawing.add_entity(ent);
+        }
+    }
+
+    drawing
+}
+
+/// Converts an .elmt file back into a .dxf file next to it, or into the output directory
+///
+/// # Errors
+///
+/// Returns an error if the element can't be read or the drawing can't be written.
+pub fn export_elmt(file_path: &Path, options: &ConversionOptions) -> Result<ConversionResult> {
+    let now = Instant::now();
+    let friendly_file_name = friendly_name(file_path);
+
+    let definition = Definition::read_file(file_path)?;
+    let drawing = element_to_drawing(definition, options.px_per_mm);
+
+    let out_path = options.output_path(file_path).with_extension("dxf");
+    if let Some(dir) = out_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).context("Could not create output directory")?;
+    }
+    drawing
+        .save_file(&out_path)
+        .context(format!("Failed to write {}", out_path.display()))?;
+
+    let mut stats = ConversionStats::from_entities(drawing.entities());
+    stats.elapsed_ms = now.elapsed().as_millis();
+
+    Ok(ConversionResult {
+        success: true,
+        message: format!(
+            "Successfully exported {friendly_file_name} to {}",
+            out_path.display()
+        ),
+        stats: Some(stats),
+        xml_content: None,
+    })
+}
@@ -1,25 +1,37 @@
 extern crate tempfile;
 
 use anyhow::Context;
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use tempfile::tempfile;
+use tracing::info;
 
+/// Creates the .elmt for `out_name` and its folder, or a temporary file when the output
+/// is only shown
+///
+/// # Errors
+///
+/// Returns an error if the folder or the file can't be created.
 pub fn create_file(
     verbose_output: bool,
     _info: bool,
     file_name: &Path,
+    out_name: &Path,
 ) -> Result<File, anyhow::Error> {
     let old_file_name = file_name.to_string_lossy();
 
-    let mut file_name = PathBuf::from(file_name);
+    let mut file_name = PathBuf::from(out_name);
     file_name.set_extension("elmt");
 
     let friendly_file_name = file_name.to_string_lossy();
     let mut out_file = tempfile().context("Could not create temporary file");
     if !verbose_output {
+        if let Some(dir) = file_name.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Could not create output directory")?;
+        }
         out_file = File::create(&file_name).context("Could not create output file");
-        println!("{friendly_file_name} was created... \nNow converting {old_file_name}...",);
+        //this runs on the worker threads of batch and watch mode, so it must not go to stdout
+        info!("{friendly_file_name} was created from {old_file_name}");
     }
 
     out_file.context("Could not return output file")
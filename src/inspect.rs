@@ -0,0 +1,323 @@
+use crate::qelmt::{Description, ScaleEntity};
+use crate::ConversionOptions;
+use dxf::entities::{Entity, EntityType};
+use dxf::{Block, Drawing};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerInfo {
+    pub name: String,
+    /// ACI colour index, `None` for true colours or by block/layer
+    pub color: Option<u8>,
+    pub line_type: String,
+    /// False for layers that are off or frozen
+    pub is_on: bool,
+    pub is_plotted: bool,
+    pub entities: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockInfo {
+    pub name: String,
+    pub entities: usize,
+    /// Number of INSERTs of this block, in the drawing and in other blocks
+    pub inserts: usize,
+    /// Levels of blocks inserted inside this one, 0 when it inserts no other block
+    pub depth: usize,
+    pub base_point: (f64, f64),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextStyleInfo {
+    pub name: String,
+    pub font: String,
+    pub height: f64,
+    pub width_factor: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Extents {
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+}
+
+/// Summary of what is inside a drawing, to decide on the conversion options before converting
+#[derive(Debug, Clone, Serialize)]
+pub struct DrawingReport {
+    pub version: String,
+    pub units: String,
+    /// Extents stored in the header, often stale or missing
+    pub header_extents: Extents,
+    /// Extents of the entities that can be converted, in drawing units
+    pub extents: Option<Extents>,
+    pub layers: Vec<LayerInfo>,
+    pub blocks: Vec<BlockInfo>,
+    /// Number of entities in model space by entity type
+    pub entities: BTreeMap<String, usize>,
+    /// Number of entities the converter skips by entity type, in model space and in blocks
+    pub unsupported: BTreeMap<String, usize>,
+    pub text_styles: Vec<TextStyleInfo>,
+    pub line_types: Vec<String>,
+}
+
+//formats only the variant name of the entity's Debug output, stopping the formatting at
+//the first '(' so the whole entity doesn't have to be written out
+struct VariantName(String);
+
+impl fmt::Write for VariantName {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(end) = s.find(['(', ' ', '{']) {
+            self.0.push_str(&s[..end]);
+            Err(fmt::Error)
+        } else {
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+}
+
+/// Name of the entity type, e.g. `Line` or `LwPolyline`
+#[must_use]
+pub fn entity_type_name(ent: &Entity) -> String {
+    let mut name = VariantName(String::new());
+    let _ = write!(name, "{:?}", ent.specific);
+    name.0
+}
+
+/// Whether the converter turns entities of this type into something. Points are left out,
+/// they only become terminals on terminal layers.
+#[must_use]
+pub fn is_supported(ent: &Entity) -> bool {
+    matches!(
+        ent.specific,
+        EntityType::Arc(_)
+            | EntityType::AttributeDefinition(_)
+            | EntityType::Circle(_)
+            | EntityType::Ellipse(_)
+            | EntityType::Insert(_)
+            | EntityType::Leader(_)
+            | EntityType::Line(_)
+            | EntityType::LwPolyline(_)
+            | EntityType::MText(_)
+            | EntityType::Polyline(_)
+            | EntityType::Solid(_)
+            | EntityType::Spline(_)
+            | EntityType::Text(_)
+    )
+}
+
+/// Levels of nested blocks inside every block, a block inserting itself further down
+/// counts until the loop closes
+#[must_use]
+pub fn block_depths(drw: &Drawing) -> HashMap<String, usize> {
+    fn depth<'a>(
+        block: &'a Block,
+        blocks: &HashMap<&str, &'a Block>,
+        depths: &mut HashMap<String, usize>,
+        path: &mut Vec<&'a str>,
+    ) -> usize {
+        if let Some(d) = depths.get(&block.name) {
+            return *d;
+        }
+        if path.contains(&block.name.as_str()) {
+            return 0;
+        }
+
+        path.push(&block.name);
+        let d = block
+            .entities
+            .iter()
+            .filter_map(|ent| match &ent.specific {
+                EntityType::Insert(ins) => blocks.get(ins.name.as_str()).copied(),
+                _ => None,
+            })
+            .map(|inner| 1 + depth(inner, blocks, depths, path))
+            .max()
+            .unwrap_or(0);
+        path.pop();
+        depths.insert(block.name.clone(), d);
+        d
+    }
+
+    let blocks: HashMap<&str, &Block> = drw.blocks().map(|b| (b.name.as_str(), b)).collect();
+    let mut depths = HashMap::new();
+    for block in drw.blocks() {
+        depth(block, &blocks, &mut depths, &mut Vec::new());
+    }
+    depths
+}
+
+/// The layers of the drawing with the number of model space entities on each of them
+#[must_use]
+pub fn layer_infos(drw: &Drawing) -> Vec<LayerInfo> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for ent in drw.entities() {
+        *counts.entry(&ent.common.layer).or_default() += 1;
+    }
+
+    drw.layers()
+        .map(|l| LayerInfo {
+            name: l.name.clone(),
+            color: l.color.index(),
+            line_type: l.line_type_name.clone(),
+            is_on: l.is_layer_on,
+            is_plotted: l.is_layer_plotted,
+            entities: counts.get(l.name.as_str()).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+impl DrawingReport {
+    #[must_use]
+    pub fn new(drw: &Drawing) -> Self {
+        let mut entities: BTreeMap<String, usize> = BTreeMap::new();
+        let mut unsupported: BTreeMap<String, usize> = BTreeMap::new();
+        let mut inserts: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for ent in drw.entities() {
+            *entities.entry(entity_type_name(ent)).or_default() += 1;
+        }
+        for ent in drw.entities().chain(drw.blocks().flat_map(|b| b.entities.iter())) {
+            if let EntityType::Insert(ins) = &ent.specific {
+                *inserts.entry(&ins.name).or_default() += 1;
+            }
+            if !is_supported(ent) {
+                *unsupported.entry(entity_type_name(ent)).or_default() += 1;
+            }
+        }
+        let depths = block_depths(drw);
+
+        //the default options have no patterns that could fail to compile
+        let description = Description {
+            objects: Description::entity_objects(drw, &ConversionOptions::default())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, obj)| obj)
+                .collect(),
+        };
+        //the description has y pointing down, flip it back into drawing coordinates
+        let extents = (!description.objects.is_empty()).then(|| Extents {
+            min: (description.left_bound(), -description.bot_bound()),
+            max: (description.right_bound(), -description.top_bound()),
+        });
+
+        let min = &drw.header.minimum_drawing_extents;
+        let max = &drw.header.maximum_drawing_extents;
+        Self {
+            version: format!("{:?}", drw.header.version),
+            units: format!("{:?}", drw.header.default_drawing_units),
+            header_extents: Extents {
+                min: (min.x, min.y),
+                max: (max.x, max.y),
+            },
+            extents,
+            layers: layer_infos(drw),
+            blocks: drw
+                .blocks()
+                .map(|b| BlockInfo {
+                    name: b.name.clone(),
+                    entities: b.entities.len(),
+                    inserts: inserts.get(b.name.as_str()).copied().unwrap_or(0),
+                    depth: depths.get(&b.name).copied().unwrap_or(0),
+                    base_point: (b.base_point.x, b.base_point.y),
+                })
+                .collect(),
+            entities,
+            unsupported,
+            text_styles: drw
+                .styles()
+                .map(|s| TextStyleInfo {
+                    name: s.name.clone(),
+                    font: s.primary_font_file_name.clone(),
+                    height: s.text_height,
+                    width_factor: s.width_factor,
+                })
+                .collect(),
+            line_types: drw.line_types().map(|lt| lt.name.clone()).collect(),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns any error from writing to `out`.
+    pub fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        fn extents(ext: &Extents) -> String {
+            format!(
+                "({:.2}, {:.2}) - ({:.2}, {:.2}), {:.2} x {:.2}",
+                ext.min.0,
+                ext.min.1,
+                ext.max.0,
+                ext.max.1,
+                ext.max.0 - ext.min.0,
+                ext.max.1 - ext.min.1
+            )
+        }
+
+        writeln!(out, "Version: {}", self.version)?;
+        writeln!(out, "Units:   {}", self.units)?;
+        writeln!(out, "Extents (header):   {}", extents(&self.header_extents))?;
+        match &self.extents {
+            Some(ext) => writeln!(out, "Extents (entities): {}", extents(ext))?,
+            None => writeln!(out, "Extents (entities): no convertible entities")?,
+        }
+
+        writeln!(out, "\nEntities:")?;
+        for (name, count) in &self.entities {
+            writeln!(out, "  {name:<20} {count:>6}")?;
+        }
+
+        if !self.unsupported.is_empty() {
+            writeln!(out, "\nUnsupported entities (skipped):")?;
+            for (name, count) in &self.unsupported {
+                writeln!(out, "  {name:<20} {count:>6}")?;
+            }
+        }
+
+        writeln!(out, "\nLayers:")?;
+        writeln!(
+            out,
+            "  {:<24} {:>5} {:<16} {:<4} {:<5} {:>8}",
+            "NAME", "COLOR", "LINETYPE", "ON", "PLOT", "ENTITIES"
+        )?;
+        for l in &self.layers {
+            writeln!(
+                out,
+                "  {:<24} {:>5} {:<16} {:<4} {:<5} {:>8}",
+                l.name,
+                l.color.map_or_else(|| "-".to_string(), |c| c.to_string()),
+                l.line_type,
+                if l.is_on { "yes" } else { "no" },
+                if l.is_plotted { "yes" } else { "no" },
+                l.entities
+            )?;
+        }
+
+        writeln!(out, "\nBlocks:")?;
+        writeln!(
+            out,
+            "  {:<24} {:>8} {:>7} {:>5}",
+            "NAME", "ENTITIES", "INSERTS", "DEPTH"
+        )?;
+        for b in &self.blocks {
+            writeln!(
+                out,
+                "  {:<24} {:>8} {:>7} {:>5}",
+                b.name, b.entities, b.inserts, b.depth
+            )?;
+        }
+
+        writeln!(out, "\nText styles:")?;
+        for s in &self.text_styles {
+            writeln!(
+                out,
+                "  {:<24} font={} height={:.2} width={:.2}",
+                s.name, s.font, s.height, s.width_factor
+            )?;
+        }
+
+        writeln!(out, "\nLine types: {}", self.line_types.join(", "))
+    }
+}
@@ -5,18 +5,36 @@
 
 pub mod qelmt;
 pub mod file_writer;
+pub mod batch;
+pub mod collection;
+pub mod watch;
+pub mod config;
+pub mod inspect;
+pub mod validate;
+pub mod diff;
+pub mod project;
+pub mod split;
+pub mod titleblock;
+pub mod export;
+pub mod svg;
+pub mod progress;
+pub mod metadata;
+pub mod diagnostics;
 
-use anyhow::{Context, Result};
-use dxf::entities::EntityType;
+use anyhow::{bail, Context, Result};
+use dxf::entities::{Entity, EntityType};
 use dxf::Drawing;
-use qelmt::{Definition, Objects};
+use progress::{Phase, ProgressHooks};
+use qelmt::{Definition, GridAnchor, GridSnap, Objects, ScaleMode, TextMode};
+use split::SplitMode;
 use simple_xml_builder::XMLElement;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ConversionStats {
     pub circles: u32,
     pub lines: u32,
@@ -30,6 +48,9 @@ pub struct ConversionStats {
     pub blocks: u32,
     pub unsupported: u32,
     pub elapsed_ms: u128,
+    // Lo que más se ha movido un punto al alinear a la rejilla, en píxeles
+    #[serde(default)]
+    pub grid_displacement: f64,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -40,11 +61,52 @@ pub struct ConversionResult {
     pub xml_content: Option<String>,
 }
 
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ConversionOptions {
     pub spline_step: u32,
     pub verbose: bool,
     pub info: bool,
     pub px_per_mm: f64, // Relación píxeles por milímetro (por defecto: 2.0 px/mm)
+    // Cómo se dimensiona el elemento: con la relación px/mm, a un ancho, alto o caja dados, o
+    // con los bornes más cercanos a un número de pasos de la rejilla
+    pub scale_mode: ScaleMode,
+    // Punto que se lleva a la rejilla de QET moviendo todo el elemento, None no lo alinea
+    pub grid_anchor: Option<GridAnchor>,
+    // Tipos de primitiva cuyas coordenadas se redondean a la rejilla
+    pub grid_snap: Vec<GridSnap>,
+    // Textos dinámicos (editables en QET) o estáticos
+    pub text_mode: TextMode,
+    // Patrones glob de capas a convertir, vacío significa todas
+    pub include_layers: Vec<String>,
+    // Patrones glob de capas a ignorar
+    pub exclude_layers: Vec<String>,
+    // Ignorar las capas apagadas, congeladas o que no se imprimen
+    pub skip_hidden_layers: bool,
+    // Patrón glob de capa -> estilo QET ("line-style:dashed;line-weight:thin;...")
+    pub layer_styles: BTreeMap<String, String>,
+    // Capas cuyos puntos, círculos e inserciones se convierten en bornes
+    pub terminal_layers: Vec<String>,
+    // Bloques cuyas inserciones se convierten en bornes
+    pub terminal_blocks: Vec<String>,
+    // Patrones glob de bloques cuyas inserciones se ignoran
+    pub exclude_blocks: Vec<String>,
+    // Carpeta de salida, relativa a la del DXF si no es absoluta. None = junto al DXF
+    pub output_dir: Option<PathBuf>,
+    // Un elemento por dibujo, o varios: por definición de bloque, por capa o por grupos cercanos
+    pub split: SplitMode,
+    // Patrones glob de los bloques a convertir al dividir por bloques, vacío significa todos
+    pub block_filter: Vec<String>,
+    // Distancia máxima, en unidades del DXF, entre entidades del mismo grupo al dividir por grupos
+    pub cluster_gap: f64,
+    // Si el .elmt de salida ya existe, solo se reemplaza su geometría: se conservan el uuid, los
+    // nombres, los bornes, los textos con info_name y las informaciones del elemento
+    pub update: bool,
+    // Al actualizar, mueve la geometría nueva para que la esquina superior izquierda de su caja
+    // coincida con la del elemento
+    pub align_corner: bool,
+    // Avisos de progreso y token para cancelar la conversión desde otro hilo
+    pub progress: ProgressHooks,
 }
 
 impl Default for ConversionOptions {
@@ -54,142 +116,460 @@ impl Default for ConversionOptions {
             verbose: false,
             info: false,
             px_per_mm: 2.0, // Por defecto: 2px / 1mm
+            scale_mode: ScaleMode::default(),
+            grid_anchor: None,
+            grid_snap: Vec::new(),
+            text_mode: TextMode::default(),
+            include_layers: Vec::new(),
+            exclude_layers: Vec::new(),
+            skip_hidden_layers: true,
+            layer_styles: BTreeMap::new(),
+            terminal_layers: Vec::new(),
+            terminal_blocks: Vec::new(),
+            exclude_blocks: Vec::new(),
+            output_dir: None,
+            split: SplitMode::default(),
+            block_filter: Vec::new(),
+            cluster_gap: 5.0,
+            update: false,
+            align_corner: false,
+            progress: ProgressHooks::default(),
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// Path of the .elmt file written for `file_path`, taking `output_dir` into account
+    #[must_use]
+    pub fn output_path(&self, file_path: &Path) -> PathBuf {
+        let elmt = file_path.with_extension("elmt");
+        let Some(dir) = &self.output_dir else {
+            return elmt;
+        };
+
+        let dir = if dir.is_absolute() {
+            dir.clone()
+        } else {
+            file_path.parent().unwrap_or(Path::new("")).join(dir)
+        };
+        match elmt.file_name() {
+            Some(name) => dir.join(name),
+            None => elmt,
         }
     }
+
+    /// Path of the .elmt file for the element `name` split out of `file_path`, it goes
+    /// into the same directory as [`Self::output_path`] and is prefixed by the drawing
+    /// name, so drawings with blocks of the same name don't overwrite each other
+    #[must_use]
+    pub fn element_path(&self, file_path: &Path, name: &str) -> PathBuf {
+        let elmt = self.output_path(file_path);
+        let stem = elmt.file_stem().unwrap_or_default().to_string_lossy();
+        elmt.with_file_name(format!("{stem}-{name}.elmt"))
+    }
+}
+
+/// Input path that makes the converter read the drawing from stdin instead of a file
+pub const STDIN_PATH: &str = "-";
+
+#[must_use]
+pub fn is_stdin_path(file_path: &Path) -> bool {
+    file_path.as_os_str() == STDIN_PATH
+}
+
+// Nombre amigable del elemento: el nombre del archivo sin extensión, o "stdin"
+pub(crate) fn friendly_name(file_path: &Path) -> String {
+    if is_stdin_path(file_path) {
+        return "stdin".to_string();
+    }
+    file_path
+        .file_stem()
+        .unwrap_or(file_path.as_os_str())
+        .to_string_lossy()
+        .to_string()
 }
 
+/// Loads a drawing from `file_path`, or from stdin when the path is `-`.
+/// Both ASCII and binary .dxf files are supported.
+///
+/// Frozen layers are marked as off, so everything that skips hidden layers skips them too.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or isn't a valid .dxf drawing.
+pub fn load_drawing(file_path: &Path) -> Result<Drawing> {
+    let (bytes, mut drawing) = if is_stdin_path(file_path) {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .context("Failed to read DXF from stdin")?;
+        let drawing = Drawing::load(&mut bytes.as_slice())
+            .context("Failed to load DXF from stdin...\n\tMake sure the input is a valid .dxf file.")?;
+        (bytes, drawing)
+    } else {
+        let friendly_file_name = friendly_name(file_path);
+        let bytes = std::fs::read(file_path)
+            .context(format!("Failed to read {}", file_path.display()))?;
+        let drawing = Drawing::load(&mut bytes.as_slice()).context(format!(
+            "Failed to load {friendly_file_name}...\n\tMake sure the file is a valid .dxf file.",
+        ))?;
+        (bytes, drawing)
+    };
+
+    let frozen = frozen_layers(&bytes);
+    for layer in drawing.layers_mut() {
+        if frozen.contains(&layer.name) {
+            layer.is_layer_on = false;
+        }
+    }
+    Ok(drawing)
+}
+
+// El crate dxf no lee los flags de las capas (código 70), así que buscamos las capas
+// congeladas (bit 1) recorriendo la tabla LAYER nosotros mismos. Solo en DXF ASCII.
+fn frozen_layers(bytes: &[u8]) -> HashSet<String> {
+    let mut frozen = HashSet::new();
+    if bytes.starts_with(b"AutoCAD Binary DXF") {
+        return frozen;
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text.lines().map(str::trim);
+    let mut in_layer_table = false;
+    let mut last_pair = (0, "");
+    let mut layer: Option<(String, i32)> = None;
+    while let (Some(code), Some(value)) = (lines.next(), lines.next()) {
+        let Ok(code) = code.parse::<i32>() else {
+            break;
+        };
+
+        if code == 0 {
+            if let Some((name, flags)) = layer.take() {
+                if flags & 1 != 0 {
+                    frozen.insert(name);
+                }
+            }
+            match value {
+                "ENDTAB" if in_layer_table => break,
+                "LAYER" if in_layer_table => layer = Some((String::new(), 0)),
+                _ => {}
+            }
+        } else if code == 2 && value == "LAYER" && last_pair == (0, "TABLE") {
+            in_layer_table = true;
+        } else if let Some((name, flags)) = layer.as_mut() {
+            match code {
+                2 => *name = value.to_string(),
+                70 => *flags = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        last_pair = (code, value);
+    }
+    frozen
+}
+
+impl ConversionStats {
+    /// Counts the entity types found in the drawing, `elapsed_ms` is left at 0
+    #[must_use]
+    pub fn from_drawing(drawing: &Drawing) -> Self {
+        Self::from_entities(drawing.entities())
+    }
+
+    /// Adds the counts and the time of `other` to these stats, keeping the largest grid displacement
+    pub fn add(&mut self, other: &ConversionStats) {
+        self.circles += other.circles;
+        self.lines += other.lines;
+        self.arcs += other.arcs;
+        self.splines += other.splines;
+        self.texts += other.texts;
+        self.ellipses += other.ellipses;
+        self.polylines += other.polylines;
+        self.lwpolylines += other.lwpolylines;
+        self.solids += other.solids;
+        self.blocks += other.blocks;
+        self.unsupported += other.unsupported;
+        self.elapsed_ms += other.elapsed_ms;
+        self.grid_displacement = self.grid_displacement.max(other.grid_displacement);
+    }
+
+    /// Counts the entity types of `entities`, `elapsed_ms` is left at 0
+    #[must_use]
+    pub fn from_entities<'a>(entities: impl Iterator<Item = &'a Entity>) -> Self {
+        let mut stats = ConversionStats::default();
+
+        // Loop through all entities, counting the element types
+        entities.for_each(|e| match e.specific {
+            EntityType::Circle(_) => stats.circles += 1,
+            EntityType::Line(_) => stats.lines += 1,
+            EntityType::Arc(_) => stats.arcs += 1,
+            EntityType::Spline(_) => stats.splines += 1,
+            EntityType::Text(_) => stats.texts += 1,
+            EntityType::Ellipse(_) => stats.ellipses += 1,
+            EntityType::Polyline(_) => stats.polylines += 1,
+            EntityType::LwPolyline(_) => stats.lwpolylines += 1,
+            EntityType::Solid(_) => stats.solids += 1,
+            EntityType::Insert(_) => stats.blocks += 1,
+            _ => stats.unsupported += 1,
+        });
+
+        stats
+    }
+}
+
+/// Converts `file_path` into a .elmt, with a .log of its texts next to it. Drawings that
+/// `options.split` divides become several elements.
+///
+/// # Errors
+///
+/// Returns an error if the drawing can't be read, the conversion is cancelled or the
+/// output files can't be written.
 pub fn convert_dxf_file(
     file_path: &Path,
     options: &ConversionOptions,
 ) -> Result<ConversionResult> {
+    if options.split != SplitMode::None {
+        return split::convert_split(file_path, options);
+    }
+
     let now = Instant::now();
-    let friendly_file_name = file_path
-        .file_stem()
-        .unwrap_or_else(|| file_path.as_os_str())
-        .to_string_lossy()
-        .to_string();
+    let friendly_file_name = friendly_name(file_path);
+    let progress = &options.progress;
 
     // Load DXF file
-    let drawing = Drawing::load_file(file_path).context(format!(
-        "Failed to load {friendly_file_name}...\n\tMake sure the file is a valid .dxf file.",
-    ))?;
-
-    let q_elmt = Definition::new(friendly_file_name.clone(), options.spline_step, options.px_per_mm, &drawing);
-
-    // Initialize counts
-    let mut circle_count: u32 = 0;
-    let mut line_count: u32 = 0;
-    let mut arc_count: u32 = 0;
-    let mut spline_count: u32 = 0;
-    let mut text_count: u32 = 0;
-    let mut ellipse_count: u32 = 0;
-    let mut polyline_count: u32 = 0;
-    let mut lwpolyline_count: u32 = 0;
-    let mut solid_count: u32 = 0;
-    let mut block_count: u32 = 0;
-    let mut other_count: u32 = 0;
-
-    // Loop through all entities, counting the element types
-    drawing.entities().for_each(|e| match e.specific {
-        EntityType::Circle(_) => circle_count += 1,
-        EntityType::Line(_) => line_count += 1,
-        EntityType::Arc(_) => arc_count += 1,
-        EntityType::Spline(_) => spline_count += 1,
-        EntityType::Text(_) => text_count += 1,
-        EntityType::Ellipse(_) => ellipse_count += 1,
-        EntityType::Polyline(_) => polyline_count += 1,
-        EntityType::LwPolyline(_) => lwpolyline_count += 1,
-        EntityType::Solid(_) => solid_count += 1,
-        EntityType::Insert(_) => block_count += 1,
-        _ => other_count += 1,
-    });
+    progress.phase(Phase::Loading);
+    let drawing = load_drawing(file_path)?;
+    progress.check()?;
+
+    let q_elmt = Definition::new(friendly_file_name.clone(), options, &drawing)?;
+    // La construcción se corta al cancelar, el elemento a medias no se escribe
+    progress.check()?;
+    let grid_displacement = q_elmt.grid_displacement();
+    let q_elmt = if is_stdin_path(file_path) {
+        q_elmt
+    } else {
+        let q_elmt = updated_element(q_elmt, &options.output_path(file_path), options)?;
+        with_metadata(q_elmt, file_path)?
+    };
+    let mut stats = ConversionStats::from_drawing(&drawing);
+    stats.grid_displacement = grid_displacement;
 
     // Generate XML
+    progress.phase(Phase::Serializing);
     let out_xml = XMLElement::from(&q_elmt);
-    let xml_content = if options.verbose {
-        Some(format!("{}", out_xml))
+    // Desde stdin no hay ruta de la que derivar el .elmt, así que solo devolvemos el XML
+    let to_memory = options.verbose || is_stdin_path(file_path);
+    let xml_content = if to_memory {
+        Some(out_xml.to_string())
     } else {
         None
     };
 
-    let elapsed_ms = now.elapsed().as_millis();
-    let stats = ConversionStats {
-        circles: circle_count,
-        lines: line_count,
-        arcs: arc_count,
-        splines: spline_count,
-        texts: text_count,
-        ellipses: ellipse_count,
-        polylines: polyline_count,
-        lwpolylines: lwpolyline_count,
-        solids: solid_count,
-        blocks: block_count,
-        unsupported: other_count,
-        elapsed_ms,
-    };
+    stats.elapsed_ms = now.elapsed().as_millis();
 
     // Create output file if not verbose
-    if !options.verbose {
-        let out_file = file_writer::create_file(false, options.info, file_path)?;
+    if !to_memory {
+        progress.check()?;
+        progress.phase(Phase::Writing);
+        let out_path = options.output_path(file_path);
+        let out_file = file_writer::create_file(false, options.info, file_path, &out_path)?;
         out_xml
             .write(&out_file)
             .context("Failed to write output file.")?;
         
-        // Crear archivo de log con información de textos convertidos
-        write_text_log(file_path, &q_elmt.description, &stats)?;
+        // Crear archivo de log con información de textos convertidos, junto al .elmt
+        write_text_log(file_path, &out_path.with_extension("log"), &q_elmt.description, &stats)?;
     }
 
     Ok(ConversionResult {
         success: true,
-        message: format!("Successfully converted {}", friendly_file_name),
+        message: format!("Successfully converted {friendly_file_name}"),
         stats: Some(stats),
         xml_content,
     })
 }
 
-// Función para escribir el archivo de log con información de textos convertidos
-fn write_text_log(file_path: &Path, description: &qelmt::Description, stats: &ConversionStats) -> Result<()> {
-    // Crear ruta del archivo de log
-    let log_path = file_path.with_extension("log");
-    let mut log_file = File::create(&log_path)
-        .context(format!("Failed to create log file: {}", log_path.display()))?;
-    
-    // Contar entidades convertidas en ELMT
-    let mut elmt_circles = 0u32;
-    let mut elmt_lines = 0u32;
-    let mut elmt_arcs = 0u32;
-    let mut elmt_polygons = 0u32;
-    let mut elmt_dynamic_texts = 0u32;
-    let mut elmt_texts = 0u32;
-    let mut elmt_groups = 0u32;
-    
-    fn count_objects_recursive(objects: &[qelmt::Objects], 
-        circles: &mut u32, lines: &mut u32, arcs: &mut u32, 
-        polygons: &mut u32, dynamic_texts: &mut u32, texts: &mut u32, groups: &mut u32) {
+/// Converts `file_path` (or stdin when the path is `-`) and writes only the
+/// .elmt XML document to `writer`. No .elmt or .log file is created, which makes
+/// it suitable for streaming the result to stdout in a pipeline.
+///
+/// # Errors
+///
+/// Returns an error if the drawing can't be loaded or the XML can't be written.
+pub fn convert_dxf_to_writer<W: Write>(
+    file_path: &Path,
+    options: &ConversionOptions,
+    writer: W,
+) -> Result<ConversionResult> {
+    if options.split != SplitMode::None {
+        bail!("Splitting into several elements can't be combined with streaming a single element");
+    }
+
+    let now = Instant::now();
+    let friendly_file_name = friendly_name(file_path);
+
+    let progress = &options.progress;
+    progress.phase(Phase::Loading);
+    let drawing = load_drawing(file_path)?;
+    progress.check()?;
+
+    let q_elmt = Definition::new(friendly_file_name.clone(), options, &drawing)?;
+    progress.check()?;
+    let grid_displacement = q_elmt.grid_displacement();
+    let q_elmt = if is_stdin_path(file_path) {
+        q_elmt
+    } else {
+        let q_elmt = updated_element(q_elmt, &options.output_path(file_path), options)?;
+        with_metadata(q_elmt, file_path)?
+    };
+    let mut stats = ConversionStats::from_drawing(&drawing);
+    stats.grid_displacement = grid_displacement;
+
+    progress.phase(Phase::Writing);
+    XMLElement::from(&q_elmt)
+        .write(writer)
+        .context("Failed to write XML output.")?;
+    stats.elapsed_ms = now.elapsed().as_millis();
+
+    Ok(ConversionResult {
+        success: true,
+        message: format!("Successfully converted {friendly_file_name}"),
+        stats: Some(stats),
+        xml_content: None,
+    })
+}
+
+// En modo actualización, fusiona el elemento convertido con el .elmt que ya existe en out_path
+pub(crate) fn updated_element(
+    definition: Definition,
+    out_path: &Path,
+    options: &ConversionOptions,
+) -> Result<Definition> {
+    if !options.update || !out_path.exists() {
+        return Ok(definition);
+    }
+    let existing = Definition::read_file(out_path)?;
+    Ok(existing.merge_geometry(definition, options.align_corner))
+}
+
+// Aplica los metadatos guardados junto al DXF, si los hay, por encima de lo que conserva el modo actualización
+pub(crate) fn with_metadata(mut definition: Definition, file_path: &Path) -> Result<Definition> {
+    if let Some(metadata) = metadata::ElementMetadata::load(file_path)? {
+        definition.apply_metadata(&metadata);
+    }
+    Ok(definition)
+}
+
+// Los metadatos describen un único elemento, al dividir el DXF no se sabe a cuál aplicarlos
+pub(crate) fn warn_unused_metadata(file_path: &Path) {
+    let sidecar = metadata::sidecar_path(file_path);
+    if sidecar.is_file() {
+        tracing::warn!(
+            "{} is ignored, element metadata only applies when the drawing becomes a single element",
+            sidecar.display()
+        );
+    }
+}
+
+// Objetos convertidos en el ELMT, por tipo
+#[derive(Default)]
+struct ElmtCounts {
+    circles: u32,
+    lines: u32,
+    arcs: u32,
+    polygons: u32,
+    dynamic_texts: u32,
+    texts: u32,
+    groups: u32,
+}
+
+impl ElmtCounts {
+    fn count(&mut self, objects: &[Objects]) {
         for obj in objects {
             match obj {
-                qelmt::Objects::Arc(_) => *arcs += 1,
-                qelmt::Objects::Ellipse(_) => *circles += 1, // Los círculos se convierten en elipses
-                qelmt::Objects::Polygon(_) => *polygons += 1,
-                qelmt::Objects::DynamicText(_) => *dynamic_texts += 1,
-                qelmt::Objects::Text(_) => *texts += 1,
-                qelmt::Objects::Line(_) => *lines += 1,
-                qelmt::Objects::Group(group_objects) => {
-                    *groups += 1;
-                    count_objects_recursive(group_objects, circles, lines, arcs, polygons, dynamic_texts, texts, groups);
+                Objects::Arc(_) => self.arcs += 1,
+                Objects::Ellipse(_) => self.circles += 1, // Los círculos se convierten en elipses
+                Objects::Polygon(_) => self.polygons += 1,
+                Objects::DynamicText(_) => self.dynamic_texts += 1,
+                Objects::Text(_) => self.texts += 1,
+                Objects::Line(_) => self.lines += 1,
+                Objects::Terminal(_) => {}
+                Objects::Group(group_objects) => {
+                    self.groups += 1;
+                    self.count(group_objects);
+                }
+            }
+        }
+    }
+}
+
+// Escribe el detalle de los textos de `objects`, incluidos los de los grupos
+fn write_texts(objects: &[Objects], log_file: &mut File, text_index: &mut usize) -> Result<()> {
+    for obj in objects {
+        match obj {
+            Objects::DynamicText(dtext) => {
+                writeln!(log_file, "--- Texto {text_index} (DynamicText) ---")?;
+                writeln!(log_file, "Contenido: \"{}\"", dtext.text.replace('\n', "\\n"))?;
+                writeln!(log_file, "Posición: x={:.2}, y={:.2}, z={:.2}", dtext.x, dtext.y, dtext.z)?;
+                writeln!(log_file, "Rotación: {:.2}°", dtext.rotation)?;
+                writeln!(log_file, "Tamaño de entrada (DXF): {:.2} unidades DXF", dtext.original_text_height)?;
+                writeln!(log_file, "Tamaño de salida (ELMT): {:.2}pt", dtext.font.point_size)?;
+                if dtext.original_text_height > 0.0 {
+                    writeln!(log_file, "Factor de escala texto aplicado: {:.2}", dtext.font.point_size / dtext.original_text_height)?;
                 }
+                writeln!(log_file, "Fuente: familia=\"{}\"", dtext.font.family)?;
+                writeln!(log_file, "Estilo: weight={}, style={:?}", dtext.font.weight, dtext.font.style)?;
+                writeln!(log_file, "Color: {}", dtext.color.display_rgb())?;
+                writeln!(log_file, "Alineación: H={:?}, V={:?}", dtext.h_alignment, dtext.v_alignment)?;
+                writeln!(log_file, "Ancho de referencia: {:.2}", dtext.reference_rectangle_width)?;
+                writeln!(log_file, "Frame: {}", dtext.frame)?;
+                writeln!(log_file, "UUID: {}", dtext.uuid)?;
+                if let Some(ref info_name) = dtext.info_name {
+                    writeln!(log_file, "Info name: {info_name}")?;
+                }
+                writeln!(log_file)?;
+                *text_index += 1;
+            }
+            Objects::Text(text) => {
+                writeln!(log_file, "--- Texto {text_index} (Text) ---")?;
+                writeln!(log_file, "Contenido: \"{}\"", text.value)?;
+                writeln!(log_file, "Posición: x={:.2}, y={:.2}", text.x, text.y)?;
+                writeln!(log_file, "Rotación: {:.2}°", text.rotation)?;
+                writeln!(log_file, "Tamaño de entrada (DXF): {:.2} unidades DXF", text.original_text_height)?;
+                writeln!(log_file, "Tamaño de salida (ELMT): {:.2}pt", text.font.point_size)?;
+                if text.original_text_height > 0.0 {
+                    writeln!(log_file, "Factor de escala texto aplicado: {:.2}", text.font.point_size / text.original_text_height)?;
+                }
+                writeln!(log_file, "Fuente: familia=\"{}\"", text.font.family)?;
+                writeln!(log_file, "Estilo: weight={}, style={:?}", text.font.weight, text.font.style)?;
+                writeln!(log_file, "Color: {}", text.color.display_rgb())?;
+                writeln!(log_file)?;
+                *text_index += 1;
+            }
+            Objects::Group(group_objects) => {
+                // Procesar recursivamente los objetos del grupo
+                write_texts(group_objects, log_file, text_index)?;
             }
+            _ => {} // Ignorar otros tipos de objetos
         }
     }
-    
-    count_objects_recursive(&description.objects, &mut elmt_circles, &mut elmt_lines, 
-        &mut elmt_arcs, &mut elmt_polygons, &mut elmt_dynamic_texts, &mut elmt_texts, &mut elmt_groups);
-    
+    Ok(())
+}
+
+// Función para escribir el archivo de log con información de textos convertidos
+pub(crate) fn write_text_log(file_path: &Path, log_path: &Path, description: &qelmt::Description, stats: &ConversionStats) -> Result<()> {
+    let mut log_file = File::create(log_path)
+        .context(format!("Failed to create log file: {}", log_path.display()))?;
+
+    // Contar entidades convertidas en ELMT
+    let mut elmt = ElmtCounts::default();
+    elmt.count(&description.objects);
+
     // Escribir encabezado
     writeln!(log_file, "=== Log de conversión DXF a ELMT ===")?;
     writeln!(log_file, "Archivo: {}", file_path.display())?;
     writeln!(log_file, "Tiempo de procesamiento: {} ms\n", stats.elapsed_ms)?;
-    
+
     // Estadísticas de entidades en el DXF
     writeln!(log_file, "=== ESTADÍSTICAS DE ENTIDADES EN EL ARCHIVO DXF ===")?;
     writeln!(log_file, "Círculos: {}", stats.circles)?;
@@ -203,98 +583,44 @@ fn write_text_log(file_path: &Path, description: &qelmt::Description, stats: &Co
     writeln!(log_file, "Sólidos: {}", stats.solids)?;
     writeln!(log_file, "Bloques: {}", stats.blocks)?;
     writeln!(log_file, "Entidades no soportadas: {}", stats.unsupported)?;
-    writeln!(log_file, "Total: {}\n", 
-        stats.circles + stats.lines + stats.arcs + stats.splines + stats.texts + 
-        stats.ellipses + stats.polylines + stats.lwpolylines + stats.solids + 
+    writeln!(log_file, "Total: {}\n",
+        stats.circles + stats.lines + stats.arcs + stats.splines + stats.texts +
+        stats.ellipses + stats.polylines + stats.lwpolylines + stats.solids +
         stats.blocks + stats.unsupported)?;
-    
+
     // Estadísticas de entidades convertidas en ELMT
     writeln!(log_file, "=== ESTADÍSTICAS DE ENTIDADES CONVERTIDAS EN ELMT ===")?;
-    writeln!(log_file, "Círculos/Elipses: {}", elmt_circles)?;
-    writeln!(log_file, "Líneas: {}", elmt_lines)?;
-    writeln!(log_file, "Arcos: {}", elmt_arcs)?;
-    writeln!(log_file, "Polígonos (incluye Splines y Polylines): {}", elmt_polygons)?;
-    writeln!(log_file, "Textos dinámicos: {}", elmt_dynamic_texts)?;
-    writeln!(log_file, "Textos estáticos: {}", elmt_texts)?;
-    writeln!(log_file, "Grupos (Bloques): {}", elmt_groups)?;
-    writeln!(log_file, "Total: {}\n", 
-        elmt_circles + elmt_lines + elmt_arcs + elmt_polygons + 
-        elmt_dynamic_texts + elmt_texts + elmt_groups)?;
-    
+    writeln!(log_file, "Círculos/Elipses: {}", elmt.circles)?;
+    writeln!(log_file, "Líneas: {}", elmt.lines)?;
+    writeln!(log_file, "Arcos: {}", elmt.arcs)?;
+    writeln!(log_file, "Polígonos (incluye Splines y Polylines): {}", elmt.polygons)?;
+    writeln!(log_file, "Textos dinámicos: {}", elmt.dynamic_texts)?;
+    writeln!(log_file, "Textos estáticos: {}", elmt.texts)?;
+    writeln!(log_file, "Grupos (Bloques): {}", elmt.groups)?;
+    writeln!(log_file, "Total: {}\n",
+        elmt.circles + elmt.lines + elmt.arcs + elmt.polygons +
+        elmt.dynamic_texts + elmt.texts + elmt.groups)?;
+
     // Entidades no convertidas
     if stats.unsupported > 0 {
         writeln!(log_file, "=== ADVERTENCIA: ENTIDADES NO CONVERTIDAS ===")?;
         writeln!(log_file, "Se encontraron {} entidades que no pudieron ser convertidas.\n", stats.unsupported)?;
     }
-    
+
+    if stats.grid_displacement > 0.0 {
+        writeln!(log_file, "=== ALINEACIÓN A LA REJILLA ===")?;
+        writeln!(log_file, "Desplazamiento máximo: {:.2} px\n", stats.grid_displacement)?;
+    }
+
     writeln!(log_file, "=== DETALLE DE TEXTOS CONVERTIDOS ===\n")?;
-    
-    // Contador de textos
+
+    // Procesar todos los objetos, contando los textos
     let mut text_index = 1;
-    
-    // Función recursiva para procesar objetos (incluyendo grupos)
-    fn process_objects(
-        objects: &[Objects],
-        log_file: &mut File,
-        text_index: &mut usize,
-    ) -> Result<()> {
-        for obj in objects {
-            match obj {
-                Objects::DynamicText(dtext) => {
-                    writeln!(log_file, "--- Texto {} (DynamicText) ---", text_index)?;
-                    writeln!(log_file, "Contenido: \"{}\"", dtext.text.replace('\n', "\\n"))?;
-                    writeln!(log_file, "Posición: x={:.2}, y={:.2}, z={:.2}", dtext.x, dtext.y, dtext.z)?;
-                    writeln!(log_file, "Rotación: {:.2}°", dtext.rotation)?;
-                    writeln!(log_file, "Tamaño de entrada (DXF): {:.2} unidades DXF", dtext.original_text_height)?;
-                    writeln!(log_file, "Tamaño de salida (ELMT): {:.2}pt", dtext.font.point_size)?;
-                    if dtext.original_text_height > 0.0 {
-                        writeln!(log_file, "Factor de escala texto aplicado: {:.2}", dtext.font.point_size / dtext.original_text_height)?;
-                    }
-                    writeln!(log_file, "Fuente: familia=\"{}\"", dtext.font.family)?;
-                    writeln!(log_file, "Estilo: weight={}, style={:?}", dtext.font.weight, dtext.font.style)?;
-                    writeln!(log_file, "Color: {}", dtext.color.display_rgb())?;
-                    writeln!(log_file, "Alineación: H={:?}, V={:?}", dtext.h_alignment, dtext.v_alignment)?;
-                    writeln!(log_file, "Ancho de referencia: {:.2}", dtext.reference_rectangle_width)?;
-                    writeln!(log_file, "Frame: {}", dtext.frame)?;
-                    writeln!(log_file, "UUID: {}", dtext.uuid)?;
-                    if let Some(ref info_name) = dtext.info_name {
-                        writeln!(log_file, "Info name: {}", info_name)?;
-                    }
-                    writeln!(log_file, "")?;
-                    *text_index += 1;
-                }
-                Objects::Text(text) => {
-                    writeln!(log_file, "--- Texto {} (Text) ---", text_index)?;
-                    writeln!(log_file, "Contenido: \"{}\"", text.value)?;
-                    writeln!(log_file, "Posición: x={:.2}, y={:.2}", text.x, text.y)?;
-                    writeln!(log_file, "Rotación: {:.2}°", text.rotation)?;
-                    writeln!(log_file, "Tamaño de entrada (DXF): {:.2} unidades DXF", text.original_text_height)?;
-                    writeln!(log_file, "Tamaño de salida (ELMT): {:.2}pt", text.font.point_size)?;
-                    if text.original_text_height > 0.0 {
-                        writeln!(log_file, "Factor de escala texto aplicado: {:.2}", text.font.point_size / text.original_text_height)?;
-                    }
-                    writeln!(log_file, "Fuente: familia=\"{}\"", text.font.family)?;
-                    writeln!(log_file, "Estilo: weight={}, style={:?}", text.font.weight, text.font.style)?;
-                    writeln!(log_file, "Color: {}", text.color.display_rgb())?;
-                    writeln!(log_file, "")?;
-                    *text_index += 1;
-                }
-                Objects::Group(group_objects) => {
-                    // Procesar recursivamente los objetos del grupo
-                    process_objects(group_objects, log_file, text_index)?;
-                }
-                _ => {} // Ignorar otros tipos de objetos
-            }
-        }
-        Ok(())
-    }
-    
-    // Procesar todos los objetos
-    process_objects(&description.objects, &mut log_file, &mut text_index)?;
-    
+    write_texts(&description.objects, &mut log_file, &mut text_index)?;
+
     writeln!(log_file, "=== Fin del log ===")?;
     writeln!(log_file, "Total de textos convertidos: {}", text_index - 1)?;
-    
+
     Ok(())
 }
 
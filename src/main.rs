@@ -6,10 +6,25 @@
 )]
 //#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 
-use anyhow::Result;
-use clap::Parser;
-use dxf2elmt::{convert_dxf_file, ConversionOptions};
-use std::{io, path::PathBuf};
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use dxf2elmt::batch::{collect_inputs, convert_batch, convert_file, BatchOptions};
+use dxf2elmt::collection::{convert_collection, CategoryNames, CollectionOptions};
+use dxf2elmt::config::{Config, Preset};
+use dxf2elmt::diff::diff_elmt;
+use dxf2elmt::export::export_elmt;
+use dxf2elmt::inspect::DrawingReport;
+use dxf2elmt::project::{convert_project, ProjectOptions};
+use dxf2elmt::qelmt::{GridAnchor, GridSnap, ScaleMode, TextMode};
+use dxf2elmt::split::SplitMode;
+use dxf2elmt::svg::{export_svg, SvgOptions};
+use dxf2elmt::titleblock::convert_titleblock;
+use dxf2elmt::validate::{validate_elmt, Severity};
+use dxf2elmt::watch::{DxfWatcher, WatchOptions};
+use dxf2elmt::{convert_dxf_file, convert_dxf_to_writer, is_stdin_path, load_drawing, ConversionOptions, ConversionStats};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{span, trace, Level};
 use tracing_subscriber::prelude::*;
 
@@ -19,30 +34,262 @@ use venator::Venator;
 #[derive(Parser, Debug)]
 #[command(name = "dxf2elmt")]
 #[command(author, version, about = "A CLI program to convert .dxf files into .elmt files", long_about = None)]
-struct Args {
-    /// The .dxf file to convert
+//keeps `dxf2elmt file.dxf` working without the convert subcommand, that's how QET calls us
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    convert: ConvertArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Converts .dxf files into .elmt files, the default when no subcommand is given
+    Convert(Box<ConvertArgs>),
+
+    /// Prints the layers, blocks, entity counts, units, extents and styles of a .dxf file
+    Inspect {
+        /// The .dxf file to inspect, use - to read the drawing from stdin
+        file_name: PathBuf,
+
+        /// Prints the report as JSON
+        #[clap(long, value_parser, default_value_t = false)]
+        json: bool,
+    },
+
+    /// Checks .elmt files for structural problems, exits with 1 if any file has errors
+    Validate {
+        /// The .elmt files to check
+        #[clap(required = true)]
+        file_names: Vec<PathBuf>,
+    },
+
+    /// Compares two .elmt files ignoring uuids and attribute order, exits with 1 if they differ
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+    },
+
+    /// Converts a title frame drawn in a .dxf file into a QET .titleblock template
+    Titleblock {
+        /// The .dxf file with the frame
+        file_name: PathBuf,
+
+        /// Pixels per millimetre of the drawing [default: 2]
+        #[clap(long, value_parser)]
+        px_per_mm: Option<f64>,
+
+        /// Writes the .titleblock file into this directory, relative to the input file unless absolute
+        #[clap(short, long, value_parser)]
+        output_dir: Option<PathBuf>,
+
+        /// Uses the named preset from the config file
+        #[clap(short, long, value_parser)]
+        preset: Option<String>,
+
+        /// Config file to use instead of the dxf2elmt.toml found from the current directory
+        #[clap(short, long, value_parser)]
+        config: Option<PathBuf>,
+    },
+
+    /// Renders .elmt files, or .dxf files as they would be converted, into .svg files
+    Svg {
+        /// The .elmt or .dxf files to render
+        #[clap(required = true)]
+        file_names: Vec<PathBuf>,
+
+        /// Size of an element pixel in the SVG [default: 1]
+        #[clap(long, value_parser)]
+        zoom: Option<f64>,
+
+        /// Leaves out the bounding box, the hotspot marker and the terminals
+        #[clap(long, value_parser, default_value_t = false)]
+        bare: bool,
+
+        /// Pixels per millimetre of the drawing [default: 2]
+        #[clap(long, value_parser)]
+        px_per_mm: Option<f64>,
+
+        /// Writes the .svg files into this directory, relative to each input file unless absolute
+        #[clap(short, long, value_parser)]
+        output_dir: Option<PathBuf>,
+
+        /// Uses the named preset from the config file
+        #[clap(short, long, value_parser)]
+        preset: Option<String>,
+
+        /// Config file to use instead of the dxf2elmt.toml found from the current directory
+        #[clap(short, long, value_parser)]
+        config: Option<PathBuf>,
+    },
+
+    /// Converts .elmt files back into .dxf files, one layer per type of primitive
+    Export {
+        /// The .elmt files to export
+        #[clap(required = true)]
+        file_names: Vec<PathBuf>,
+
+        /// Pixels per millimetre of the element [default: 2]
+        #[clap(long, value_parser)]
+        px_per_mm: Option<f64>,
+
+        /// Writes the .dxf files into this directory, relative to each input file unless absolute
+        #[clap(short, long, value_parser)]
+        output_dir: Option<PathBuf>,
+
+        /// Uses the named preset from the config file
+        #[clap(short, long, value_parser)]
+        preset: Option<String>,
+
+        /// Config file to use instead of the dxf2elmt.toml found from the current directory
+        #[clap(short, long, value_parser)]
+        config: Option<PathBuf>,
+    },
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(clap::Args, Debug)]
+struct ConvertArgs {
+    /// The .dxf file to convert, use - to read the drawing from stdin
     //#[clap(short, long, value_parser)]
     file_names: Vec<PathBuf>,
 
-    /// Activates verbose output, eliminates .elmt file writing
+    /// Activates verbose output, prints the .elmt XML instead of writing the file (same as --stdout)
     #[clap(short, long, value_parser, default_value_t = false)]
     verbose: bool,
 
-    /// Converts text entities into dynamic text instead of the default text box
-    #[clap(short, long, value_parser, default_value_t = false)]
+    /// Writes only the .elmt XML to stdout, stats and diagnostics go to stderr
+    #[clap(long, value_parser, default_value_t = false)]
+    stdout: bool,
+
+    /// Deprecated: texts are dynamic by default, same as --text-mode dynamic
+    #[clap(short, long, value_parser, default_value_t = false, hide = true)]
     dtext: bool,
 
-    /// Determine the number of lines you want each spline to have (more lines = greater resolution)
-    #[clap(short, long, value_parser, default_value_t = 20)]
-    spline_step: u32,
+    /// Determine the number of lines you want each spline to have (more lines = greater resolution) [default: 20]
+    #[clap(short, long, value_parser)]
+    spline_step: Option<u32>,
+
+    /// Pixels per millimetre of the drawing [default: 2]
+    #[clap(long, value_parser)]
+    px_per_mm: Option<f64>,
 
-    /// Toggles information output... defaults to off
+    /// Sizes the element by the px/mm ratio, to a width, height or box in pixels, or so the closest terminals are some grid steps apart: ratio, width:<px>, height:<px>, fit:<width>x<height> or pitch:<steps> [default: ratio]
+    #[clap(long, value_parser)]
+    scale_mode: Option<ScaleMode>,
+
+    /// Moves the element so this point lands on QET's 10 px grid: hotspot, terminal (the first one) or corner (top left of the bounding box)
+    #[clap(long, value_parser)]
+    grid_anchor: Option<GridAnchor>,
+
+    /// Snaps the coordinates of these primitives to the grid: terminals, lines, polygons, arcs, ellipses or texts (can be repeated)
+    #[clap(long, value_parser)]
+    grid_snap: Vec<GridSnap>,
+
+    /// Converts texts into dynamic or static texts [default: dynamic]
+    #[clap(long, value_parser)]
+    text_mode: Option<TextMode>,
+
+    /// Only converts entities on layers matching this glob (can be repeated)
+    #[clap(long, value_parser)]
+    include_layer: Vec<String>,
+
+    /// Skips entities on layers matching this glob (can be repeated)
+    #[clap(long, value_parser)]
+    exclude_layer: Vec<String>,
+
+    /// Skips inserts of blocks matching this glob (can be repeated)
+    #[clap(long, value_parser)]
+    exclude_block: Vec<String>,
+
+    /// Also converts entities on layers that are off, frozen or not plotted
+    #[clap(long, value_parser, default_value_t = false)]
+    keep_hidden_layers: bool,
+
+    /// Writes the .elmt files into this directory, relative to each input file unless absolute
+    #[clap(short, long, value_parser)]
+    output_dir: Option<PathBuf>,
+
+    /// Writes one .elmt per block definition, layer or cluster of entities instead of one for the whole drawing [default: none]
+    #[clap(long, value_parser)]
+    split: Option<SplitMode>,
+
+    /// Only converts the block definitions matching this glob when splitting by blocks (can be repeated)
+    #[clap(long, value_parser)]
+    block: Vec<String>,
+
+    /// Maximum distance in drawing units between entities of the same cluster [default: 5]
+    #[clap(long, value_parser)]
+    cluster_gap: Option<f64>,
+
+    /// Updates .elmt files that already exist: only the graphics are replaced, the uuid, names, terminals, info texts and element informations are kept. --update=false turns off an update set by the config
+    #[clap(long, value_parser, num_args = 0..=1, require_equals = true, default_missing_value = "true", conflicts_with = "project")]
+    update: Option<bool>,
+
+    /// Moves the new graphics so the top left corner of their bounding box lands on the one of the updated element, only used with --update
+    #[clap(long, value_parser, num_args = 0..=1, require_equals = true, default_missing_value = "true", alias = "align-hotspot")]
+    align_corner: Option<bool>,
+
+    /// Uses the named preset from the config file
+    #[clap(short, long, value_parser)]
+    preset: Option<String>,
+
+    /// Config file to use instead of the dxf2elmt.toml found from the current directory
+    #[clap(short, long, value_parser)]
+    config: Option<PathBuf>,
+
+    /// Toggles information output... defaults to off, --info=false turns it off when the config enables it
+    #[clap(short, long, value_parser, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    info: Option<bool>,
+
+    /// Walks given directories recursively when batch converting
+    #[clap(short, long, value_parser, default_value_t = false)]
+    recursive: bool,
+
+    /// Only converts files whose path relative to the input directory matches this glob, e.g. "**/*.dxf" (can be repeated)
+    #[clap(long, value_parser)]
+    include: Vec<String>,
+
+    /// Skips files whose path relative to the input directory matches this glob, e.g. "old/**" (can be repeated)
+    #[clap(long, value_parser)]
+    exclude: Vec<String>,
+
+    /// Number of files converted in parallel, defaults to one per cpu
+    #[clap(short, long, value_parser)]
+    jobs: Option<usize>,
+
+    /// Watches the given files or directories and reconverts every .dxf file that changes
     #[clap(short, long, value_parser, default_value_t = false)]
-    info: bool,
+    watch: bool,
+
+    /// Milliseconds to wait after the last change before reconverting in watch mode
+    #[clap(long, value_parser, default_value_t = 500)]
+    debounce: u64,
+
+    /// Writes the .elmt files as a QET element collection in this directory, mirroring the input folders
+    #[clap(long, value_parser)]
+    collection: Option<PathBuf>,
+
+    /// TOML file mapping the collection folders to their names per language
+    #[clap(long, value_parser, requires = "collection")]
+    category_names: Option<PathBuf>,
+
+    /// Writes all the converted elements into this .qet project instead of .elmt files
+    #[clap(long, value_parser, conflicts_with = "collection")]
+    project: Option<PathBuf>,
+
+    /// Adds a folio to the project with every element placed on a grid for review
+    #[clap(long, value_parser, default_value_t = false, requires = "project")]
+    review_folio: bool,
+
+    /// Writes a batch summary report, as CSV if the file ends in .csv otherwise as JSON
+    #[clap(long, value_parser)]
+    report: Option<PathBuf>,
 }
 
 
-#[allow(clippy::too_many_lines)]
 fn main() -> Result<()> {
     #[cfg(feature = "venator")]
     let tr_reg = tracing_subscriber::registry()
@@ -83,8 +330,87 @@ fn main() -> Result<()> {
     trace!("Starting dxf2elmt");
 
     // Collect arguments
-    let args: Args = Args::parse_from(wild::args());
+    let cli = Cli::parse_from(wild::args());
+
+    match cli.command {
+        Some(Command::Convert(args)) => convert(*args),
+        None => convert(cli.convert),
+        Some(Command::Inspect { file_name, json }) => inspect(&file_name, json),
+        Some(Command::Validate { file_names }) => validate(&file_names),
+        Some(Command::Diff { old, new }) => diff(&old, &new),
+        Some(Command::Titleblock {
+            file_name,
+            px_per_mm,
+            output_dir,
+            preset,
+            config,
+        }) => {
+            let options = preset_options(config.as_deref(), preset.as_deref(), px_per_mm, output_dir)?;
+            let result = convert_titleblock(&file_name, &options)?;
+            println!("{}", result.message);
+            Ok(())
+        }
+        Some(Command::Svg {
+            file_names,
+            zoom,
+            bare,
+            px_per_mm,
+            output_dir,
+            preset,
+            config,
+        }) => {
+            let options = preset_options(config.as_deref(), preset.as_deref(), px_per_mm, output_dir)?;
+            let svg_options = SvgOptions {
+                bounding_box: !bare,
+                hotspot: !bare,
+                terminals: !bare,
+                zoom: zoom.unwrap_or(1.0),
+            };
+            for file_name in &file_names {
+                let result = export_svg(file_name, &options, &svg_options)?;
+                println!("{}", result.message);
+            }
+            Ok(())
+        }
+        Some(Command::Export {
+            file_names,
+            px_per_mm,
+            output_dir,
+            preset,
+            config,
+        }) => {
+            let options = preset_options(config.as_deref(), preset.as_deref(), px_per_mm, output_dir)?;
+            for file_name in &file_names {
+                let result = export_elmt(file_name, &options)?;
+                println!("{}", result.message);
+            }
+            Ok(())
+        }
+    }
+}
 
+// options of the subcommands that only take a preset, a scale and an output folder
+fn preset_options(
+    config: Option<&Path>,
+    preset: Option<&str>,
+    px_per_mm: Option<f64>,
+    output_dir: Option<PathBuf>,
+) -> Result<ConversionOptions> {
+    let config = Config::load(config)?;
+    let mut settings = config.resolve(preset)?;
+    settings.merge(&Preset {
+        px_per_mm,
+        output_dir,
+        ..Preset::default()
+    });
+    settings.validate().context("Invalid options")?;
+    let mut options = ConversionOptions::default();
+    settings.apply(&mut options);
+    Ok(options)
+}
+
+#[allow(clippy::too_many_lines)]
+fn convert(args: ConvertArgs) -> Result<()> {
     // Check if any files were provided
     if args.file_names.is_empty() {
         eprintln!("Error: No input files specified.");
@@ -97,43 +423,231 @@ fn main() -> Result<()> {
     let dxf_loop_span = span!(Level::TRACE, "Looping over dxf files");
     let dxf_loop_guard = dxf_loop_span.enter();
     
-    let options = ConversionOptions {
-        spline_step: args.spline_step,
-        verbose: args.verbose,
-        info: args.info,
-        px_per_mm: 2.0, // Default: 2px / 1mm
+    let options = {
+        //config files first, then the preset, and whatever is given on the command line wins
+        let config = Config::load(args.config.as_deref())?;
+        let mut settings = config.resolve(args.preset.as_deref())?;
+        let text_mode = args.text_mode.or_else(|| {
+            args.dtext.then(|| {
+                eprintln!("Warning: --dtext is deprecated, texts are dynamic by default. Use --text-mode to choose.");
+                TextMode::Dynamic
+            })
+        });
+        let non_empty = |patterns: Vec<String>| (!patterns.is_empty()).then_some(patterns);
+        settings.merge(&Preset {
+            px_per_mm: args.px_per_mm,
+            scale_mode: args.scale_mode,
+            grid_anchor: args.grid_anchor,
+            grid_snap: (!args.grid_snap.is_empty()).then_some(args.grid_snap),
+            spline_step: args.spline_step,
+            text_mode,
+            info: args.info,
+            include_layers: non_empty(args.include_layer),
+            exclude_layers: non_empty(args.exclude_layer),
+            skip_hidden_layers: args.keep_hidden_layers.then_some(false),
+            exclude_blocks: non_empty(args.exclude_block),
+            output_dir: args.output_dir,
+            split: args.split,
+            blocks: non_empty(args.block),
+            cluster_gap: args.cluster_gap,
+            update: args.update,
+            align_corner: args.align_corner,
+            ..Preset::default()
+        });
+        settings.validate().context("Invalid options")?;
+        //a preset may leave the split to the command line, a gap given here needs it
+        if args.cluster_gap.is_some() && settings.split != Some(SplitMode::Clusters) {
+            bail!("--cluster-gap is only used with --split clusters");
+        }
+
+        let mut options = ConversionOptions::default();
+        settings.apply(&mut options);
+        options
     };
 
+    if args.watch {
+        let watch_options = WatchOptions {
+            recursive: args.recursive,
+            debounce: Duration::from_millis(args.debounce),
+            include: args.include,
+            exclude: args.exclude,
+        };
+        let mut watcher = DxfWatcher::new(&args.file_names, &watch_options)?;
+        println!("Watching for changes, press Ctrl+C to stop...");
+        while let Some(files) = watcher.changed_files(Duration::from_secs(1)) {
+            for file in files {
+                convert_file(&file, &options).write_row(&mut io::stdout().lock())?;
+            }
+        }
+        drop(dxf_loop_guard);
+        return Ok(());
+    }
+
+    // Reading from stdin leaves no path to derive the .elmt from, so it implies --stdout
+    let to_stdout =
+        args.stdout || args.verbose || args.file_names.iter().any(|f| is_stdin_path(f));
+    if to_stdout && args.file_names.len() > 1 {
+        eprintln!("Error: Only one input file can be written to stdout.");
+        std::process::exit(1);
+    }
+    if to_stdout && options.split != SplitMode::None {
+        eprintln!("Error: Split elements can't be written to stdout.");
+        std::process::exit(1);
+    }
+
+    // Several files or any directory switch to batch mode, which keeps going past failures
+    let batch_mode = !to_stdout
+        && (args.file_names.len() > 1
+            || args.report.is_some()
+            || args.collection.is_some()
+            || args.project.is_some()
+            || args.file_names.iter().any(|f| f.is_dir()));
+    if batch_mode {
+        let batch_options = BatchOptions {
+            recursive: args.recursive,
+            include: args.include,
+            exclude: args.exclude,
+            jobs: args.jobs,
+        };
+        let summary = if let Some(root) = args.collection {
+            let names = match &args.category_names {
+                Some(path) => CategoryNames::load_file(path)?,
+                None => CategoryNames::default(),
+            };
+            let collection = CollectionOptions { root, names };
+            convert_collection(&args.file_names, &options, &batch_options, &collection)?
+        } else if let Some(path) = args.project {
+            let files = collect_inputs(&args.file_names, &batch_options)?;
+            let project = ProjectOptions {
+                path,
+                title: None,
+                diagram: args.review_folio,
+            };
+            convert_project(&files, &options, &batch_options, &project)?
+        } else {
+            let files = collect_inputs(&args.file_names, &batch_options)?;
+            convert_batch(&files, &options, &batch_options)?
+        };
+        summary.write_table(&mut io::stdout().lock())?;
+        if let Some(report) = args.report {
+            summary.write_report(&report)?;
+        }
+        drop(dxf_loop_guard);
+
+        if summary.has_failures() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     for file_name in args.file_names {
-        let result = convert_dxf_file(&file_name, &options)?;
+        let result = if to_stdout {
+            let stdout = io::stdout().lock();
+            convert_dxf_to_writer(&file_name, &options, stdout)?
+        } else {
+            let result = convert_dxf_file(&file_name, &options)?;
+            println!("{}", result.message);
+            result
+        };
 
         if options.info {
             if let Some(stats) = result.stats {
-                println!("Conversion complete!\n");
-                println!("STATS");
-                println!("~~~~~~~~~~~~~~~");
-                println!("Circles: {}", stats.circles);
-                println!("Lines: {}", stats.lines);
-                println!("Arcs: {}", stats.arcs);
-                println!("Splines: {}", stats.splines);
-                println!("Texts: {}", stats.texts);
-                println!("Ellipses: {}", stats.ellipses);
-                println!("Polylines: {}", stats.polylines);
-                println!("LwPolylines: {}", stats.lwpolylines);
-                println!("Solids: {}", stats.solids);
-                println!("Blocks: {}", stats.blocks);
-                println!("Currently Unsupported: {}", stats.unsupported);
-                println!("\nTime Elapsed: {} ms", stats.elapsed_ms);
+                if to_stdout {
+                    print_stats(&mut io::stderr().lock(), &stats)?;
+                } else {
+                    print_stats(&mut io::stdout().lock(), &stats)?;
+                }
             }
         }
+    }
+    drop(dxf_loop_guard);
+
+    Ok(())
+}
+
+fn inspect(file_name: &Path, json: bool) -> Result<()> {
+    let drawing = load_drawing(file_name)?;
+    let report = DrawingReport::new(&drawing);
+
+    let mut out = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+    } else {
+        report.write_text(&mut out)?;
+    }
+    Ok(())
+}
+
+fn validate(file_names: &[PathBuf]) -> Result<()> {
+    let mut has_errors = false;
+    let mut out = io::stdout().lock();
 
-        if options.verbose {
-            if let Some(xml) = result.xml_content {
-                print!("{xml}");
+    for file_name in file_names {
+        let issues = std::fs::read_to_string(file_name)
+            .context(format!("Failed to read {}", file_name.display()))
+            .and_then(|xml| validate_elmt(&xml));
+        match issues {
+            Ok(issues) if issues.is_empty() => writeln!(out, "{}: OK", file_name.display())?,
+            Ok(issues) => {
+                has_errors |= issues.iter().any(|i| i.severity == Severity::Error);
+                for issue in issues {
+                    writeln!(out, "{}: {issue}", file_name.display())?;
+                }
+            }
+            Err(e) => {
+                has_errors = true;
+                writeln!(out, "{}: error: {e:#}", file_name.display())?;
             }
         }
     }
-    drop(dxf_loop_guard);
 
+    if has_errors {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+fn diff(old: &Path, new: &Path) -> Result<()> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))
+    };
+    let diffs = diff_elmt(&read(old)?, &read(new)?)?;
+
+    if diffs.is_empty() {
+        println!("The elements are equivalent");
+        return Ok(());
+    }
+
+    let mut out = io::stdout().lock();
+    for d in &diffs {
+        writeln!(out, "{d}")?;
+    }
+    out.flush()?;
+    std::process::exit(1);
+}
+
+fn print_stats(out: &mut impl Write, stats: &ConversionStats) -> io::Result<()> {
+    writeln!(out, "Conversion complete!\n")?;
+    writeln!(out, "STATS")?;
+    writeln!(out, "~~~~~~~~~~~~~~~")?;
+    writeln!(out, "Circles: {}", stats.circles)?;
+    writeln!(out, "Lines: {}", stats.lines)?;
+    writeln!(out, "Arcs: {}", stats.arcs)?;
+    writeln!(out, "Splines: {}", stats.splines)?;
+    writeln!(out, "Texts: {}", stats.texts)?;
+    writeln!(out, "Ellipses: {}", stats.ellipses)?;
+    writeln!(out, "Polylines: {}", stats.polylines)?;
+    writeln!(out, "LwPolylines: {}", stats.lwpolylines)?;
+    writeln!(out, "Solids: {}", stats.solids)?;
+    writeln!(out, "Blocks: {}", stats.blocks)?;
+    writeln!(out, "Currently Unsupported: {}", stats.unsupported)?;
+    if stats.grid_displacement > 0.0 {
+        writeln!(
+            out,
+            "Grid alignment moved points up to {:.2} px",
+            stats.grid_displacement
+        )?;
+    }
+    writeln!(out, "\nTime Elapsed: {} ms", stats.elapsed_ms)
+}
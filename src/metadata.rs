@@ -0,0 +1,123 @@
+use crate::qelmt::{LinkType, Placement, TermOrient, Terminal};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Names of the element informations QET's element editor offers
+pub const ELEMENT_INFO_NAMES: [&str; 11] = [
+    "label",
+    "comment",
+    "description",
+    "designation",
+    "manufacturer",
+    "manufacturer_reference",
+    "machine_manufacturer_reference",
+    "supplier",
+    "quantity",
+    "unity",
+    "plant",
+];
+
+/// Names, informations, link type and element informations of the element converted from
+/// a drawing. They're kept in a sidecar next to the .dxf, so every reconversion uses them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ElementMetadata {
+    /// Language code -> name of the element in that language
+    pub names: BTreeMap<String, String>,
+    pub informations: Option<String>,
+    pub link_type: Option<LinkType>,
+    /// Element information name (e.g. `manufacturer`) -> value
+    pub element_infos: BTreeMap<String, String>,
+    /// Terminals placed by hand, replacing the ones found by the conversion when set
+    pub terminals: Option<Vec<TerminalPlacement>>,
+}
+
+/// A terminal in drawing coordinates, so it follows the graphics when they're converted with
+/// another scale or grid anchor. The uuid is kept so reconverting doesn't break the wires
+/// connected to it in QET projects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TerminalPlacement {
+    pub x: f64,
+    pub y: f64,
+    pub orientation: TermOrient,
+    #[serde(default)]
+    pub name: String,
+    pub uuid: Uuid,
+}
+
+impl TerminalPlacement {
+    #[must_use]
+    pub fn new(x: f64, y: f64, orientation: TermOrient) -> Self {
+        Self {
+            x,
+            y,
+            orientation,
+            name: String::new(),
+            uuid: Uuid::new_v4(),
+        }
+    }
+
+    /// Where `term`, a terminal of an element converted with `placement`, is in the drawing
+    #[must_use]
+    pub fn from_terminal(term: &Terminal, placement: Placement) -> Self {
+        let (x, y) = placement.to_drawing(term.x, term.y);
+        Self {
+            x,
+            y,
+            orientation: term.orientation,
+            name: term.name.clone(),
+            uuid: term.uuid,
+        }
+    }
+
+    /// The terminal in an element converted with `placement`
+    #[must_use]
+    pub fn to_terminal(&self, placement: Placement) -> Terminal {
+        let (x, y) = placement.terminal_to_element(self.x, self.y);
+        let mut term = Terminal::new(x, y, self.orientation);
+        term.name.clone_from(&self.name);
+        term.uuid = self.uuid;
+        term
+    }
+}
+
+/// `symbol.meta.toml` for `symbol.dxf`
+#[must_use]
+pub fn sidecar_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("meta.toml")
+}
+
+impl ElementMetadata {
+    /// The metadata in the sidecar of `file_path`, `None` when there is no sidecar
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the sidecar exists but can't be read or isn't valid TOML.
+    pub fn load(file_path: &Path) -> Result<Option<Self>> {
+        let path = sidecar_path(file_path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .context(format!("Failed to read metadata file: {}", path.display()))?;
+        let metadata = toml::from_str(&content)
+            .context(format!("Invalid metadata file: {}", path.display()))?;
+        Ok(Some(metadata))
+    }
+
+    /// Writes the sidecar of `file_path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(&self, file_path: &Path) -> Result<()> {
+        let path = sidecar_path(file_path);
+        let content = toml::to_string_pretty(self).context("Failed to serialize the metadata")?;
+        std::fs::write(&path, content)
+            .context(format!("Failed to write metadata file: {}", path.display()))
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stages a conversion goes through, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Loading,
+    Building,
+    Scaling,
+    Serializing,
+    Writing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Phase(Phase),
+    /// Entities of the drawing gone through while building the objects, out of `total`
+    Entities {
+        done: usize,
+        total: usize,
+    },
+}
+
+/// Flag stopping a conversion, clones share the flag so it can be cancelled from
+/// another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Error of a cancelled conversion, tell it apart with `err.is::<Cancelled>()`
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Conversion cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Callback receiving the progress of a conversion and the token that cancels it.
+/// The default reports nothing and is never cancelled.
+#[derive(Clone, Default)]
+pub struct ProgressHooks {
+    pub on_progress: Option<Arc<dyn Fn(Progress) + Send + Sync>>,
+    pub cancel: CancelToken,
+}
+
+impl fmt::Debug for ProgressHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressHooks")
+            .field("on_progress", &self.on_progress.is_some())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl ProgressHooks {
+    #[must_use]
+    pub fn new(on_progress: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self {
+            on_progress: Some(Arc::new(on_progress)),
+            cancel: CancelToken::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_cancel(self, cancel: CancelToken) -> Self {
+        Self { cancel, ..self }
+    }
+
+    pub fn report(&self, progress: Progress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+
+    pub fn phase(&self, phase: Phase) {
+        self.report(Progress::Phase(phase));
+    }
+
+    //every entity would flood a channel for drawings with hundreds of thousands of them
+    pub(crate) fn entities(&self, done: usize, total: usize) {
+        if done % 64 == 0 || done == total {
+            self.report(Progress::Entities { done, total });
+        }
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// # Errors
+    ///
+    /// Returns [`Cancelled`] once the conversion has been cancelled.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+}
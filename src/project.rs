@@ -0,0 +1,335 @@
+use crate::batch::{run_batch, BatchOptions, BatchSummary, FileReport};
+use crate::qelmt::{two_dec, Definition, TermOrient};
+use crate::split::{file_stem, split_drawing, unique_name, SplitElement, SplitMode};
+use crate::{friendly_name, load_drawing, warn_unused_metadata, with_metadata};
+use crate::{ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{Context, Result};
+use simple_xml_builder::XMLElement;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Version of the project format written, the one of QET 0.8
+const PROJECT_VERSION: &str = "0.80";
+
+/// Category of the embedded collection the converted elements go into, below `import`
+const CATEGORY: &str = "dxf2elmt";
+
+/// Size of the columns and rows of the folio, the QET defaults
+const COL_SIZE: i64 = 60;
+const ROW_SIZE: i64 = 80;
+const MIN_COLS: i64 = 17;
+const MIN_ROWS: i64 = 8;
+
+/// Room left around every element placed on the review folio
+const CELL_MARGIN: i64 = 40;
+
+/// Where the elements of a batch go when it's written as a QET project
+#[derive(Debug, Default, Clone)]
+pub struct ProjectOptions {
+    /// The .qet file to write
+    pub path: PathBuf,
+    /// Title of the project, defaults to the name of the file
+    pub title: Option<String>,
+    /// Adds a folio with every element placed on a grid, to review them at a glance
+    pub diagram: bool,
+}
+
+/// A QET project whose embedded collection holds the converted elements
+#[derive(Debug, Default)]
+pub struct QetProject {
+    title: String,
+    //file name in the collection -> element
+    elements: Vec<(String, Definition)>,
+    names: HashSet<String>,
+}
+
+impl QetProject {
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds an element to the embedded collection, the file name gets a number appended if
+    /// another element already uses it
+    pub fn add(&mut self, name: &str, definition: Definition) {
+        let unique = unique_name(&mut self.names, &file_stem(name));
+        self.elements.push((format!("{unique}.elmt"), definition));
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    fn collection_xml(&self) -> XMLElement {
+        fn names_xml(name: &str) -> XMLElement {
+            let mut names = XMLElement::new("names");
+            let mut name_xml = XMLElement::new("name");
+            name_xml.add_attribute("lang", "en");
+            name_xml.add_text(name);
+            names.add_child(name_xml);
+            names
+        }
+
+        let mut category = XMLElement::new("category");
+        category.add_attribute("name", CATEGORY);
+        category.add_child(names_xml("dxf2elmt"));
+        for (file_name, definition) in &self.elements {
+            let mut element = XMLElement::new("element");
+            element.add_attribute("name", file_name);
+            element.add_child(definition.into());
+            category.add_child(element);
+        }
+
+        let mut import = XMLElement::new("category");
+        import.add_attribute("name", "import");
+        import.add_child(names_xml("Imported elements"));
+        import.add_child(category);
+
+        let mut collection = XMLElement::new("collection");
+        collection.add_child(import);
+        collection
+    }
+
+    /// A folio with the elements placed on a grid, as many columns as rows, each element
+    /// centered in a cell big enough for the largest one
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn diagram_xml(&self) -> XMLElement {
+        let per_row = ((self.elements.len() as f64).sqrt().ceil() as usize).max(1);
+        let (cell_w, cell_h) = self.elements.iter().fold((0, 0), |(w, h), (_, def)| {
+            let (dw, dh) = def.size();
+            (w.max(dw), h.max(dh))
+        });
+        let (cell_w, cell_h) = (cell_w + CELL_MARGIN, cell_h + CELL_MARGIN);
+
+        let mut elements = XMLElement::new("elements");
+        let mut terminal_id = 0;
+        for (i, (file_name, definition)) in self.elements.iter().enumerate() {
+            let (col, row) = ((i % per_row) as i64, (i / per_row) as i64);
+            let (width, height) = definition.size();
+            let (hotspot_x, hotspot_y) = definition.hotspot();
+
+            //the position of an element is the one of its hotspot, snapped to the 10px grid
+            let x = CELL_MARGIN + col * cell_w + (cell_w - width) / 2 + hotspot_x;
+            let y = CELL_MARGIN + row * cell_h + (cell_h - height) / 2 + hotspot_y;
+
+            let mut element = XMLElement::new("element");
+            element.add_attribute("x", x / 10 * 10);
+            element.add_attribute("y", y / 10 * 10);
+            element.add_attribute("orientation", 0);
+            element.add_attribute("uuid", format!("{{{}}}", Uuid::new_v4()));
+            element.add_attribute("type", format!("embed://import/{CATEGORY}/{file_name}"));
+            element.add_attribute("prefix", "");
+            element.add_attribute("freezeLabel", "false");
+
+            //QET refuses elements whose terminals aren't all listed in the folio
+            let mut terminals = XMLElement::new("terminals");
+            for term in definition.terminals() {
+                terminal_id += 1;
+                let mut term_xml = XMLElement::new("terminal");
+                term_xml.add_attribute("x", two_dec(term.x));
+                term_xml.add_attribute("y", two_dec(term.y));
+                term_xml.add_attribute(
+                    "orientation",
+                    match term.orientation {
+                        TermOrient::North => 0,
+                        TermOrient::East => 1,
+                        TermOrient::South => 2,
+                        TermOrient::West => 3,
+                    },
+                );
+                term_xml.add_attribute("id", terminal_id);
+                terminals.add_child(term_xml);
+            }
+            element.add_child(terminals);
+            element.add_child(XMLElement::new("inputs"));
+            elements.add_child(element);
+        }
+
+        let rows = self.elements.len().div_ceil(per_row) as i64;
+        let mut diagram = XMLElement::new("diagram");
+        diagram.add_attribute("title", "Converted elements");
+        diagram.add_attribute("folio", "%id/%total");
+        diagram.add_attribute("order", 1);
+        diagram.add_attribute("version", PROJECT_VERSION);
+        diagram.add_attribute(
+            "cols",
+            ((2 * CELL_MARGIN + per_row as i64 * cell_w) / COL_SIZE + 1).max(MIN_COLS),
+        );
+        diagram.add_attribute("colsize", COL_SIZE);
+        diagram.add_attribute(
+            "rows",
+            ((2 * CELL_MARGIN + rows * cell_h) / ROW_SIZE + 1).max(MIN_ROWS),
+        );
+        diagram.add_attribute("rowsize", ROW_SIZE);
+        diagram.add_attribute("displaycols", "true");
+        diagram.add_attribute("displayrows", "true");
+        diagram.add_child(elements);
+        diagram.add_child(XMLElement::new("conductors"));
+        diagram
+    }
+
+    /// The project document, with the review folio when `diagram` is set
+    #[must_use]
+    pub fn to_xml(&self, diagram: bool) -> XMLElement {
+        let mut project = XMLElement::new("project");
+        project.add_attribute("version", PROJECT_VERSION);
+        project.add_attribute("title", &self.title);
+        project.add_attribute("folioSheetQuantity", i32::from(diagram));
+        if diagram {
+            project.add_child(self.diagram_xml());
+        }
+        project.add_child(self.collection_xml());
+        project
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn write(&self, path: &Path, diagram: bool) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .context(format!("Failed to create directory: {}", dir.display()))?;
+        }
+        let file = File::create(path)
+            .context(format!("Failed to create project file: {}", path.display()))?;
+        self.to_xml(diagram)
+            .write(file)
+            .context(format!("Failed to write project file: {}", path.display()))
+    }
+}
+
+//the metadata sidecar of a drawing converted into a single element applies to it, like
+//when writing the .elmt file
+fn project_elements(
+    elements: Vec<SplitElement>,
+    file: &Path,
+    options: &ConversionOptions,
+) -> Result<Vec<SplitElement>> {
+    if options.split != SplitMode::None {
+        warn_unused_metadata(file);
+        return Ok(elements);
+    }
+    elements
+        .into_iter()
+        .map(|element| {
+            Ok(SplitElement {
+                definition: with_metadata(element.definition, file)?,
+                ..element
+            })
+        })
+        .collect()
+}
+
+/// Converts every file on a thread pool and writes all the elements into a single .qet
+/// project instead of .elmt files. Split modes apply, every element of a file is added.
+/// The metadata sidecar of a drawing applies when it becomes a single element.
+///
+/// # Errors
+///
+/// Returns an error if the thread pool can't be created or the project can't be written.
+/// Files that fail to convert are reported in the summary instead.
+pub fn convert_project(
+    files: &[PathBuf],
+    options: &ConversionOptions,
+    batch_options: &BatchOptions,
+    project_options: &ProjectOptions,
+) -> Result<BatchSummary> {
+    let converted = Mutex::new(BTreeMap::new());
+    let summary = run_batch(files, batch_options, |file| {
+        let now = Instant::now();
+        let result = load_drawing(file)
+            .and_then(|drw| split_drawing(&drw, &friendly_name(file), options))
+            .and_then(|elements| project_elements(elements, file, options))
+            .map(|elements: Vec<SplitElement>| {
+                let mut stats = ConversionStats::default();
+                for element in &elements {
+                    stats.add(&element.stats);
+                }
+                stats.elapsed_ms = now.elapsed().as_millis();
+                let result = ConversionResult {
+                    success: true,
+                    message: format!(
+                        "Successfully converted {} into {} elements",
+                        friendly_name(file),
+                        elements.len()
+                    ),
+                    stats: Some(stats),
+                    xml_content: None,
+                };
+                //a poisoned lock only means another file panicked, its elements are still fine
+                converted
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .insert(file.to_path_buf(), elements);
+                result
+            });
+        FileReport::new(file, Some(project_options.path.clone()), now, result)
+    })?;
+
+    let title = project_options.title.clone().unwrap_or_else(|| {
+        project_options
+            .path
+            .file_stem()
+            .map_or_else(|| "dxf2elmt".to_string(), |s| s.to_string_lossy().to_string())
+    });
+    let mut project = QetProject::new(title);
+    //sorted by input file so the project doesn't depend on the order files finish in
+    let converted = converted
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    for element in converted.into_values().flatten() {
+        project.add(&element.name, element.definition);
+    }
+    project.write(&project_options.path, project_options.diagram)?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ElementMetadata;
+    use dxf::entities::{Entity, EntityType, Line};
+    use dxf::{Drawing, Point};
+
+    #[test]
+    fn project_elements_apply_the_metadata_of_single_element_drawings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("relay.dxf");
+        let metadata = ElementMetadata {
+            names: BTreeMap::from([("es".to_string(), "Relé".to_string())]),
+            ..ElementMetadata::default()
+        };
+        metadata.save(&file)?;
+        let mut drw = Drawing::new();
+        drw.add_entity(Entity::new(EntityType::Line(Line::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(10.0, 0.0, 0.0),
+        ))));
+
+        let named = |options: &ConversionOptions| -> Result<bool> {
+            let elements = project_elements(split_drawing(&drw, "relay", options)?, &file, options)?;
+            Ok(XMLElement::from(&elements[0].definition).to_string().contains("Relé"))
+        };
+        assert!(named(&ConversionOptions::default())?);
+        let split = ConversionOptions {
+            split: SplitMode::Layers,
+            ..ConversionOptions::default()
+        };
+        assert!(!named(&split)?);
+        Ok(())
+    }
+}
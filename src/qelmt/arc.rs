@@ -1,18 +1,20 @@
-use super::{two_dec, ScaleEntity};
-use dxf::entities;
+use super::{two_dec, xml_bool_or, xml_f64, ScaleEntity};
+use dxf::entities::{self, EntityType};
+use dxf::{Point, Vector};
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Arc {
     //need to brush up on my Rust scoping rules, isn't there a way to make this pub to just the module?
     pub x: f64,
     pub y: f64,
 
-    width: f64,
-    height: f64,
-    start: f64,
-    angle: f64,
-    style: String,
+    pub(crate) width: f64,
+    pub(crate) height: f64,
+    pub(crate) start: f64,
+    pub(crate) angle: f64,
+    pub(crate) style: String,
     antialias: bool,
 }
 
@@ -53,6 +55,23 @@ impl From<&entities::Arc> for Arc {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Arc {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        Ok(Arc {
+            x: xml_f64(node, "x")?,
+            y: xml_f64(node, "y")?,
+            width: xml_f64(node, "width")?,
+            height: xml_f64(node, "height")?,
+            start: xml_f64(node, "start")?,
+            angle: xml_f64(node, "angle")?,
+            antialias: xml_bool_or(node, "antialias", false),
+            style: node.attribute("style").unwrap_or_default().into(),
+        })
+    }
+}
+
 impl From<&Arc> for XMLElement {
     fn from(arc: &Arc) -> Self {
         let mut arc_xml: XMLElement = XMLElement::new("arc");
@@ -68,6 +87,41 @@ impl From<&Arc> for XMLElement {
     }
 }
 
+//back to a DXF arc, or an elliptical one when the bounding box isn't square
+impl From<&Arc> for EntityType {
+    fn from(arc: &Arc) -> Self {
+        let center = Point::new(arc.x + arc.width / 2.0, -(arc.y + arc.height / 2.0), 0.0);
+
+        //QET also has clockwise spans, DXF arcs always go counter clockwise
+        let start = if arc.angle < 0.0 {
+            arc.start + arc.angle
+        } else {
+            arc.start
+        }
+        .rem_euclid(360.0);
+        let end = start + arc.angle.abs().min(360.0);
+
+        if (arc.width - arc.height).abs() < 1e-6 {
+            return EntityType::Arc(entities::Arc::new(center, arc.width / 2.0, start, end));
+        }
+
+        //the parameters of an ellipse start from its major axis
+        let (major_axis, minor_axis_ratio, offset) = if arc.width >= arc.height {
+            (Vector::new(arc.width / 2.0, 0.0, 0.0), arc.height / arc.width, 0.0)
+        } else {
+            (Vector::new(0.0, arc.height / 2.0, 0.0), arc.width / arc.height, 90.0)
+        };
+        EntityType::Ellipse(entities::Ellipse {
+            center,
+            major_axis,
+            minor_axis_ratio,
+            start_parameter: (start - offset).to_radians(),
+            end_parameter: (end - offset).to_radians(),
+            ..entities::Ellipse::default()
+        })
+    }
+}
+
 impl ScaleEntity for Arc {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.x *= fact_x;
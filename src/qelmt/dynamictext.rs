@@ -1,6 +1,9 @@
-use super::{two_dec, FontInfo, ScaleEntity, TextEntity};
-use dxf::entities::{self, AttributeDefinition};
+use super::{two_dec, xml_bool_or, xml_color, xml_f64, xml_parse_or, xml_uuid};
+use super::{FontInfo, FontStyle, ScaleEntity, TextEntity};
+use dxf::entities::{self, AttributeDefinition, EntityType};
+use dxf::enums::{AttachmentPoint, HorizontalTextJustification, VerticalTextJustification};
 use hex_color::HexColor;
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
@@ -17,7 +20,7 @@ use super::{HAlignment, VAlignment};
 // \W...\; (ancho), \~ (espacio), \\ (barra invertida literal), \S...\; (apilados -> texto plano).
 // IMPORTANTE: Todo lo que está antes del primer ';' se considera código de formato y se elimina.
 // El texto real comienza después del primer ';'.
-fn normalize_mtext(input: &str) -> String {
+pub(crate) fn normalize_mtext(input: &str) -> String {
     // Primero, encontrar el primer ';' - todo antes de él es código de formato
     let first_semicolon = input.find(';');
     let text_start = if let Some(pos) = first_semicolon {
@@ -76,7 +79,6 @@ fn normalize_mtext(input: &str) -> String {
         if i + 1 < bytes.len() && (bytes[i + 1] as char).is_alphabetic() {
             let start = i;
             i += 2; // Saltar \ y la primera letra
-            let mut found_semicolon = false;
 
             // Consumir letras, números, guiones, guiones bajos, pipes, puntos y espacios hasta encontrar ';'
             // Los puntos son necesarios para números decimales en códigos como \W0.82571;
@@ -85,23 +87,15 @@ fn normalize_mtext(input: &str) -> String {
                 if b == b';' {
                     // Encontramos el final del código, omitirlo completamente
                     i += 1;
-                    found_semicolon = true;
                     break;
                 } else if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'|' || b == b' ' || b == b'.' {
                     i += 1;
                 } else {
                     // No es parte del código, restaurar todo desde el inicio
-                    for j in start..i {
-                        out.push(bytes[j] as char);
-                    }
+                    out.extend(bytes[start..i].iter().map(|&b| b as char));
                     break;
                 }
             }
-
-            // Si encontramos el ';', el código fue eliminado correctamente
-            if found_semicolon {
-                continue;
-            }
         } else {
             // No es un código de formato, mantener la barra invertida
             out.push('\\');
@@ -212,9 +206,10 @@ pub struct DynamicText {
     pub original_text_height: f64, // Altura original del texto en unidades DXF
 }
 
-impl From<&DynamicText> for XMLElement {
-    fn from(txt: &DynamicText) -> Self {
-        let mut dtxt_xml = XMLElement::new("dynamic_text");
+impl DynamicText {
+    /// Top left corner of the text item QET places, the position written to the .elmt
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn item_pos(&self) -> (f64, f64) {
         // taken from QET_ElementScaler: "ElmtDynText::AsSVGstring"
         //    // Position und Rotationspunkt berechnen:
         //    posx = x + (size/8.0)+4.05 - 0.5;
@@ -224,7 +219,7 @@ impl From<&DynamicText> for XMLElement {
         //
         // reversed and slightly modified after looking at the result in element-editor:
         //
-        let pt_size: f64 = txt.font.point_size;
+        let pt_size: f64 = self.font.point_size;
         //
         // we need the horizontal alignment and the text-width to move to right x-position:
         // txt.reference_rectangle_width, // should be text-width (Group code 41)
@@ -239,30 +234,61 @@ impl From<&DynamicText> for XMLElement {
         // o.k. ... as long as we do not know the real width:
         // "guess" the width by number of characters and font-size:
         //
-        let graphene_count = txt.text.graphemes(true).count();
-        let txt_width = if txt.reference_rectangle_width > 2.0 {
-            txt.reference_rectangle_width
+        let graphene_count = self.text.graphemes(true).count();
+        let txt_width = if self.reference_rectangle_width > 2.0 {
+            self.reference_rectangle_width
         } else {
             (graphene_count as f64) * pt_size * 0.75
         };
 
         let x_pos = {
-            let x_pos = txt.x + 0.5 - (pt_size / 8.0) - 4.05;
-            match txt.h_alignment {
+            let x_pos = self.x + 0.5 - (pt_size / 8.0) - 4.05;
+            match self.h_alignment {
                 HAlignment::Left => x_pos,
                 HAlignment::Center => x_pos - txt_width / 2.0,
                 HAlignment::Right => x_pos - txt_width,
             }
         };
-        let y_pos = txt.y + 0.5 - (7.0 / 5.0 * pt_size + 26.0 / 5.0) + pt_size;
+        let y_pos = self.y + 0.5 - (7.0 / 5.0 * pt_size + 26.0 / 5.0) + pt_size;
+
+        (x_pos, y_pos)
+    }
+}
+
+//the reference rectangle and the original height only matter while converting and aren't
+//written, so two texts are the same when they put the same item at the same place in QET
+impl PartialEq for DynamicText {
+    fn eq(&self, other: &Self) -> bool {
+        let (x, y) = self.item_pos();
+        let (other_x, other_y) = other.item_pos();
+        self.text == other.text
+            && self.info_name == other.info_name
+            && (two_dec(x), two_dec(y)) == (two_dec(other_x), two_dec(other_y))
+            && self.z == other.z
+            && self.rotation == other.rotation
+            && self.uuid == other.uuid
+            && self.h_alignment == other.h_alignment
+            && self.font == other.font
+            && self.text_from == other.text_from
+            && self.v_alignment == other.v_alignment
+            && self.frame == other.frame
+            && self.text_width == other.text_width
+            && self.keep_visual_rotation == other.keep_visual_rotation
+            && self.color == other.color
+    }
+}
 
+impl From<&DynamicText> for XMLElement {
+    fn from(txt: &DynamicText) -> Self {
+        let mut dtxt_xml = XMLElement::new("dynamic_text");
+        let (x_pos, y_pos) = txt.item_pos();
         dtxt_xml.add_attribute("x", two_dec(x_pos));
         dtxt_xml.add_attribute("y", two_dec(y_pos));
         dtxt_xml.add_attribute("z", two_dec(txt.z));
         dtxt_xml.add_attribute("rotation", two_dec(txt.rotation));
         dtxt_xml.add_attribute("uuid", format!("{{{}}}", txt.uuid));
         dtxt_xml.add_attribute("font", &txt.font);
-        dtxt_xml.add_attribute("Halignment", &txt.h_alignment);
+        dtxt_xml.add_attribute("Halignment", txt.h_alignment);
         dtxt_xml.add_attribute("Valignment", &txt.v_alignment);
         dtxt_xml.add_attribute("text_from", &txt.text_from);
         dtxt_xml.add_attribute("frame", txt.frame);
@@ -279,7 +305,9 @@ impl From<&DynamicText> for XMLElement {
         dtxt_xml.add_child(text_xml);
 
         if let Some(i_name) = &txt.info_name {
-            dtxt_xml.add_attribute("info_name", i_name);
+            let mut info_name_xml = XMLElement::new("info_name");
+            info_name_xml.add_text(i_name);
+            dtxt_xml.add_child(info_name_xml);
         }
 
         if txt.keep_visual_rotation {
@@ -290,6 +318,114 @@ impl From<&DynamicText> for XMLElement {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for DynamicText {
+    type Error = anyhow::Error;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        let text: String = node
+            .children()
+            .find(|n| n.has_tag_name("text"))
+            .and_then(|n| n.text())
+            .unwrap_or_default()
+            .into();
+        let font: FontInfo = xml_parse_or(node, "font", FontInfo::default())?;
+        let h_alignment = xml_parse_or(node, "Halignment", HAlignment::Left)?;
+
+        //undoes the offsets applied when writing it out, the width of the text is
+        //guessed since the reference rectangle isn't in the file. The x found is only
+        //the one of the original text when it had no reference rectangle, but the item
+        //is placed in the same spot
+        let pt_size = font.point_size;
+        let txt_width = (text.graphemes(true).count() as f64) * pt_size * 0.75;
+        let x = {
+            let x = xml_f64(node, "x")? - 0.5 + (pt_size / 8.0) + 4.05;
+            match h_alignment {
+                HAlignment::Left => x,
+                HAlignment::Center => x + txt_width / 2.0,
+                HAlignment::Right => x + txt_width,
+            }
+        };
+        let y = xml_f64(node, "y")? - 0.5 + (7.0 / 5.0 * pt_size + 26.0 / 5.0) - pt_size;
+
+        Ok(DynamicText {
+            text,
+            //QET writes it as a child element, older versions of this tool as an attribute
+            info_name: node
+                .attribute("info_name")
+                .or_else(|| {
+                    node.children()
+                        .find(|n| n.has_tag_name("info_name"))
+                        .and_then(|n| n.text())
+                })
+                .map(Into::into),
+            x,
+            y,
+            z: xml_parse_or(node, "z", 0.0)?,
+            rotation: xml_parse_or(node, "rotation", 0.0)?,
+            uuid: xml_uuid(node, "uuid"),
+            h_alignment,
+            font,
+            text_from: node.attribute("text_from").unwrap_or("UserText").into(),
+            v_alignment: xml_parse_or(node, "Valignment", VAlignment::Top)?,
+            frame: xml_bool_or(node, "frame", false),
+            text_width: xml_parse_or(node, "text_width", -1)?,
+            keep_visual_rotation: xml_bool_or(node, "keep_visual_rotation", false),
+            color: xml_color(node, "color"),
+            reference_rectangle_width: 0.0,
+            original_text_height: 0.0,
+        })
+    }
+}
+
+//single lines become TEXT entities, several lines an MTEXT
+impl From<&DynamicText> for EntityType {
+    fn from(txt: &DynamicText) -> Self {
+        let location = dxf::Point::new(txt.x, -txt.y, txt.z);
+        if txt.text.contains('\n') {
+            let attachment_point = match (txt.h_alignment, &txt.v_alignment) {
+                (HAlignment::Left, VAlignment::Top) => AttachmentPoint::TopLeft,
+                (HAlignment::Center, VAlignment::Top) => AttachmentPoint::TopCenter,
+                (HAlignment::Right, VAlignment::Top) => AttachmentPoint::TopRight,
+                (HAlignment::Left, VAlignment::Center) => AttachmentPoint::MiddleLeft,
+                (HAlignment::Center, VAlignment::Center) => AttachmentPoint::MiddleCenter,
+                (HAlignment::Right, VAlignment::Center) => AttachmentPoint::MiddleRight,
+                (HAlignment::Left, VAlignment::Bottom) => AttachmentPoint::BottomLeft,
+                (HAlignment::Center, VAlignment::Bottom) => AttachmentPoint::BottomCenter,
+                (HAlignment::Right, VAlignment::Bottom) => AttachmentPoint::BottomRight,
+            };
+            return EntityType::MText(entities::MText {
+                insertion_point: location,
+                initial_text_height: txt.font.point_size,
+                rotation_angle: txt.rotation,
+                text: txt.text.lines().collect::<Vec<_>>().join("\\P"),
+                attachment_point,
+                ..entities::MText::default()
+            });
+        }
+
+        EntityType::Text(entities::Text {
+            location: location.clone(),
+            //aligned texts are placed by their second alignment point
+            second_alignment_point: location,
+            text_height: txt.font.point_size,
+            rotation: txt.rotation,
+            value: txt.text.clone(),
+            horizontal_text_justification: match txt.h_alignment {
+                HAlignment::Left => HorizontalTextJustification::Left,
+                HAlignment::Center => HorizontalTextJustification::Center,
+                HAlignment::Right => HorizontalTextJustification::Right,
+            },
+            vertical_text_justification: match txt.v_alignment {
+                VAlignment::Top => VerticalTextJustification::Top,
+                VAlignment::Center => VerticalTextJustification::Middle,
+                VAlignment::Bottom => VerticalTextJustification::Bottom,
+            },
+            ..entities::Text::default()
+        })
+    }
+}
+
 impl ScaleEntity for DynamicText {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.x *= fact_x;
@@ -303,8 +439,15 @@ impl ScaleEntity for DynamicText {
     }
 
     fn right_bound(&self) -> f64 {
-        //todo!()
-        1.0
+        //same rough guess as for the static text, good enough to keep the text inside
+        //the element and to know which texts are close to each other
+        let longest = self
+            .text
+            .lines()
+            .map(|l| l.graphemes(true).count())
+            .max()
+            .unwrap_or(0);
+        self.x + f64::from(u32::try_from(longest).unwrap_or(u32::MAX)) * self.font.point_size * 0.6
     }
 
     fn top_bound(&self) -> f64 {
@@ -312,8 +455,8 @@ impl ScaleEntity for DynamicText {
     }
 
     fn bot_bound(&self) -> f64 {
-        //todo!()
-        1.0
+        let extra_lines = self.text.lines().count().saturating_sub(1);
+        self.y + f64::from(u32::try_from(extra_lines).unwrap_or(u32::MAX)) * self.font.point_size
     }
 }
 
@@ -323,6 +466,7 @@ pub struct DTextBuilder<'a> {
 }
 
 impl<'a> DTextBuilder<'a> {
+    #[must_use]
     pub fn from_text(text: &'a entities::Text) -> Self {
         Self {
             text: TextEntity::Text(text),
@@ -330,6 +474,7 @@ impl<'a> DTextBuilder<'a> {
         }
     }
 
+    #[must_use]
     pub fn from_mtext(text: &'a entities::MText) -> Self {
         Self {
             text: TextEntity::MText(text),
@@ -337,6 +482,7 @@ impl<'a> DTextBuilder<'a> {
         }
     }
 
+    #[must_use]
     pub fn from_attrib(attrib: &'a AttributeDefinition) -> Self {
         Self {
             text: TextEntity::Attrib(attrib),
@@ -344,6 +490,7 @@ impl<'a> DTextBuilder<'a> {
         }
     }
 
+    #[must_use]
     pub fn color(self, color: HexColor) -> Self {
         Self {
             color: Some(color),
@@ -354,6 +501,8 @@ impl<'a> DTextBuilder<'a> {
 
 
 
+    #[must_use]
+    #[allow(clippy::too_many_lines, clippy::cast_possible_truncation)]
     pub fn build(self) -> DynamicText {
         let (
             x,
@@ -467,11 +616,10 @@ impl<'a> DTextBuilder<'a> {
             TextEntity::Text(txt) => {
                 extract_mtext_format(&txt.value)
             }
-            _ => MTextFormatInfo::default(),
+            TextEntity::Attrib(_) => MTextFormatInfo::default(),
         };
 
         // Determinar el estilo de fuente basado en bold e italic
-        use super::FontStyle;
         let font_style = if format_info.italic {
             FontStyle::Italic
         } else {
@@ -532,7 +680,7 @@ impl<'a> DTextBuilder<'a> {
                     // Para simplificar, usamos el color del builder si color_index es 0 o inválido
                     if color_idx > 0 && color_idx < 256 {
                         // Convertir índice DXF a color (simplificado)
-                        HexColor::from_u32(color_idx as u32)
+                        HexColor::from_u32(color_idx.unsigned_abs())
                     } else {
                         self.color.unwrap_or(HexColor::BLACK)
                     }
@@ -1,12 +1,14 @@
-use super::{two_dec, Circularity, ScaleEntity};
-use dxf::entities::{self, Circle, LwPolyline, Polyline};
+use super::{two_dec, xml_bool_or, xml_f64, Circularity, ScaleEntity};
+use dxf::entities::{self, Circle, EntityType, LwPolyline, Polyline};
+use dxf::{Point, Vector};
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Ellipse {
-    height: f64,
-    width: f64,
-    style: String,
+    pub(crate) height: f64,
+    pub(crate) width: f64,
+    pub(crate) style: String,
 
     //need to brush up on my Rust scoping rules, isn't there a way to make this pub to just the module?
     pub x: f64,
@@ -138,6 +140,28 @@ impl TryFrom<&LwPolyline> for Ellipse {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Ellipse {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        let (width, height) = if node.has_tag_name("circle") {
+            let diameter = xml_f64(node, "diameter")?;
+            (diameter, diameter)
+        } else {
+            (xml_f64(node, "width")?, xml_f64(node, "height")?)
+        };
+
+        Ok(Ellipse {
+            x: xml_f64(node, "x")?,
+            y: xml_f64(node, "y")?,
+            width,
+            height,
+            antialias: xml_bool_or(node, "antialias", false),
+            style: node.attribute("style").unwrap_or_default().into(),
+        })
+    }
+}
+
 impl From<&Ellipse> for XMLElement {
     fn from(ell: &Ellipse) -> Self {
         let mut ell_xml: XMLElement = XMLElement::new("ellipse");
@@ -151,6 +175,30 @@ impl From<&Ellipse> for XMLElement {
     }
 }
 
+//back to a DXF circle, or an ellipse when the bounding box isn't square
+impl From<&Ellipse> for EntityType {
+    fn from(ell: &Ellipse) -> Self {
+        let center = Point::new(ell.x + ell.width / 2.0, -(ell.y + ell.height / 2.0), 0.0);
+        if (ell.width - ell.height).abs() < 1e-6 {
+            return EntityType::Circle(Circle::new(center, ell.width / 2.0));
+        }
+
+        let (major_axis, minor_axis_ratio) = if ell.width >= ell.height {
+            (Vector::new(ell.width / 2.0, 0.0, 0.0), ell.height / ell.width)
+        } else {
+            (Vector::new(0.0, ell.height / 2.0, 0.0), ell.width / ell.height)
+        };
+        EntityType::Ellipse(entities::Ellipse {
+            center,
+            major_axis,
+            minor_axis_ratio,
+            start_parameter: 0.0,
+            end_parameter: 2.0 * std::f64::consts::PI,
+            ..entities::Ellipse::default()
+        })
+    }
+}
+
 impl ScaleEntity for Ellipse {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.x *= fact_x;
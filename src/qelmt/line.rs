@@ -1,14 +1,16 @@
 use super::two_dec;
 use super::LineEnd;
 use super::ScaleEntity;
+use super::{xml_bool_or, xml_f64, xml_parse_or};
 use dxf::entities::{self, LwPolyline, Polyline};
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Line {
-    length2: f64,
-    end2: LineEnd,
-    length1: f64,
+    pub(crate) length2: f64,
+    pub(crate) end2: LineEnd,
+    pub(crate) length1: f64,
 
     //need to brush up on my Rust scoping rules, isn't there a way to make this pub to just the module?
     pub x1: f64,
@@ -16,8 +18,8 @@ pub struct Line {
     pub x2: f64,
     pub y2: f64,
 
-    style: String,
-    end1: LineEnd,
+    pub(crate) style: String,
+    pub(crate) end1: LineEnd,
     antialias: bool,
 }
 
@@ -154,23 +156,52 @@ impl From<&entities::Leader> for Leader {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Line {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        Ok(Line {
+            x1: xml_f64(node, "x1")?,
+            y1: xml_f64(node, "y1")?,
+            length1: xml_parse_or(node, "length1", 1.5)?,
+            end1: xml_parse_or(node, "end1", LineEnd::None)?,
+            x2: xml_f64(node, "x2")?,
+            y2: xml_f64(node, "y2")?,
+            length2: xml_parse_or(node, "length2", 1.5)?,
+            end2: xml_parse_or(node, "end2", LineEnd::None)?,
+            antialias: xml_bool_or(node, "antialias", false),
+            style: node.attribute("style").unwrap_or_default().into(),
+        })
+    }
+}
+
 impl From<&Line> for XMLElement {
     fn from(line: &Line) -> Self {
         let mut line_xml: XMLElement = XMLElement::new("line");
         line_xml.add_attribute("x1", two_dec(line.x1));
         line_xml.add_attribute("y1", two_dec(line.y1));
         line_xml.add_attribute("length1", two_dec(line.length1));
-        line_xml.add_attribute("end1", &line.end1);
+        line_xml.add_attribute("end1", line.end1);
         line_xml.add_attribute("x2", two_dec(line.x2));
         line_xml.add_attribute("y2", two_dec(line.y2));
         line_xml.add_attribute("length2", two_dec(line.length2));
-        line_xml.add_attribute("end2", &line.end2);
+        line_xml.add_attribute("end2", line.end2);
         line_xml.add_attribute("antialias", line.antialias);
         line_xml.add_attribute("style", &line.style);
         line_xml
     }
 }
 
+//the line ends have no DXF equivalent and are dropped
+impl From<&Line> for entities::Line {
+    fn from(line: &Line) -> Self {
+        entities::Line::new(
+            dxf::Point::new(line.x1, -line.y1, 0.0),
+            dxf::Point::new(line.x2, -line.y2, 0.0),
+        )
+    }
+}
+
 impl ScaleEntity for Line {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.x1 *= fact_x;
@@ -1,17 +1,26 @@
+use crate::metadata::ElementMetadata;
+use crate::progress::{CancelToken, Phase};
+use crate::ConversionOptions;
+use anyhow::{bail, Context};
 use dxf::entities::{AttributeDefinition, Entity, EntityType};
 use dxf::entities::{LwPolyline, Polyline};
 use dxf::enums::{AttachmentPoint, HorizontalTextJustification, Units, VerticalTextJustification};
 use dxf::{Block, Drawing};
 use dynamictext::DTextBuilder;
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
 use hex_color::HexColor;
 use itertools::Itertools;
+use roxmltree::{Document, Node};
 use simple_xml_builder::XMLElement;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::f64::consts::PI;
 use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
 use uuid::Uuid;
 
-use tracing::{error, info, span, trace, Level};
+use tracing::{error, info, span, trace, warn, Level};
 
 pub mod arc;
 pub use arc::Arc;
@@ -31,6 +40,9 @@ pub use polygon::Polygon;
 pub mod ellipse;
 pub use ellipse::Ellipse;
 
+pub mod terminal;
+pub use terminal::{TermOrient, Terminal};
+
 fn find_block<'a>(drw: &'a Drawing, name: &str) -> Option<&'a Block> {
     //this is ugly there has to be a cleaner way to filter this....but for my first attempt at pulling the
     //blocks out of the drawing it works.
@@ -42,12 +54,6 @@ fn find_block<'a>(drw: &'a Drawing, name: &str) -> Option<&'a Block> {
     drw.blocks().filter(|bl| bl.name == name).take(1).next()
 }
 
-#[derive(Debug)]
-enum Either<L, R> {
-    Left(L),
-    Right(R),
-}
-
 #[derive(Debug)]
 pub struct Definition {
     r#type: ItemType,
@@ -60,18 +66,40 @@ pub struct Definition {
     uuid: ElmtUuid,
     names: Names,
     element_infos: Option<ElemInfos>,
-    informations: &'static str,
+    informations: String,
     pub description: Description,
+    //how far the grid alignment moved the furthest point, in pixels
+    grid_displacement: f64,
+    //how points of the drawing were taken into the element
+    placement: Placement,
     //counts
 }
 
+//the grid displacement and the placement come from the conversion, they aren't written to the .elmt
+impl PartialEq for Definition {
+    fn eq(&self, other: &Self) -> bool {
+        self.r#type == other.r#type
+            && self.width == other.width
+            && self.height == other.height
+            && self.hotspot_x == other.hotspot_x
+            && self.hotspot_y == other.hotspot_y
+            && self.version == other.version
+            && self.link_type == other.link_type
+            && self.uuid == other.uuid
+            && self.names == other.names
+            && self.element_infos == other.element_infos
+            && self.informations == other.informations
+            && self.description == other.description
+    }
+}
+
 //Since the ScaleEntity trait was added to all the objects/elements
 //and I need to add the get bounds to all it probably makes sense to have
 
 //them all within the same trait instead of multiple traits, as a collective
 //set of functions needed by the objects...but I should probably come up with
 //a better trait name then. For now I'll leave it and just get the code working
-trait ScaleEntity {
+pub trait ScaleEntity {
     fn scale(&mut self, fact_x: f64, fact_y: f64);
 
     fn left_bound(&self) -> f64;
@@ -162,18 +190,166 @@ impl Circularity for LwPolyline {
     }
 }
 
+/// Spacing of the grid of QET's element editor, in pixels. Terminals have to be on it
+/// for wires to connect to them.
+pub const GRID_STEP: f64 = 10.0;
+
+//drops the objects `keep` says no to, and the groups left empty
+fn retain_objects(objects: &mut Vec<Objects>, keep: &impl Fn(&Objects) -> bool) {
+    objects.retain_mut(|obj| match obj {
+        Objects::Group(children) => {
+            retain_objects(children, keep);
+            !children.is_empty()
+        }
+        obj => keep(obj),
+    });
+}
+
 impl Definition {
-    pub fn new(name: impl Into<String>, spline_step: u32, px_per_mm: f64, drw: &Drawing) -> Self {
+    /// # Errors
+    ///
+    /// Returns an error if one of the layer or block patterns isn't a valid glob.
+    pub fn new(name: impl Into<String>, options: &ConversionOptions, drw: &Drawing) -> anyhow::Result<Self> {
         /*for st in drw.styles() {
             dbg!(st);
         }*/
-        let final_scale_factor = Self::apply_unit_conversion(drw.header.default_drawing_units, px_per_mm);
-        let description = {
-            let mut description: Description = (drw, spline_step, px_per_mm).into();
-            description.scale(final_scale_factor, final_scale_factor);
-            description
-        };
+        options.progress.phase(Phase::Building);
+        Ok(Self::from_description(name, options, drw, (drw, options).try_into()?))
+    }
 
+    /// Element made of the entities of a single block definition, the base point of the
+    /// block becomes the hotspot of the element
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the layer or block patterns isn't a valid glob.
+    pub fn from_block(
+        name: impl Into<String>,
+        options: &ConversionOptions,
+        drw: &Drawing,
+        block: &Block,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::from_description(name, options, drw, Description::from_block(drw, block, options)?))
+    }
+
+    /// Element made of already converted objects, still in drawing units
+    pub fn from_description(
+        name: impl Into<String>,
+        options: &ConversionOptions,
+        drw: &Drawing,
+        mut description: Description,
+    ) -> Self {
+        options.progress.phase(Phase::Scaling);
+        let final_scale_factor = options
+            .scale_mode
+            .factor(&description, Self::unit_scale(drw, options.px_per_mm));
+        description.scale(final_scale_factor, final_scale_factor);
+        description.orient_terminals();
+        let alignment = description.align_to_grid(options.grid_anchor, &options.grid_snap);
+        let ((width, height), (hotspot_x, hotspot_y)) = Self::fit(&description);
+
+        Definition {
+            r#type: ItemType::Element,
+            width,
+            height,
+            hotspot_x,
+            hotspot_y,
+            version: "0.8.0".into(),
+            link_type: LinkType::Simple,
+            uuid: Uuid::new_v4().into(),
+            names: Names {
+                names: vec![Name {
+                    lang: "en".into(),
+                    value: name.into(), //need to truncate the extension
+                }],
+            },
+            element_infos: None,
+            informations: "Created using dxf2elmt!".into(),
+            description,
+            grid_displacement: alignment.displacement,
+            placement: Placement {
+                factor: final_scale_factor,
+                shift: alignment.shift,
+                snap_terminals: options.grid_snap.contains(&GridSnap::Terminals),
+            },
+        }
+    }
+
+    /// Replaces the names, informations, link type, element informations and terminals with
+    /// the ones set in `metadata`, what it leaves empty stays as it is
+    pub fn apply_metadata(&mut self, metadata: &ElementMetadata) {
+        if !metadata.names.is_empty() {
+            self.names = Names {
+                names: metadata
+                    .names
+                    .iter()
+                    .map(|(lang, value)| Name {
+                        lang: lang.clone(),
+                        value: value.clone(),
+                    })
+                    .collect(),
+            };
+        }
+        if let Some(informations) = &metadata.informations {
+            self.informations.clone_from(informations);
+        }
+        if let Some(link_type) = metadata.link_type {
+            self.link_type = link_type;
+        }
+        if !metadata.element_infos.is_empty() {
+            self.element_infos = Some(ElemInfos {
+                elem_info: metadata
+                    .element_infos
+                    .iter()
+                    .map(|(name, value)| ElemInfo {
+                        name: name.clone(),
+                        show: 1,
+                        value: value.clone(),
+                    })
+                    .collect(),
+            });
+        }
+        //placed terminals replace the detected ones, they may be out of the old bounds
+        if let Some(terminals) = &metadata.terminals {
+            retain_objects(&mut self.description.objects, &|obj| {
+                !matches!(obj, Objects::Terminal(_))
+            });
+            self.description.objects.extend(
+                terminals
+                    .iter()
+                    .map(|term| Objects::Terminal(term.to_terminal(self.placement))),
+            );
+            ((self.width, self.height), (self.hotspot_x, self.hotspot_y)) =
+                Self::fit(&self.description);
+        }
+    }
+
+    /// The names, informations, link type and element informations of this element, the
+    /// terminals are left to the conversion
+    #[must_use]
+    pub fn metadata(&self) -> ElementMetadata {
+        ElementMetadata {
+            names: self
+                .names
+                .names
+                .iter()
+                .map(|name| (name.lang.clone(), name.value.clone()))
+                .collect(),
+            informations: Some(self.informations.clone()),
+            link_type: Some(self.link_type),
+            element_infos: self
+                .element_infos
+                .iter()
+                .flat_map(|infos| &infos.elem_info)
+                .map(|info| (info.name.clone(), info.value.clone()))
+                .collect(),
+            terminals: None,
+        }
+    }
+
+    //size and hotspot of an element made of `description`, snapped to the 10px grid
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn fit(description: &Description) -> ((i64, i64), (i64, i64)) {
         //The below calculation for width and hotspot_x are taken from the qet source code
         let (width, hotspot_x) = {
             let tmp_width = description.right_bound() - description.left_bound();
@@ -212,25 +388,193 @@ impl Definition {
             )
         };
 
-        Definition {
+        ((width, height), (hotspot_x, hotspot_y))
+    }
+
+    /// Replaces the graphics of this element with the ones of `converted`, keeping its uuid,
+    /// names, informations, element informations, terminals and the dynamic texts showing an
+    /// element information. The terminals of `converted` are only used when this element
+    /// has none. With `align_corner` the new graphics are moved so the top left corner of the
+    /// bounding box of `converted` lands on the one of this element, for drawings with
+    /// another origin.
+    #[must_use]
+    pub fn merge_geometry(mut self, converted: Definition, align_corner: bool) -> Self {
+        let shift = (
+            self.description.left_bound() - converted.description.left_bound(),
+            self.description.top_bound() - converted.description.top_bound(),
+        );
+        let mut kept = std::mem::take(&mut self.description.objects);
+        retain_objects(&mut kept, &|obj| match obj {
+            Objects::Terminal(_) => true,
+            Objects::DynamicText(dtxt) => dtxt.info_name.is_some(),
+            _ => false,
+        });
+        let has_terminals = kept
+            .iter()
+            .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+            .any(|obj| matches!(obj, Objects::Terminal(_)));
+        let info_names: HashSet<String> = kept
+            .iter()
+            .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+            .filter_map(|obj| match obj {
+                Objects::DynamicText(dtxt) => dtxt.info_name.clone(),
+                _ => None,
+            })
+            .collect();
+
+        let mut objects = converted.description.objects;
+        retain_objects(&mut objects, &|obj| match obj {
+            Objects::Terminal(_) => !has_terminals,
+            Objects::DynamicText(dtxt) => !dtxt
+                .info_name
+                .as_ref()
+                .is_some_and(|name| info_names.contains(name)),
+            _ => true,
+        });
+        self.placement = converted.placement;
+        if align_corner {
+            for obj in &mut objects {
+                obj.translate(shift.0, shift.1);
+            }
+            self.placement.shift.0 += shift.0;
+            self.placement.shift.1 += shift.1;
+        }
+
+        kept.extend(objects);
+        self.description.objects = kept;
+        ((self.width, self.height), (self.hotspot_x, self.hotspot_y)) = Self::fit(&self.description);
+        self
+    }
+
+    /// Reads an element back from the XML of an .elmt file, in element coordinates. Reading
+    /// what [`XMLElement::from`] wrote gives back an equal element, to the precision values
+    /// are written with: two decimals for coordinates, whole degrees for arc angles and whole
+    /// points for font sizes. What only matters while converting, like the grid displacement
+    /// or the reference rectangle of texts, isn't written and isn't compared.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the XML can't be parsed, isn't an element definition or one of
+    /// its primitives is missing an attribute or has an invalid value.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_xml(xml: &str) -> anyhow::Result<Self> {
+        let doc = Document::parse(xml).context("Invalid XML")?;
+        let def = doc.root_element();
+        if !def.has_tag_name("definition") {
+            bail!(
+                "Not an element definition, the root is <{}>",
+                def.tag_name().name()
+            );
+        }
+        let child = |name: &str| def.children().find(|n| n.has_tag_name(name));
+
+        let names = child("names").map_or_else(Vec::new, |names| {
+            names
+                .children()
+                .filter(|n| n.has_tag_name("name"))
+                .map(|n| Name {
+                    lang: n.attribute("lang").unwrap_or("en").into(),
+                    value: n.text().unwrap_or_default().into(),
+                })
+                .collect()
+        });
+
+        let element_infos = child("elementInformations").map(|infos| ElemInfos {
+            elem_info: infos
+                .children()
+                .filter(|n| n.has_tag_name("elementInformation"))
+                .map(|n| ElemInfo {
+                    name: n.attribute("name").unwrap_or_default().into(),
+                    show: n
+                        .attribute("show")
+                        .and_then(|show| show.trim().parse().ok())
+                        .unwrap_or(1),
+                    value: n.text().unwrap_or_default().into(),
+                })
+                .collect(),
+        });
+
+        Ok(Definition {
             r#type: ItemType::Element,
-            width,
-            height,
-            hotspot_x,
-            hotspot_y,
-            version: "0.8.0".into(),
-            link_type: LinkType::Simple,
-            uuid: Uuid::new_v4().into(),
-            names: Names {
-                names: vec![Name {
-                    lang: "en".into(),
-                    value: name.into(), //need to truncate the extension
-                }],
+            width: xml_f64(def, "width")?.round() as i64,
+            height: xml_f64(def, "height")?.round() as i64,
+            hotspot_x: xml_f64(def, "hotspot_x")?.round() as i64,
+            hotspot_y: xml_f64(def, "hotspot_y")?.round() as i64,
+            version: def.attribute("version").unwrap_or("0.8.0").into(),
+            link_type: xml_parse_or(def, "link_type", LinkType::Simple)?,
+            uuid: child("uuid")
+                .map_or_else(Uuid::new_v4, |n| xml_uuid(n, "uuid"))
+                .into(),
+            names: Names { names },
+            element_infos,
+            informations: child("informations")
+                .and_then(|n| n.text())
+                .unwrap_or_default()
+                .into(),
+            description: match child("description") {
+                Some(desc) => desc.try_into()?,
+                None => Description {
+                    objects: Vec::new(),
+                },
             },
-            element_infos: None,
-            informations: "Created using dxf2elmt!",
-            description,
-        }
+            grid_displacement: 0.0,
+            placement: Placement::default(),
+        })
+    }
+
+    /// Reads an .elmt file, see [`Self::from_xml`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't a valid element.
+    pub fn read_file(path: &Path) -> anyhow::Result<Self> {
+        let xml = std::fs::read_to_string(path)
+            .context(format!("Failed to read element file: {}", path.display()))?;
+        Self::from_xml(&xml).context(format!("Failed to read element {}", path.display()))
+    }
+
+    /// Width and height of the element
+    #[must_use]
+    pub fn size(&self) -> (i64, i64) {
+        (self.width, self.height)
+    }
+
+    /// Position of the origin of the element, from its top left corner
+    #[must_use]
+    pub fn hotspot(&self) -> (i64, i64) {
+        (self.hotspot_x, self.hotspot_y)
+    }
+
+    /// How far, in pixels, aligning the element to the grid moved the point that moved the
+    /// most. 0 when it wasn't aligned.
+    #[must_use]
+    pub fn grid_displacement(&self) -> f64 {
+        self.grid_displacement
+    }
+
+    /// How the conversion took points of the drawing into this element, the identity with
+    /// y pointing down for elements read from a file
+    #[must_use]
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    /// Every terminal of the element, including the ones inside groups
+    pub fn terminals(&self) -> impl Iterator<Item = &Terminal> {
+        self.description
+            .objects
+            .iter()
+            .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+            .filter_map(|obj| match obj {
+                Objects::Terminal(term) => Some(term),
+                _ => None,
+            })
+    }
+
+    /// Pixels per drawing unit, taking the units of the drawing into account
+    #[must_use]
+    pub fn unit_scale(drw: &Drawing, px_per_mm: f64) -> f64 {
+        Self::apply_unit_conversion(drw.header.default_drawing_units, px_per_mm)
     }
 
     // Apply unit conversion to the scale factor
@@ -240,11 +584,10 @@ impl Definition {
         // Unit conversions taken from: https://www.unitconverters.net/length-converter.html
         // Convert DXF units to millimeters, then multiply by px_per_mm to get pixels
         let unit_to_mm_factor = match unit {
-            Units::Unitless => 1.0, // No conversion for unitless
+            Units::Unitless | Units::Millimeters => 1.0, // Unitless drawings are taken as millimeters
             Units::Inches => 25.4, // 1 inch = 25.4 mm
             Units::Feet => 304.8, // 1 foot = 304.8 mm
             Units::Miles | Units::USSurveyMile => 1_609_344.0, // 1 mile = 1,609,344 mm
-            Units::Millimeters => 1.0,
             Units::Centimeters => 10.0, // 1 cm = 10 mm
             Units::Meters => 1_000.0, // 1 m = 1000 mm
             Units::Kilometers => 1_000_000.0, // 1 km = 1,000,000 mm
@@ -278,7 +621,7 @@ impl From<&Definition> for XMLElement {
         def_xml.add_attribute("hotspot_x", def.hotspot_x);
         def_xml.add_attribute("hotspot_y", def.hotspot_y);
         def_xml.add_attribute("version", &def.version);
-        def_xml.add_attribute("link_type", &def.link_type);
+        def_xml.add_attribute("link_type", def.link_type);
         def_xml.add_attribute("type", &def.r#type);
 
         def_xml.add_child((&def.uuid).into());
@@ -288,7 +631,7 @@ impl From<&Definition> for XMLElement {
         }
 
         let mut info_elmt = XMLElement::new("informations");
-        info_elmt.add_text(def.informations);
+        info_elmt.add_text(&def.informations);
         def_xml.add_child(info_elmt);
 
         def_xml.add_child((&def.description).into());
@@ -297,7 +640,7 @@ impl From<&Definition> for XMLElement {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Objects {
     Arc(Arc),
     Ellipse(Ellipse),
@@ -305,26 +648,83 @@ pub enum Objects {
     DynamicText(DynamicText),
     Text(Text),
     Line(Line),
-    //Terminal(Terminal),
+    Terminal(Terminal),
     Group(Vec<Objects>),
 }
 
 impl Objects {
+    /// Moves the object, and the children of a group, by `dx` and `dy` pixels
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            Objects::Arc(arc) => {
+                arc.x += dx;
+                arc.y += dy;
+            }
+            Objects::Ellipse(ell) => {
+                ell.x += dx;
+                ell.y += dy;
+            }
+            Objects::Polygon(poly) => {
+                for coord in &mut poly.coordinates {
+                    coord.x += dx;
+                    coord.y += dy;
+                }
+            }
+            Objects::DynamicText(dtxt) => {
+                dtxt.x += dx;
+                dtxt.y += dy;
+            }
+            Objects::Text(txt) => {
+                txt.x += dx;
+                txt.y += dy;
+            }
+            Objects::Line(line) => {
+                line.x1 += dx;
+                line.y1 += dy;
+                line.x2 += dx;
+                line.y2 += dy;
+            }
+            Objects::Terminal(term) => {
+                term.x += dx;
+                term.y += dy;
+            }
+            Objects::Group(children) => {
+                for obj in children {
+                    obj.translate(dx, dy);
+                }
+            }
+        }
+    }
+
+    #[must_use]
     pub fn descendants(&self) -> Descendants<'_> {
         Descendants {
             stack: vec![self.children()],
         }
     }
 
+    #[must_use]
     pub fn children(&self) -> Children<'_> {
         match self {
             Objects::Group(l) => Children { slice: l.iter() },
             _ => Children { slice: [].iter() },
         }
     }
+
+    fn set_style(&mut self, style: &str) {
+        match self {
+            Objects::Arc(arc) => arc.style = style.into(),
+            Objects::Ellipse(ellipse) => ellipse.style = style.into(),
+            Objects::Polygon(polygon) => polygon.style = style.into(),
+            Objects::Line(line) => line.style = style.into(),
+            Objects::Group(vec) => vec.iter_mut().for_each(|ob| ob.set_style(style)),
+            Objects::DynamicText(_) | Objects::Text(_) | Objects::Terminal(_) => {}
+        }
+    }
 }
 
-pub(crate) struct Descendants<'a> {
+/// The objects nested in a group, at any depth
+pub struct Descendants<'a> {
     stack: Vec<Children<'a>>,
 }
 
@@ -346,7 +746,8 @@ impl<'a> Iterator for Descendants<'a> {
     }
 }
 
-pub(crate) struct Children<'a> {
+/// The objects directly in a group
+pub struct Children<'a> {
     slice: std::slice::Iter<'a, Objects>,
 }
 
@@ -370,6 +771,7 @@ impl ScaleEntity for Objects {
             Objects::DynamicText(dynamic_text) => dynamic_text.scale(fact_x, fact_y),
             Objects::Text(text) => text.scale(fact_x, fact_y),
             Objects::Line(line) => line.scale(fact_x, fact_y),
+            Objects::Terminal(term) => term.scale(fact_x, fact_y),
             Objects::Group(vec) => vec.iter_mut().for_each(|ob| ob.scale(fact_x, fact_y)),
         }
     }
@@ -382,6 +784,7 @@ impl ScaleEntity for Objects {
             Objects::DynamicText(dynamic_text) => dynamic_text.left_bound(),
             Objects::Text(text) => text.left_bound(),
             Objects::Line(line) => line.left_bound(),
+            Objects::Terminal(term) => term.left_bound(),
             Objects::Group(vec) => {
                 let lb = vec.iter().min_by(|ob1, ob2| {
                     ob1.left_bound()
@@ -406,6 +809,7 @@ impl ScaleEntity for Objects {
             Objects::DynamicText(dynamic_text) => dynamic_text.right_bound(),
             Objects::Text(text) => text.right_bound(),
             Objects::Line(line) => line.right_bound(),
+            Objects::Terminal(term) => term.right_bound(),
             Objects::Group(vec) => {
                 let rb = vec.iter().max_by(|ob1, ob2| {
                     ob1.right_bound()
@@ -430,6 +834,7 @@ impl ScaleEntity for Objects {
             Objects::DynamicText(dynamic_text) => dynamic_text.top_bound(),
             Objects::Text(text) => text.top_bound(),
             Objects::Line(line) => line.top_bound(),
+            Objects::Terminal(term) => term.top_bound(),
             Objects::Group(vec) => {
                 let tb = vec.iter().min_by(|ob1, ob2| {
                     ob1.top_bound()
@@ -454,6 +859,7 @@ impl ScaleEntity for Objects {
             Objects::DynamicText(dynamic_text) => dynamic_text.bot_bound(),
             Objects::Text(text) => text.bot_bound(),
             Objects::Line(line) => line.bot_bound(),
+            Objects::Terminal(term) => term.bot_bound(),
             Objects::Group(vec) => {
                 let bb = vec.iter().max_by(|ob1, ob2| {
                     ob1.bot_bound()
@@ -489,32 +895,547 @@ pub(crate) struct Offset {
     y: f64,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextMode {
+    /// Texts become dynamic texts that can be edited in the element editor
+    #[default]
+    Dynamic,
+    /// Texts become static texts
+    Static,
+}
+
+impl FromStr for TextMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dynamic" => Ok(Self::Dynamic),
+            "static" => Ok(Self::Static),
+            _ => Err(format!("Unknown text mode {s}, expected dynamic or static")),
+        }
+    }
+}
+
+impl Display for TextMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Dynamic => "dynamic",
+                Self::Static => "static",
+            }
+        )
+    }
+}
+
+/// How the graphics of the drawing are sized into the element
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ScaleMode {
+    /// Drawing units converted to millimetres, times `px_per_mm`
+    #[default]
+    Ratio,
+    /// The graphics are this many pixels wide
+    Width(f64),
+    /// The graphics are this many pixels high
+    Height(f64),
+    /// The graphics are as big as they can be in a box of this width and height in pixels,
+    /// keeping their aspect ratio
+    Fit(f64, f64),
+    /// The two closest terminals are this many steps of the grid apart
+    Pitch(f64),
+}
+
+impl ScaleMode {
+    /// Factor from drawing units to element pixels for `description`, which is still in
+    /// drawing units. Falls back to `unit_scale`, the one of the px/mm ratio, when there
+    /// is nothing to measure, e.g. a description without size or with less than two terminals.
+    #[must_use]
+    pub fn factor(self, description: &Description, unit_scale: f64) -> f64 {
+        let width = description.right_bound() - description.left_bound();
+        let height = description.bot_bound() - description.top_bound();
+        let fit = |target: f64, size: f64| (size > f64::EPSILON).then_some(target / size);
+        let factor = match self {
+            Self::Ratio => return unit_scale,
+            Self::Width(w) => fit(w, width),
+            Self::Height(h) => fit(h, height),
+            Self::Fit(w, h) => match (fit(w, width), fit(h, height)) {
+                (Some(fx), Some(fy)) => Some(fx.min(fy)),
+                (fx, fy) => fx.or(fy),
+            },
+            Self::Pitch(steps) => description
+                .terminal_pitch()
+                .and_then(|pitch| fit(steps * GRID_STEP, pitch)),
+        };
+        factor.unwrap_or_else(|| {
+            warn!("Nothing to measure for scale mode {self}, using the px/mm ratio");
+            unit_scale
+        })
+    }
+}
+
+impl FromStr for ScaleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            format!(
+                "Unknown scale mode {s}, expected ratio, width:<px>, height:<px>, fit:<width>x<height> or pitch:<grid steps>"
+            )
+        };
+        let number = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .ok_or_else(|| format!("Scale mode {s} needs positive numbers"))
+        };
+
+        let lower = s.to_ascii_lowercase();
+        let (mode, value) = lower.split_once(':').unwrap_or((lower.as_str(), ""));
+        match mode.trim() {
+            "ratio" if value.is_empty() => Ok(Self::Ratio),
+            "width" => Ok(Self::Width(number(value)?)),
+            "height" => Ok(Self::Height(number(value)?)),
+            "fit" => {
+                let (w, h) = value.split_once('x').ok_or_else(err)?;
+                Ok(Self::Fit(number(w)?, number(h)?))
+            }
+            "pitch" => Ok(Self::Pitch(number(value)?)),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl Display for ScaleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ratio => write!(f, "ratio"),
+            Self::Width(w) => write!(f, "width:{w}"),
+            Self::Height(h) => write!(f, "height:{h}"),
+            Self::Fit(w, h) => write!(f, "fit:{w}x{h}"),
+            Self::Pitch(steps) => write!(f, "pitch:{steps}"),
+        }
+    }
+}
+
+impl TryFrom<String> for ScaleMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ScaleMode> for String {
+    fn from(mode: ScaleMode) -> Self {
+        mode.to_string()
+    }
+}
+
+/// Point of the element put on the grid when aligning it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GridAnchor {
+    /// The hotspot is always on the grid, so nothing is moved and only the primitives
+    /// chosen to snap are
+    Hotspot,
+    /// The first terminal of the element
+    Terminal,
+    /// The top left corner of the bounding box
+    Corner,
+}
+
+impl FromStr for GridAnchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hotspot" => Ok(Self::Hotspot),
+            "terminal" => Ok(Self::Terminal),
+            "corner" => Ok(Self::Corner),
+            _ => Err(format!(
+                "Unknown grid anchor {s}, expected hotspot, terminal or corner"
+            )),
+        }
+    }
+}
+
+impl Display for GridAnchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Hotspot => "hotspot",
+                Self::Terminal => "terminal",
+                Self::Corner => "corner",
+            }
+        )
+    }
+}
+
+impl TryFrom<String> for GridAnchor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<GridAnchor> for String {
+    fn from(value: GridAnchor) -> Self {
+        value.to_string()
+    }
+}
+
+/// Kind of primitive whose coordinates can be snapped to the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GridSnap {
+    Terminals,
+    /// Both ends of lines
+    Lines,
+    /// Every point of polygons
+    Polygons,
+    /// Centres of arcs, their size is kept
+    Arcs,
+    /// Centres of ellipses and circles, their size is kept
+    Ellipses,
+    /// Positions of static and dynamic texts
+    Texts,
+}
+
+impl GridSnap {
+    pub const ALL: [GridSnap; 6] = [
+        Self::Terminals,
+        Self::Lines,
+        Self::Polygons,
+        Self::Arcs,
+        Self::Ellipses,
+        Self::Texts,
+    ];
+
+    fn matches(self, obj: &Objects) -> bool {
+        matches!(
+            (self, obj),
+            (Self::Terminals, Objects::Terminal(_))
+                | (Self::Lines, Objects::Line(_))
+                | (Self::Polygons, Objects::Polygon(_))
+                | (Self::Arcs, Objects::Arc(_))
+                | (Self::Ellipses, Objects::Ellipse(_))
+                | (Self::Texts, Objects::Text(_) | Objects::DynamicText(_))
+        )
+    }
+}
+
+impl FromStr for GridSnap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|snap| snap.to_string() == s.to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown primitive to snap {s}, expected terminals, lines, polygons, arcs, ellipses or texts"
+                )
+            })
+    }
+}
+
+impl Display for GridSnap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Terminals => "terminals",
+                Self::Lines => "lines",
+                Self::Polygons => "polygons",
+                Self::Arcs => "arcs",
+                Self::Ellipses => "ellipses",
+                Self::Texts => "texts",
+            }
+        )
+    }
+}
+
+impl TryFrom<String> for GridSnap {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<GridSnap> for String {
+    fn from(value: GridSnap) -> Self {
+        value.to_string()
+    }
+}
+
+/// What aligning a description to the grid did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridAlignment {
+    /// How far the whole description was moved to put the anchor on the grid, in pixels
+    pub shift: (f64, f64),
+    /// How far the point that moved the most went, including the snapping, in pixels
+    pub displacement: f64,
+}
+
+/// How a conversion takes a point of the drawing into the element: scaled by `factor` with
+/// y pointing down, then moved by `shift` to put the grid anchor on the grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub factor: f64,
+    pub shift: (f64, f64),
+    /// Terminals were snapped to the grid
+    pub snap_terminals: bool,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            shift: (0.0, 0.0),
+            snap_terminals: false,
+        }
+    }
+}
+
+impl Placement {
+    /// Element coordinates of the point (`x`, `y`) of the drawing
+    #[must_use]
+    pub fn to_element(self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.factor + self.shift.0, -y * self.factor + self.shift.1)
+    }
+
+    /// Drawing coordinates of the point (`x`, `y`) of the element, the reverse of
+    /// [`Self::to_element`]
+    #[must_use]
+    pub fn to_drawing(self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.shift.0) / self.factor, -(y - self.shift.1) / self.factor)
+    }
+
+    /// Element coordinates of a terminal placed at (`x`, `y`) in the drawing, on the grid
+    /// when the terminals of the conversion were snapped to it
+    #[must_use]
+    pub fn terminal_to_element(self, x: f64, y: f64) -> (f64, f64) {
+        let (mut x, mut y) = self.to_element(x, y);
+        if self.snap_terminals {
+            snap_to_grid(&mut x, &mut y);
+        }
+        (x, y)
+    }
+}
+
+//moves `x` and `y` onto the closest grid point, returning how much they moved
+fn snap_to_grid(x: &mut f64, y: &mut f64) -> (f64, f64) {
+    let (sx, sy) = (
+        (*x / GRID_STEP).round() * GRID_STEP,
+        (*y / GRID_STEP).round() * GRID_STEP,
+    );
+    let moved = (sx - *x, sy - *y);
+    (*x, *y) = (sx, sy);
+    moved
+}
+
+fn glob_set(patterns: &[String]) -> anyhow::Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pat in patterns {
+        builder.add(Glob::new(pat).context(format!("Invalid glob pattern: {pat}"))?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// The layer and block name based rules from the [`ConversionOptions`], with the
+/// patterns compiled once so they can be checked for every entity
+#[derive(Debug, Default)]
+pub struct EntityRules {
+    //layers that are off, frozen or not plotted, upper cased as layer names aren't case sensitive
+    hidden_layers: HashSet<String>,
+    include_layers: Option<GlobSet>,
+    exclude_layers: Option<GlobSet>,
+    layer_styles: Vec<(GlobMatcher, String)>,
+    terminal_layers: Option<GlobSet>,
+    terminal_blocks: Option<GlobSet>,
+    exclude_blocks: Option<GlobSet>,
+}
+
+impl EntityRules {
+    /// # Errors
+    ///
+    /// Returns an error if one of the layer or block patterns isn't a valid glob.
+    pub fn new(options: &ConversionOptions, drw: &Drawing) -> anyhow::Result<Self> {
+        Ok(Self {
+            hidden_layers: if options.skip_hidden_layers {
+                drw.layers()
+                    .filter(|l| !l.is_layer_on || !l.is_layer_plotted)
+                    .map(|l| l.name.to_uppercase())
+                    .collect()
+            } else {
+                HashSet::new()
+            },
+            include_layers: glob_set(&options.include_layers)?,
+            exclude_layers: glob_set(&options.exclude_layers)?,
+            layer_styles: options
+                .layer_styles
+                .iter()
+                .map(|(pat, style)| {
+                    let glob = Glob::new(pat).context(format!("Invalid glob pattern: {pat}"))?;
+                    Ok((glob.compile_matcher(), style.clone()))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            terminal_layers: glob_set(&options.terminal_layers)?,
+            terminal_blocks: glob_set(&options.terminal_blocks)?,
+            exclude_blocks: glob_set(&options.exclude_blocks)?,
+        })
+    }
+
+    /// Whether the entity should be converted, it has to be visible, on a layer that
+    /// isn't hidden or filtered out, and not an insert of an excluded block
+    #[must_use]
+    pub fn allows(&self, ent: &Entity) -> bool {
+        self.allows_on(ent, &ent.common.layer)
+    }
+
+    fn allows_on(&self, ent: &Entity, layer: &str) -> bool {
+        let block_allowed = match &ent.specific {
+            EntityType::Insert(ins) => self.block_allowed(&ins.name),
+            _ => true,
+        };
+        ent.common.is_visible && block_allowed && self.layer_allowed(layer)
+    }
+
+    #[must_use]
+    pub fn block_allowed(&self, name: &str) -> bool {
+        !self
+            .exclude_blocks
+            .as_ref()
+            .is_some_and(|gs| gs.is_match(name))
+    }
+
+    #[must_use]
+    pub fn layer_allowed(&self, layer: &str) -> bool {
+        !self.hidden_layers.contains(&layer.to_uppercase())
+            && self.include_layers
+            .as_ref()
+            .map_or(true, |gs| gs.is_match(layer))
+            && !self
+                .exclude_layers
+                .as_ref()
+                .is_some_and(|gs| gs.is_match(layer))
+    }
+
+    fn style_for(&self, layer: &str) -> Option<&str> {
+        self.layer_styles
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(layer))
+            .map(|(_, style)| style.as_str())
+    }
+
+    fn is_terminal(&self, ent: &Entity, layer: &str) -> bool {
+        let on_term_layer = self
+            .terminal_layers
+            .as_ref()
+            .is_some_and(|gs| gs.is_match(layer));
+        match &ent.specific {
+            EntityType::Insert(ins) => {
+                on_term_layer
+                    || self
+                        .terminal_blocks
+                        .as_ref()
+                        .is_some_and(|gs| gs.is_match(&ins.name))
+            }
+            EntityType::ModelPoint(_) | EntityType::Circle(_) => on_term_layer,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ObjectsBuilder<'a> {
     ent: &'a Entity,
     spline_step: u32,
     px_per_mm: f64,
+    text_mode: TextMode,
+    rules: Option<&'a EntityRules>,
+    parent_layer: Option<&'a str>,
     blocks: &'a [&'a Block],
     offset: Offset,
     scale_fact: ScaleFactor,
+    cancel: Option<&'a CancelToken>,
 }
 
 impl<'a> ObjectsBuilder<'a> {
+    #[must_use]
     pub fn new(ent: &'a Entity, spline_step: u32, px_per_mm: f64) -> Self {
         Self {
             ent,
             spline_step,
             px_per_mm,
+            text_mode: TextMode::default(),
+            rules: None,
+            parent_layer: None,
             blocks: &[],
             offset: Offset::default(),
             scale_fact: ScaleFactor::default(),
+            cancel: None,
         }
     }
 
+    #[must_use]
     pub fn blocks(self, blocks: &'a [&'a Block]) -> Self {
         Self { blocks, ..self }
     }
 
+    #[must_use]
+    pub fn text_mode(self, text_mode: TextMode) -> Self {
+        Self { text_mode, ..self }
+    }
+
+    #[must_use]
+    pub fn rules(self, rules: &'a EntityRules) -> Self {
+        Self {
+            rules: Some(rules),
+            ..self
+        }
+    }
+
+    /// Token checked before building every entity, the entities of blocks included
+    #[must_use]
+    pub fn cancel(self, cancel: &'a CancelToken) -> Self {
+        Self {
+            cancel: Some(cancel),
+            ..self
+        }
+    }
+
+    /// Layer of the INSERT the entity is part of, entities of a block on layer 0
+    /// take the layer of the insert
+    #[must_use]
+    pub fn parent_layer(self, layer: &'a str) -> Self {
+        Self {
+            parent_layer: Some(layer),
+            ..self
+        }
+    }
+
+    fn layer(&self) -> &'a str {
+        match self.parent_layer {
+            Some(parent) if self.ent.common.layer == "0" => parent,
+            _ => &self.ent.common.layer,
+        }
+    }
+
+    #[must_use]
     pub fn offsets(self, x: f64, y: f64) -> Self {
         Self {
             offset: Offset { x, y },
@@ -522,6 +1443,7 @@ impl<'a> ObjectsBuilder<'a> {
         }
     }
 
+    #[must_use]
     pub fn scaling(self, fact_x: f64, fact_y: f64) -> Self {
         Self {
             scale_fact: ScaleFactor {
@@ -532,8 +1454,68 @@ impl<'a> ObjectsBuilder<'a> {
         }
     }
 
-    #[allow(clippy::too_many_lines)]
+    //the 24 bit color of the entity, dxf keeps it in an i32
+    #[allow(clippy::cast_sign_loss)]
+    fn color(&self) -> HexColor {
+        HexColor::from_u32(self.ent.common.color_24_bit as u32)
+    }
+
+    /// Converts the entity, and the ones of its block for inserts
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the conversion was cancelled, the entity is filtered out by the
+    /// rules, is empty or isn't supported.
     pub fn build(self) -> Result<Objects, &'static str /*add better error later*/> {
+        if self.cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err("Conversion cancelled");
+        }
+
+        let Some(rules) = self.rules else {
+            return self.build_entity();
+        };
+
+        let layer = self.layer();
+        if !rules.allows_on(self.ent, layer) {
+            return Err("Entity is hidden or on a filtered layer");
+        }
+
+        if rules.is_terminal(self.ent, layer) {
+            return self.build_terminal();
+        }
+
+        let mut obj = self.build_entity()?;
+        if let Some(style) = rules.style_for(layer) {
+            obj.set_style(style);
+        }
+        Ok(obj)
+    }
+
+    fn build_terminal(self) -> Result<Objects, &'static str> {
+        let (x, y, orientation) = match &self.ent.specific {
+            EntityType::Insert(ins) => (
+                ins.location.x,
+                ins.location.y,
+                Some(TermOrient::from_rotation(ins.rotation)),
+            ),
+            EntityType::ModelPoint(pt) => (pt.location.x, pt.location.y, None),
+            EntityType::Circle(circ) => (circ.center.x, circ.center.y, None),
+            _ => return Err("Entity can't be converted into a terminal"),
+        };
+
+        //points and circles don't say which way the terminal is facing, they get oriented
+        //towards the closest edge once the whole element is known
+        let mut term = Terminal::new(x, -y, orientation.unwrap_or(TermOrient::North));
+        term.auto_orient = orientation.is_none();
+        term.scale(self.scale_fact.x, self.scale_fact.y);
+        term.x += self.offset.x;
+        term.y -= self.offset.y;
+
+        Ok(Objects::Terminal(term))
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn build_entity(self) -> Result<Objects, &'static str /*add better error later*/> {
         match &self.ent.specific {
             EntityType::Circle(circle) => {
                 let mut ellipse: Ellipse = circle.into();
@@ -593,11 +1575,10 @@ impl<'a> ObjectsBuilder<'a> {
                     //to enable it...I'm wondering if it makes more sense to default to use dynamic text
                     //for now I'll set it to use dynamic text, and once I get the CLI flag passing through
                     //I might change the default parameter to use Dynamic Text
-                    if false {
-                        //how best to pass in the flag for dynamic text or not....should the flag also default to true?
+                    if self.text_mode == TextMode::Static {
                         let mut text: Text = (
                             text,
-                            HexColor::from_u32(self.ent.common.color_24_bit as u32),
+                            self.color(),
                         )
                             .into();
 
@@ -616,7 +1597,7 @@ impl<'a> ObjectsBuilder<'a> {
                         Objects::Text(text)
                     } else {
                         let mut dtext = DTextBuilder::from_text(text)
-                            .color(HexColor::from_u32(self.ent.common.color_24_bit as u32))
+                            .color(self.color())
                             .build();
 
                         dtext.scale(self.scale_fact.x, self.scale_fact.y);
@@ -645,17 +1626,9 @@ impl<'a> ObjectsBuilder<'a> {
                     //to enable it...I'm wondering if it makes more sense to default to use dynamic text
                     //for now I'll set it to use dynamic text, and once I get the CLI flag passing through
                     //I might change the default parameter to use Dynamic Text
-                    if false {
-                        //how best to pass in the flag for dynamic text or not....should the flag also default to true?
-                        /*let mut text: Text =
-                            (mtext, HexColor::from_u32(ent.common.color_24_bit as u32)).into();
-                            text.x += offset_x;
-                            text.y -= offset_y;
-                        Objects::Text(text)*/
-                        todo!();
-                    } else {
+                    {
                         let mut dtext = DTextBuilder::from_mtext(mtext)
-                            .color(HexColor::from_u32(self.ent.common.color_24_bit as u32))
+                            .color(self.color())
                             .build();
 
                         dtext.scale(self.scale_fact.x, self.scale_fact.y);
@@ -663,7 +1636,13 @@ impl<'a> ObjectsBuilder<'a> {
                         dtext.x += self.offset.x;
                         dtext.y -= self.offset.y;
 
-                        Objects::DynamicText(dtext)
+                        //there is no direct MText conversion into a static Text, so reuse the
+                        //dynamic text builder which already handles the MTEXT formatting codes
+                        if self.text_mode == TextMode::Static {
+                            Objects::Text(dtext.into())
+                        } else {
+                            Objects::DynamicText(dtext)
+                        }
                     },
                 )
             }
@@ -774,7 +1753,7 @@ impl<'a> ObjectsBuilder<'a> {
                         .entities
                         .iter()
                         .filter_map(|ent| {
-                            ObjectsBuilder::new(ent, self.spline_step, self.px_per_mm)
+                            let builder = ObjectsBuilder::new(ent, self.spline_step, self.px_per_mm)
                                 .offsets(
                                     ins.location.x - block.base_point.x,
                                     ins.location.y - block.base_point.y,
@@ -784,8 +1763,18 @@ impl<'a> ObjectsBuilder<'a> {
                                     self.scale_fact.y * ins.y_scale_factor,
                                 )
                                 .blocks(self.blocks)
-                                .build()
-                                .ok()
+                                .text_mode(self.text_mode)
+                                .parent_layer(self.layer());
+                            let builder = match self.cancel {
+                                Some(cancel) => builder.cancel(cancel),
+                                None => builder,
+                            };
+                            match self.rules {
+                                Some(rules) => builder.rules(rules),
+                                None => builder,
+                            }
+                            .build()
+                            .ok()
                         })
                         .collect(),
                 ))
@@ -812,7 +1801,7 @@ impl<'a> ObjectsBuilder<'a> {
             EntityType::AttributeDefinition(attrib) => Ok({
                 //need to look up the proper way to get the color for the Attrib
                 let mut dtext = DTextBuilder::from_attrib(attrib)
-                    .color(HexColor::from_u32(self.ent.common.color_24_bit as u32))
+                    .color(self.color())
                     .build();
 
                 dtext.scale(self.scale_fact.x, self.scale_fact.y);
@@ -830,26 +1819,6 @@ impl<'a> ObjectsBuilder<'a> {
     }
 }
 
-impl From<&Objects> for Either<XMLElement, Vec<XMLElement>> {
-    fn from(obj: &Objects) -> Self {
-        match obj {
-            Objects::Arc(arc) => Either::Left(arc.into()),
-            Objects::Ellipse(ell) => Either::Left(ell.into()),
-            Objects::Polygon(poly) => Either::Left(poly.into()),
-            Objects::DynamicText(dtext) => Either::Left(dtext.into()),
-            Objects::Text(txt) => Either::Left(txt.into()),
-            Objects::Line(line) => Either::Left(line.into()),
-            Objects::Group(block) => Either::Right(
-                block
-                    .iter()
-                    //.flatten()
-                    .filter_map(|obj| XMLElement::try_from(obj).ok())
-                    .collect(),
-            ),
-        }
-    }
-}
-
 impl TryFrom<&Objects> for XMLElement {
     type Error = &'static str; // add better error later
 
@@ -861,16 +1830,163 @@ impl TryFrom<&Objects> for XMLElement {
             Objects::DynamicText(dtext) => Ok(dtext.into()),
             Objects::Text(txt) => Ok(txt.into()),
             Objects::Line(line) => Ok(line.into()),
+            Objects::Terminal(term) => Ok(term.into()),
             Objects::Group(_) => Err("Unsupported"),
         }
     }
 }
 
-#[derive(Debug)]
+//primitives of an .elmt description that can be read back, circles and rectangles
+//come from elements made with older versions of QET
+const ELMT_PRIMITIVES: [&str; 9] = [
+    "arc",
+    "ellipse",
+    "circle",
+    "polygon",
+    "rect",
+    "dynamic_text",
+    "text",
+    "line",
+    "terminal",
+];
+
+impl TryFrom<Node<'_, '_>> for Objects {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        Ok(match node.tag_name().name() {
+            "arc" => Objects::Arc(node.try_into()?),
+            "ellipse" | "circle" => Objects::Ellipse(node.try_into()?),
+            "polygon" | "rect" => Objects::Polygon(node.try_into()?),
+            "dynamic_text" => Objects::DynamicText(node.try_into()?),
+            "text" => Objects::Text(node.try_into()?),
+            "line" => Objects::Line(node.try_into()?),
+            "terminal" => Objects::Terminal(node.try_into()?),
+            tag => bail!("Unsupported primitive <{tag}>"),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Description {
     pub objects: Vec<Objects>,
 }
 
+impl Description {
+    /// Moves the description so `anchor` lands on the grid, then snaps the points of the
+    /// primitives of the `snap` kinds to it
+    pub fn align_to_grid(
+        &mut self,
+        anchor: Option<GridAnchor>,
+        snap: &[GridSnap],
+    ) -> GridAlignment {
+        fn snap_objects(
+            objects: &mut [Objects],
+            snap: &[GridSnap],
+            shift: (f64, f64),
+            max: &mut f64,
+        ) {
+            for obj in objects {
+                if let Objects::Group(children) = obj {
+                    snap_objects(children, snap, shift, max);
+                    continue;
+                }
+                if !snap.iter().any(|kind| kind.matches(obj)) {
+                    continue;
+                }
+                let mut points = Vec::new();
+                match obj {
+                    Objects::Terminal(term) => points.push(snap_to_grid(&mut term.x, &mut term.y)),
+                    Objects::Line(line) => {
+                        points.push(snap_to_grid(&mut line.x1, &mut line.y1));
+                        points.push(snap_to_grid(&mut line.x2, &mut line.y2));
+                    }
+                    Objects::Polygon(poly) => points.extend(
+                        poly.coordinates
+                            .iter_mut()
+                            .map(|coord| snap_to_grid(&mut coord.x, &mut coord.y)),
+                    ),
+                    Objects::Arc(arc) => {
+                        let (mut cx, mut cy) = (arc.x + arc.width / 2.0, arc.y + arc.height / 2.0);
+                        let (dx, dy) = snap_to_grid(&mut cx, &mut cy);
+                        (arc.x, arc.y) = (arc.x + dx, arc.y + dy);
+                        points.push((dx, dy));
+                    }
+                    Objects::Ellipse(ell) => {
+                        let (mut cx, mut cy) = (ell.x + ell.width / 2.0, ell.y + ell.height / 2.0);
+                        let (dx, dy) = snap_to_grid(&mut cx, &mut cy);
+                        (ell.x, ell.y) = (ell.x + dx, ell.y + dy);
+                        points.push((dx, dy));
+                    }
+                    Objects::Text(txt) => points.push(snap_to_grid(&mut txt.x, &mut txt.y)),
+                    Objects::DynamicText(dtxt) => {
+                        points.push(snap_to_grid(&mut dtxt.x, &mut dtxt.y));
+                    }
+                    Objects::Group(_) => {}
+                }
+                for (dx, dy) in points {
+                    *max = max.max((shift.0 + dx).hypot(shift.1 + dy));
+                }
+            }
+        }
+
+        let (dx, dy) = match anchor {
+            None | Some(GridAnchor::Hotspot) => (0.0, 0.0),
+            Some(GridAnchor::Terminal) => {
+                let first = self
+                    .objects
+                    .iter()
+                    .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+                    .find_map(|obj| match obj {
+                        Objects::Terminal(term) => Some((term.x, term.y)),
+                        _ => None,
+                    });
+                if let Some((mut x, mut y)) = first {
+                    snap_to_grid(&mut x, &mut y)
+                } else {
+                    warn!("No terminal to align to the grid, the element is left where it is");
+                    (0.0, 0.0)
+                }
+            }
+            Some(GridAnchor::Corner) => {
+                let (mut x, mut y) = (self.left_bound(), self.top_bound());
+                snap_to_grid(&mut x, &mut y)
+            }
+        };
+        let mut max = dx.hypot(dy);
+        if max > 0.0 {
+            self.objects
+                .iter_mut()
+                .for_each(|obj| obj.translate(dx, dy));
+        }
+        snap_objects(&mut self.objects, snap, (dx, dy), &mut max);
+        GridAlignment {
+            shift: (dx, dy),
+            displacement: max,
+        }
+    }
+
+    /// Shortest distance between two terminals, `None` with less than two of them
+    #[must_use]
+    pub fn terminal_pitch(&self) -> Option<f64> {
+        let terminals: Vec<&Terminal> = self
+            .objects
+            .iter()
+            .flat_map(|obj| std::iter::once(obj).chain(obj.descendants()))
+            .filter_map(|obj| match obj {
+                Objects::Terminal(term) => Some(term),
+                _ => None,
+            })
+            .collect();
+        terminals
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| (a.x - b.x).hypot(a.y - b.y))
+            .filter(|d| *d > f64::EPSILON)
+            .min_by(f64::total_cmp)
+    }
+}
+
 impl ScaleEntity for Description {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.objects
@@ -900,7 +2016,7 @@ impl ScaleEntity for Description {
         });
 
         if let Some(rb) = rb {
-            rb.left_bound()
+            rb.right_bound()
         } else {
             0.0
         }
@@ -928,13 +2044,50 @@ impl ScaleEntity for Description {
         });
 
         if let Some(bb) = bb {
-            bb.top_bound()
+            bb.bot_bound()
         } else {
             0.0
         }
     }
 }
 
+impl Description {
+    /// Points the terminals that don't have an orientation yet towards the closest edge of
+    /// the element, which is where the conductor will usually be connected from
+    fn orient_terminals(&mut self) {
+        fn orient(objects: &mut [Objects], bounds: (f64, f64, f64, f64)) {
+            let (left, right, top, bot) = bounds;
+            for obj in objects {
+                match obj {
+                    Objects::Terminal(term) if term.auto_orient => {
+                        term.orientation = [
+                            (term.y - top, TermOrient::North),
+                            (right - term.x, TermOrient::East),
+                            (bot - term.y, TermOrient::South),
+                            (term.x - left, TermOrient::West),
+                        ]
+                        .into_iter()
+                        .min_by(|(d1, _), (d2, _)| {
+                            d1.partial_cmp(d2).unwrap_or(std::cmp::Ordering::Greater)
+                        })
+                        .map_or(TermOrient::North, |(_, orient)| orient);
+                    }
+                    Objects::Group(vec) => orient(vec, bounds),
+                    _ => {}
+                }
+            }
+        }
+
+        let bounds = (
+            self.left_bound(),
+            self.right_bound(),
+            self.top_bound(),
+            self.bot_bound(),
+        );
+        orient(&mut self.objects, bounds);
+    }
+}
+
 impl From<&Description> for XMLElement {
     fn from(desc: &Description) -> Self {
         let mut desc_xml = XMLElement::new("description");
@@ -952,6 +2105,26 @@ impl From<&Description> for XMLElement {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Description {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        let mut objects = Vec::new();
+        for child in node.children().filter(Node::is_element) {
+            let tag = child.tag_name().name();
+            if !ELMT_PRIMITIVES.contains(&tag) {
+                warn!("Skipping unsupported primitive <{tag}>");
+                continue;
+            }
+            let row = node.document().text_pos_at(child.range().start).row;
+            objects.push(
+                Objects::try_from(child).context(format!("Invalid <{tag}> on line {row}"))?,
+            );
+        }
+        Ok(Description { objects })
+    }
+}
+
 /*impl TryFrom<Drawing> for Description {
     type Error = &'static str; //add better error later
 
@@ -959,17 +2132,70 @@ impl From<&Description> for XMLElement {
         drw.entities().filter_map(|ent| Objects::try_from(ent).ok()).collect();
     }
 }*/
-impl From<(&Drawing, u32, f64)> for Description {
-    fn from((drw, spline_step, px_per_mm): (&Drawing, u32, f64)) -> Self {
-        let _from_drw_span = span!(Level::TRACE, "Converting Drawing to Description");
+impl Description {
+    /// Converts the entities of `block`, moved so its base point ends up at the origin
+    fn from_block(drw: &Drawing, block: &Block, options: &ConversionOptions) -> anyhow::Result<Self> {
+        let _from_block_span = span!(Level::TRACE, "Converting Block to Description");
+        let rules = EntityRules::new(options, drw)?;
+        let blocks: Vec<&Block> = drw.blocks().collect();
+        trace!(
+            "Creating Description from block {}. Base Point(x:{}, y:{})",
+            block.name,
+            block.base_point.x,
+            block.base_point.y
+        );
+
+        Ok(Self {
+            objects: block
+                .entities
+                .iter()
+                .filter_map(|ent| {
+                    ObjectsBuilder::new(ent, options.spline_step, options.px_per_mm)
+                        .offsets(-block.base_point.x, -block.base_point.y)
+                        .blocks(&blocks)
+                        .text_mode(options.text_mode)
+                        .rules(&rules)
+                        .cancel(&options.progress.cancel)
+                        .build()
+                        .ok()
+                })
+                .collect(),
+        })
+    }
 
-        Self {
-            objects: drw
-                .entities()
-                .filter_map(|ent| match &ent.specific {
-                    EntityType::Insert(ins) => {
+    /// Converts the entities of the drawing, keeping track of the entity each object
+    /// came from. Entities that are filtered out or can't be converted are left out.
+    /// Stops early, with what was built so far, once the conversion is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the layer or block patterns isn't a valid glob.
+    pub fn entity_objects<'a>(
+        drw: &'a Drawing,
+        options: &ConversionOptions,
+    ) -> anyhow::Result<Vec<(&'a Entity, Objects)>> {
+        let spline_step = options.spline_step;
+        let px_per_mm = options.px_per_mm;
+        let rules = EntityRules::new(options, drw)?;
+        let blocks: Vec<&Block> = drw.blocks().collect();
+        let progress = &options.progress;
+        let cancel = &progress.cancel;
+        let total = drw.entities().count();
+
+        let objects: Vec<_> = drw
+            .entities()
+            .enumerate()
+            .take_while(|(done, _)| {
+                progress.entities(*done, total);
+                !cancel.is_cancelled()
+            })
+            .map(|(_, ent)| ent)
+            .filter(|ent| rules.allows(ent))
+            .filter_map(|ent| {
+                let obj = match &ent.specific {
+                    EntityType::Insert(ins) if !rules.is_terminal(ent, &ent.common.layer) => {
+                        let ins_layer = &ent.common.layer;
                         let block = find_block(drw, &ins.name)?;
-                        let blocks: Vec<&Block> = drw.blocks().collect();
                         trace!(
                             "Creating Group from block {}. Pos(x:{}, y:{}). Scale(x:{}, y:{})",
                             ins.name,
@@ -978,7 +2204,7 @@ impl From<(&Drawing, u32, f64)> for Description {
                             ins.x_scale_factor,
                             ins.y_scale_factor
                         );
-                        Some(Objects::Group(
+                        Objects::Group(
                             block
                                 .entities
                                 .iter()
@@ -990,33 +2216,48 @@ impl From<(&Drawing, u32, f64)> for Description {
                                         .offsets(ins.location.x, ins.location.y)
                                         .scaling(ins.x_scale_factor, ins.y_scale_factor)
                                         .blocks(&blocks)
+                                        .text_mode(options.text_mode)
+                                        .parent_layer(ins_layer)
+                                        .rules(&rules)
+                                        .cancel(cancel)
                                         .build()
                                         .ok()
                                 })
                                 .collect(),
-                        ))
+                        )
                     }
-                    _ => ObjectsBuilder::new(ent, spline_step, px_per_mm).build().ok(),
-                })
-                .collect(),
+                    _ => ObjectsBuilder::new(ent, spline_step, px_per_mm)
+                        .text_mode(options.text_mode)
+                        .rules(&rules)
+                        .cancel(cancel)
+                        .build()
+                        .ok()?,
+                };
+                Some((ent, obj))
+            })
+            .collect();
+        if !cancel.is_cancelled() {
+            progress.entities(total, total);
         }
+        Ok(objects)
     }
 }
 
-//probably don't need to worry about this as they won't exist in the dxf...
-/*pub struct Terminal {
-    x: f64,
-    y: f64,
-    uuid: Uuid,
-    name: String,
-    orientation: TermOrient,
-    //type?
-    //  Generic
-    //  Indoor Terminal Block
-    //  External Terminal Block
-}*/
+impl TryFrom<(&Drawing, &ConversionOptions)> for Description {
+    type Error = anyhow::Error;
 
-#[derive(Debug)]
+    fn try_from((drw, options): (&Drawing, &ConversionOptions)) -> anyhow::Result<Self> {
+        let _from_drw_span = span!(Level::TRACE, "Converting Drawing to Description");
+        Ok(Self {
+            objects: Self::entity_objects(drw, options)?
+                .into_iter()
+                .map(|(_, obj)| obj)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Names {
     names: Vec<Name>,
 }
@@ -1034,13 +2275,13 @@ impl From<&Names> for XMLElement {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Name {
     lang: String, //should this be an enum of language shorts at some point, maybe not worth it
     value: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ElmtUuid {
     uuid: Uuid,
 }
@@ -1063,7 +2304,7 @@ impl From<&ElmtUuid> for XMLElement {
 //I'll ever need for this tool, so it might be worth just hard coding the "element"
 //string when writing out the XML, but for now I'll just comment out the other enum
 //variants to suppress the clippy warnings.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum ItemType {
     Element = 1,
     /*ElementsCategory = 2,
@@ -1099,7 +2340,7 @@ impl Display for ItemType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum HAlignment {
     Left,
     Center,
@@ -1120,6 +2361,19 @@ impl Display for HAlignment {
     }
 }
 
+impl FromStr for HAlignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "AlignLeft" => Ok(Self::Left),
+            "AlignHCenter" => Ok(Self::Center),
+            "AlignRight" => Ok(Self::Right),
+            _ => Err(format!("Unknown horizontal alignment {s}")),
+        }
+    }
+}
+
 impl From<AttachmentPoint> for HAlignment {
     fn from(value: AttachmentPoint) -> Self {
         match value {
@@ -1137,6 +2391,7 @@ impl From<AttachmentPoint> for HAlignment {
 }
 
 impl From<HorizontalTextJustification> for HAlignment {
+    #[allow(clippy::match_same_arms)]
     fn from(value: HorizontalTextJustification) -> Self {
         //https://ezdxf.readthedocs.io/en/stable/tutorials/text.html#tut-text
         match value {
@@ -1151,7 +2406,7 @@ impl From<HorizontalTextJustification> for HAlignment {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum VAlignment {
     Top,
     Center,
@@ -1172,6 +2427,19 @@ impl Display for VAlignment {
     }
 }
 
+impl FromStr for VAlignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "AlignTop" => Ok(Self::Top),
+            "AlignVCenter" => Ok(Self::Center),
+            "AlignBottom" => Ok(Self::Bottom),
+            _ => Err(format!("Unknown vertical alignment {s}")),
+        }
+    }
+}
+
 impl From<AttachmentPoint> for VAlignment {
     fn from(value: AttachmentPoint) -> Self {
         match value {
@@ -1201,13 +2469,13 @@ impl From<VerticalTextJustification> for VAlignment {
     }
 }
 
-#[derive(Debug)]
-enum LineEnd {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnd {
     None,
     SimpleArrow,
-    /*TriangleArrow,
+    TriangleArrow,
     Circle,
-    Diamond,*/
+    Diamond,
 }
 
 impl Display for LineEnd {
@@ -1218,23 +2486,54 @@ impl Display for LineEnd {
             match self {
                 Self::None => "none",
                 Self::SimpleArrow => "simple",
-                /*Self::TriangleArrow => "triangle",
+                Self::TriangleArrow => "triangle",
                 Self::Circle => "circle",
-                Self::Diamond => "diamond",*/
+                Self::Diamond => "diamond",
             }
         )
     }
 }
 
-#[derive(Debug)]
-enum LinkType {
+impl FromStr for LineEnd {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "simple" => Ok(Self::SimpleArrow),
+            "triangle" => Ok(Self::TriangleArrow),
+            "circle" => Ok(Self::Circle),
+            "diamond" => Ok(Self::Diamond),
+            _ => Err(format!("Unknown line end {s}")),
+        }
+    }
+}
+
+/// How QET links the element to other elements of a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkType {
     Simple,
-    /*Master,
+    Master,
     Slave,
     NextReport,
+    #[serde(rename = "previous_report")]
     PrevReport,
+    #[serde(rename = "terminal")]
     TermBlock,
-    Thumbnail,*/
+    Thumbnail,
+}
+
+impl LinkType {
+    pub const ALL: [LinkType; 7] = [
+        Self::Simple,
+        Self::Master,
+        Self::Slave,
+        Self::NextReport,
+        Self::PrevReport,
+        Self::TermBlock,
+        Self::Thumbnail,
+    ];
 }
 
 impl Display for LinkType {
@@ -1244,18 +2543,35 @@ impl Display for LinkType {
             "{}",
             match self {
                 Self::Simple => "simple",
-                /*Self::Master => "master",
+                Self::Master => "master",
                 Self::Slave => "slave",
                 Self::NextReport => "next_report",
                 Self::PrevReport => "previous_report",
                 Self::TermBlock => "terminal",
-                Self::Thumbnail => "thumbnail",*/
+                Self::Thumbnail => "thumbnail",
             }
         )
     }
 }
 
-#[derive(Debug)]
+impl FromStr for LinkType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(Self::Simple),
+            "master" => Ok(Self::Master),
+            "slave" => Ok(Self::Slave),
+            "next_report" => Ok(Self::NextReport),
+            "previous_report" => Ok(Self::PrevReport),
+            "terminal" => Ok(Self::TermBlock),
+            "thumbnail" => Ok(Self::Thumbnail),
+            _ => Err(format!("Unknown link type {s}")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct ElemInfos {
     elem_info: Vec<ElemInfo>,
 }
@@ -1271,7 +2587,7 @@ impl From<&ElemInfos> for XMLElement {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ElemInfo {
     //there seems to be a list in the editor with the following values (per the XML)
     //  * supplier
@@ -1307,10 +2623,63 @@ impl From<&ElemInfo> for XMLElement {
 }
 
 #[inline]
+#[must_use]
 pub fn two_dec(num: f64) -> f64 {
     (num * 100.0).round() / 100.0
 }
 
+/// Attribute of a node of an .elmt file, an error naming it when it's missing
+pub(crate) fn xml_attr<'a>(node: Node<'a, '_>, name: &str) -> anyhow::Result<&'a str> {
+    node.attribute(name).with_context(|| {
+        format!(
+            "<{}> is missing the {name} attribute",
+            node.tag_name().name()
+        )
+    })
+}
+
+pub(crate) fn xml_f64(node: Node<'_, '_>, name: &str) -> anyhow::Result<f64> {
+    let value = xml_attr(node, name)?;
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid {name} {value}"))
+}
+
+/// Attribute parsed with [`FromStr`], `default` when it's missing
+pub(crate) fn xml_parse_or<T>(node: Node<'_, '_>, name: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match node.attribute(name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid {name}: {e}")),
+        None => Ok(default),
+    }
+}
+
+//QET writes booleans as true/false, but older elements sometimes have 1/0
+pub(crate) fn xml_bool_or(node: Node<'_, '_>, name: &str, default: bool) -> bool {
+    node.attribute(name)
+        .map_or(default, |value| matches!(value.trim(), "true" | "1"))
+}
+
+//uuids are written between braces, elements without a valid one get a new uuid
+pub(crate) fn xml_uuid(node: Node<'_, '_>, name: &str) -> Uuid {
+    node.attribute(name)
+        .and_then(|value| Uuid::parse_str(value.trim_matches(['{', '}'])).ok())
+        .unwrap_or_else(Uuid::new_v4)
+}
+
+pub(crate) fn xml_color(node: Node<'_, '_>, name: &str) -> HexColor {
+    node.attribute(name)
+        .and_then(|value| HexColor::parse(value).ok())
+        .unwrap_or(HexColor::BLACK)
+}
+
 //Should be the relevant Qt5 Code for the font string in Qt5...
 //Might need to look it up for Qt6, since it appears to have changed
 //and add in support for either or?
@@ -1360,7 +2729,7 @@ QString QFont::toString() const
 }
     */
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum FontStyleHint {
     Helvetica,
     Times,
@@ -1397,7 +2766,23 @@ impl From<&FontStyleHint> for i32 {
     }
 }
 
-#[derive(Debug)]
+impl From<i32> for FontStyleHint {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => FontStyleHint::Times,
+            2 => FontStyleHint::Courier,
+            3 => FontStyleHint::OldEnglish,
+            4 => FontStyleHint::System,
+            5 => FontStyleHint::AnyStyle,
+            6 => FontStyleHint::Cursive,
+            7 => FontStyleHint::Monospace,
+            8 => FontStyleHint::Fantasy,
+            _ => FontStyleHint::Helvetica,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub enum FontStyle {
     Normal,
     Italic,
@@ -1414,7 +2799,17 @@ impl From<&FontStyle> for i32 {
     }
 }
 
-#[derive(Debug)]
+impl From<i32> for FontStyle {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => FontStyle::Italic,
+            2 => FontStyle::Oblique,
+            _ => FontStyle::Normal,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct FontInfo {
     pub family: String,
     pub point_size: f64,
@@ -1470,9 +2865,339 @@ impl Display for FontInfo {
     }
 }
 
+//the reverse of the Display impl, Qt6 adds a few fields after the always 0 one, the
+//style name is only picked up from the shorter Qt5 strings
+impl FromStr for FontInfo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(',').collect();
+        if fields.len() < 10 {
+            return Err(format!("Invalid font {s}"));
+        }
+        let number = |i: usize| -> Result<f64, String> {
+            fields[i]
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid font {s}"))
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let int = |i: usize| number(i).map(|n| n.round() as i32);
+
+        Ok(Self {
+            family: fields[0].into(),
+            point_size: number(1)?,
+            pixel_size: int(2)?,
+            style_hint: int(3)?.into(),
+            weight: int(4)?,
+            style: int(5)?.into(),
+            underline: int(6)? != 0,
+            strike_out: int(7)? != 0,
+            fixed_pitch: int(8)? != 0,
+            style_name: (fields.len() == 11 && !fields[10].is_empty())
+                .then(|| fields[10].to_string()),
+        })
+    }
+}
+
 #[derive(Debug)]
 enum TextEntity<'a> {
     Text(&'a dxf::entities::Text),
     MText(&'a dxf::entities::MText),
     Attrib(&'a AttributeDefinition),
 }
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use crate::metadata::TerminalPlacement;
+    use dxf::Point;
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Objects {
+        Objects::Line(Line::from(&dxf::entities::Line::new(
+            Point::new(x1, y1, 0.0),
+            Point::new(x2, y2, 0.0),
+        )))
+    }
+
+    fn terminal(x: f64, y: f64) -> Objects {
+        Objects::Terminal(Terminal::new(x, y, TermOrient::North))
+    }
+
+    #[test]
+    fn scale_mode_from_str_parses_every_mode() {
+        assert_eq!("ratio".parse(), Ok(ScaleMode::Ratio));
+        assert_eq!("Width:120".parse(), Ok(ScaleMode::Width(120.0)));
+        assert_eq!("height: 40.5".parse(), Ok(ScaleMode::Height(40.5)));
+        assert_eq!("fit:100x50".parse(), Ok(ScaleMode::Fit(100.0, 50.0)));
+        assert_eq!("pitch:2".parse(), Ok(ScaleMode::Pitch(2.0)));
+        for bad in ["", "ratio:2", "width", "width:0", "height:-1", "fit:100", "pitch:inf", "zoom:2"] {
+            assert!(bad.parse::<ScaleMode>().is_err(), "{bad}");
+        }
+        let fit = ScaleMode::Fit(100.0, 50.0);
+        assert_eq!(fit.to_string().parse(), Ok(fit));
+    }
+
+    #[test]
+    fn scale_mode_factor_measures_the_description() {
+        //100 wide and 20 high in drawing units, terminals 4 apart
+        let desc = Description {
+            objects: vec![line(0.0, 0.0, 100.0, -20.0), terminal(0.0, 0.0), terminal(4.0, 0.0)],
+        };
+        assert_eq!(ScaleMode::Ratio.factor(&desc, 3.0), 3.0);
+        assert_eq!(ScaleMode::Width(200.0).factor(&desc, 3.0), 2.0);
+        assert_eq!(ScaleMode::Height(10.0).factor(&desc, 3.0), 0.5);
+        assert_eq!(ScaleMode::Fit(200.0, 10.0).factor(&desc, 3.0), 0.5);
+        assert_eq!(ScaleMode::Pitch(2.0).factor(&desc, 3.0), 2.0 * GRID_STEP / 4.0);
+    }
+
+    #[test]
+    fn scale_mode_factor_falls_back_to_the_ratio_without_anything_to_measure() {
+        let flat = Description {
+            objects: vec![line(0.0, 0.0, 100.0, 0.0)],
+        };
+        assert_eq!(ScaleMode::Height(10.0).factor(&flat, 3.0), 3.0);
+        assert_eq!(ScaleMode::Fit(200.0, 10.0).factor(&flat, 3.0), 2.0);
+        assert_eq!(ScaleMode::Pitch(2.0).factor(&flat, 3.0), 3.0);
+    }
+
+    #[test]
+    fn align_to_grid_moves_the_anchor_onto_the_grid() {
+        let mut desc = Description {
+            objects: vec![line(3.0, -4.0, 33.0, -24.0), terminal(12.0, 17.0)],
+        };
+        //the first terminal goes from (12, 17) to (10, 20)
+        let moved = desc.align_to_grid(Some(GridAnchor::Terminal), &[]);
+        assert_eq!(moved.shift, (-2.0, 3.0));
+        assert_eq!(moved.displacement, 2f64.hypot(3.0));
+        //the line helper flips y like the dxf import, so the line starts at (3, 4)
+        assert_eq!((desc.left_bound(), desc.top_bound()), (1.0, 7.0));
+
+        let moved = desc.align_to_grid(Some(GridAnchor::Corner), &[]);
+        assert_eq!(moved.displacement, 1f64.hypot(3.0));
+        assert_eq!((desc.left_bound(), desc.top_bound()), (0.0, 10.0));
+    }
+
+    #[test]
+    fn align_to_grid_snaps_only_the_chosen_primitives() {
+        let mut desc = Description {
+            objects: vec![line(3.0, 0.0, 27.0, 0.0), terminal(12.0, 17.0)],
+        };
+        let moved = desc.align_to_grid(Some(GridAnchor::Hotspot), &[GridSnap::Terminals]);
+        assert_eq!(moved.shift, (0.0, 0.0));
+        assert_eq!(moved.displacement, 2f64.hypot(3.0));
+        let Objects::Line(line) = &desc.objects[0] else { unreachable!() };
+        assert_eq!((line.x1, line.x2), (3.0, 27.0));
+        let Objects::Terminal(term) = &desc.objects[1] else { unreachable!() };
+        assert_eq!((term.x, term.y), (10.0, 20.0));
+
+        assert_eq!(desc.align_to_grid(None, &GridSnap::ALL).displacement, 3.0);
+        let Objects::Line(line) = &desc.objects[0] else { unreachable!() };
+        assert_eq!((line.x1, line.x2), (0.0, 30.0));
+    }
+
+    //one of every primitive, with what is only used while converting set: a reference
+    //rectangle and original heights for the texts, an automatically oriented terminal and
+    //a grid displacement
+    fn converted_definition() -> Definition {
+        let mtext = dxf::entities::MText {
+            insertion_point: Point::new(60.0, -10.0, 0.0),
+            initial_text_height: 5.0,
+            text: "K1".into(),
+            attachment_point: dxf::enums::AttachmentPoint::TopRight,
+            reference_rectangle_width: 30.0,
+            ..dxf::entities::MText::default()
+        };
+        let label = dxf::entities::Text {
+            location: Point::new(30.0, -40.0, 0.0),
+            text_height: 4.0,
+            value: "Label".into(),
+            horizontal_text_justification: dxf::enums::HorizontalTextJustification::Center,
+            ..dxf::entities::Text::default()
+        };
+        let mut info_text = DTextBuilder::from_text(&label).build();
+        info_text.info_name = Some("label".into());
+        info_text.text_from = "ElementInfo".into();
+        let note = dxf::entities::Text {
+            location: Point::new(0.0, -50.0, 0.0),
+            text_height: 3.0,
+            value: "note".into(),
+            ..dxf::entities::Text::default()
+        };
+        let mut outline = dxf::entities::LwPolyline {
+            vertices: [(0.0, 0.0), (40.0, 0.0), (40.0, -20.0)]
+                .into_iter()
+                .map(|(x, y)| dxf::LwPolylineVertex { x, y, ..Default::default() })
+                .collect(),
+            ..dxf::entities::LwPolyline::default()
+        };
+        outline.set_is_closed(true);
+        let mut terminal = Terminal::new(3.0, 12.0, TermOrient::North);
+        terminal.auto_orient = true;
+
+        let description = Description {
+            objects: vec![
+                line(0.0, 0.0, 50.0, -30.0),
+                Objects::Arc(Arc::from(&dxf::entities::Arc::new(Point::new(20.0, -20.0, 0.0), 10.0, 0.0, 90.0))),
+                Objects::Ellipse(Ellipse::from(&dxf::entities::Circle::new(Point::new(10.0, -10.0, 0.0), 5.0))),
+                Objects::Polygon(Polygon::from(&outline)),
+                Objects::DynamicText(DTextBuilder::from_mtext(&mtext).build()),
+                Objects::DynamicText(info_text),
+                Objects::Text(Text::from((&note, HexColor::BLACK))),
+                Objects::Terminal(terminal),
+            ],
+        };
+        let options = ConversionOptions {
+            px_per_mm: 1.0,
+            grid_anchor: Some(GridAnchor::Terminal),
+            ..ConversionOptions::default()
+        };
+        let mut drw = Drawing::new();
+        drw.header.default_drawing_units = Units::Millimeters;
+        Definition::from_description("round trip", &options, &drw, description)
+    }
+
+    #[test]
+    fn reading_a_written_element_gives_it_back() -> anyhow::Result<()> {
+        let def = converted_definition();
+        assert!(def.grid_displacement() > 0.0);
+        assert!(def.terminals().all(|term| term.auto_orient));
+        let xml = XMLElement::from(&def).to_string();
+        assert!(xml.contains("<info_name>label</info_name>"));
+        assert_eq!(Definition::from_xml(&xml)?, def);
+        Ok(())
+    }
+
+    #[test]
+    fn reading_keeps_every_primitive() -> anyhow::Result<()> {
+        let def = converted_definition();
+        let read = Definition::from_xml(&XMLElement::from(&def).to_string())?;
+        assert_eq!(read.description.objects.len(), def.description.objects.len());
+        for (read, written) in read.description.objects.iter().zip(&def.description.objects) {
+            assert_eq!(read, written);
+        }
+        Ok(())
+    }
+
+    fn element(hotspot: (i64, i64), primitives: &str) -> Definition {
+        Definition::from_xml(&format!(
+            r#"<definition type="element" width="80" height="60" hotspot_x="{}" hotspot_y="{}"><description>{primitives}</description></definition>"#,
+            hotspot.0, hotspot.1
+        ))
+        .unwrap()
+    }
+
+    fn lines(def: &Definition) -> Vec<(f64, f64, f64, f64)> {
+        def.description
+            .objects
+            .iter()
+            .filter_map(|obj| match obj {
+                Objects::Line(line) => Some((line.x1, line.y1, line.x2, line.y2)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_geometry_lines_up_the_corners_of_the_bounding_boxes() {
+        //the hotspot of the element is inside it, 20 from its left and 30 from its top
+        let existing = || {
+            element(
+                (20, 30),
+                r#"<line x1="-20" y1="-30" x2="40" y2="10"/><terminal x="-20" y="0" orientation="w"/>"#,
+            )
+        };
+        //same shape drawn with the origin on its top left corner
+        let converted = || element((0, 0), r#"<line x1="0" y1="0" x2="60" y2="40"/>"#);
+
+        let merged = existing().merge_geometry(converted(), true);
+        assert_eq!(lines(&merged), vec![(-20.0, -30.0, 40.0, 10.0)]);
+        assert_eq!(merged.terminals().count(), 1);
+
+        let unaligned = existing().merge_geometry(converted(), false);
+        assert_eq!(lines(&unaligned), vec![(0.0, 0.0, 60.0, 40.0)]);
+    }
+
+    #[test]
+    fn placed_terminals_follow_the_geometry_at_any_scale() {
+        //placed on the end of the line, in drawing units with y up
+        let metadata = ElementMetadata {
+            terminals: Some(vec![TerminalPlacement::new(43.0, 24.0, TermOrient::East)]),
+            ..ElementMetadata::default()
+        };
+        let mut drw = Drawing::new();
+        drw.header.default_drawing_units = Units::Millimeters;
+        for px_per_mm in [2.0, 3.0] {
+            let options = ConversionOptions {
+                px_per_mm,
+                grid_anchor: Some(GridAnchor::Corner),
+                ..ConversionOptions::default()
+            };
+            let description = Description {
+                objects: vec![line(3.0, 4.0, 43.0, 24.0)],
+            };
+            let mut def = Definition::from_description("placed", &options, &drw, description);
+            def.apply_metadata(&metadata);
+
+            let (_, _, x2, y2) = lines(&def)[0];
+            let term = def.terminals().next().expect("placed terminal");
+            assert_eq!((term.x, term.y), (x2, y2), "{px_per_mm} px/mm");
+            assert_eq!(
+                TerminalPlacement::from_terminal(term, def.placement()),
+                metadata.terminals.as_ref().unwrap()[0]
+            );
+        }
+    }
+
+    #[test]
+    fn description_bounds_take_the_far_side_of_the_outermost_objects() {
+        let desc = Description {
+            objects: vec![line(0.0, 0.0, 10.0, -5.0), line(20.0, -30.0, 50.0, -40.0)],
+        };
+
+        assert_eq!(desc.left_bound(), 0.0);
+        assert_eq!(desc.top_bound(), 0.0);
+        //these used to be the left and top sides of the same objects, 20 and 30
+        assert_eq!(desc.right_bound(), 50.0);
+        assert_eq!(desc.bot_bound(), 40.0);
+    }
+
+    #[test]
+    fn element_size_and_hotspot_cover_the_whole_drawing() -> anyhow::Result<()> {
+        let mut drw = Drawing::new();
+        drw.header.default_drawing_units = dxf::enums::Units::Millimeters;
+        for (x1, y1, x2, y2) in [(0.0, 0.0, 10.0, -5.0), (20.0, -30.0, 50.0, -40.0)] {
+            drw.add_entity(dxf::entities::Entity::new(dxf::entities::EntityType::Line(
+                dxf::entities::Line::new(Point::new(x1, y1, 0.0), Point::new(x2, y2, 0.0)),
+            )));
+        }
+
+        //50 x 40 px of graphics with a 5 px margin, it used to be 30 x 40 from the near sides
+        let options = ConversionOptions {
+            spline_step: 1,
+            px_per_mm: 1.0,
+            ..ConversionOptions::default()
+        };
+        let def = Definition::new("bounds", &options, &drw)?;
+        assert_eq!((def.width, def.height), (60, 50));
+        assert_eq!((def.hotspot_x, def.hotspot_y), (5, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_patterns_stop_the_conversion() {
+        let drw = Drawing::new();
+        let options = ConversionOptions {
+            exclude_blocks: vec!["DIM[".into()],
+            ..ConversionOptions::default()
+        };
+        assert!(EntityRules::new(&options, &drw).is_err());
+        let options = ConversionOptions {
+            layer_styles: [("[".to_string(), "line-style:dashed".to_string())].into(),
+            ..ConversionOptions::default()
+        };
+        assert!(Definition::new("patterns", &options, &drw).is_err());
+        assert!(Definition::new("patterns", &ConversionOptions::default(), &drw).is_ok());
+    }
+}
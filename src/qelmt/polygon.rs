@@ -1,5 +1,7 @@
-use super::{two_dec, ScaleEntity};
+use super::{two_dec, xml_bool_or, xml_f64, ScaleEntity};
 use dxf::entities::{LwPolyline, Polyline, Solid, Spline};
+use dxf::LwPolylineVertex;
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 use std::ops::{Add, Mul};
 
@@ -9,7 +11,7 @@ use std::ops::{Add, Mul};
 //if I started, then stopped, and then didn't realize where I left off
 //and started again but used a different name...?
 //Might need to take a closer look and clean this up.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Coordinate {
     pub x: f64,
     pub y: f64,
@@ -21,6 +23,7 @@ pub struct Point {
     pub y: f64,
 }
 impl Point {
+    #[must_use]
     pub fn new(x: f64, y: f64) -> Point {
         Point { x, y }
     }
@@ -44,7 +47,7 @@ impl Add for Point {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Polygon {
     pub style: String,
     pub antialias: bool,
@@ -103,29 +106,21 @@ impl From<&LwPolyline> for Polygon {
 }
 
 impl From<(&Spline, u32)> for Polygon {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn from((spline, spline_step): (&Spline, u32)) -> Self {
-        let mut i: usize = 0;
-        let mut points: Vec<Point> = Vec::new();
-        for _a in &spline.control_points {
-            points.push(Point::new(
-                spline.control_points[i].x,
-                spline.control_points[i].y,
-            ));
-            i += 1;
-        }
-        i = 0;
-        let mut knots: Vec<f64> = Vec::new();
-        for _a in &spline.knot_values {
-            knots.push(spline.knot_values[i]);
-            i += 1;
-        }
+        let points: Vec<Point> = spline
+            .control_points
+            .iter()
+            .map(|point| Point::new(point.x, point.y))
+            .collect();
+        let knots = spline.knot_values.clone();
         let curr_spline = bspline::BSpline::new(
             spline.degree_of_curve.unsigned_abs() as usize,
             points,
             knots,
         );
         let step: f64 =
-            (curr_spline.knot_domain().1 - curr_spline.knot_domain().0) / (spline_step as f64);
+            (curr_spline.knot_domain().1 - curr_spline.knot_domain().0) / f64::from(spline_step);
 
         //there is probably a way to clean up some of this logic and use iterators
         //although it looks like step_by doesn't work on a f64 range...hmmm
@@ -136,14 +131,12 @@ impl From<(&Spline, u32)> for Polygon {
                 ((curr_spline.knot_domain().1 - curr_spline.knot_domain().0) / step) as usize + 1,
             );
             let mut j: f64 = curr_spline.knot_domain().0;
-            i = 0;
             while j < curr_spline.knot_domain().1 {
                 coords.push(Coordinate {
                     x: curr_spline.point(j).x,
                     y: -curr_spline.point(j).y,
                 });
                 j += step;
-                i += 1;
             }
             coords
         };
@@ -194,6 +187,46 @@ impl From<&Solid> for Polygon {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Polygon {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        //rectangles become closed polygons, their rounded corners are lost
+        if node.has_tag_name("rect") {
+            let (x, y) = (xml_f64(node, "x")?, xml_f64(node, "y")?);
+            let (width, height) = (xml_f64(node, "width")?, xml_f64(node, "height")?);
+            return Ok(Polygon {
+                coordinates: [(x, y), (x + width, y), (x + width, y + height), (x, y + height)]
+                    .into_iter()
+                    .map(|(x, y)| Coordinate { x, y })
+                    .collect(),
+                closed: true,
+                antialias: xml_bool_or(node, "antialias", false),
+                style: node.attribute("style").unwrap_or_default().into(),
+            });
+        }
+
+        //the points are numbered from 1 and end at the first missing x
+        let mut coordinates = Vec::new();
+        for n in 1.. {
+            if node.attribute(format!("x{n}").as_str()).is_none() {
+                break;
+            }
+            coordinates.push(Coordinate {
+                x: xml_f64(node, &format!("x{n}"))?,
+                y: xml_f64(node, &format!("y{n}"))?,
+            });
+        }
+
+        Ok(Polygon {
+            coordinates,
+            closed: xml_bool_or(node, "closed", true),
+            antialias: xml_bool_or(node, "antialias", false),
+            style: node.attribute("style").unwrap_or_default().into(),
+        })
+    }
+}
+
 impl From<&Polygon> for XMLElement {
     fn from(poly: &Polygon) -> Self {
         let mut poly_xml: XMLElement = XMLElement::new("polygon");
@@ -214,6 +247,25 @@ impl From<&Polygon> for XMLElement {
     }
 }
 
+impl From<&Polygon> for LwPolyline {
+    fn from(poly: &Polygon) -> Self {
+        let mut lwpoly = LwPolyline {
+            vertices: poly
+                .coordinates
+                .iter()
+                .map(|coord| LwPolylineVertex {
+                    x: coord.x,
+                    y: -coord.y,
+                    ..Default::default()
+                })
+                .collect(),
+            ..LwPolyline::default()
+        };
+        lwpoly.set_is_closed(poly.closed);
+        lwpoly
+    }
+}
+
 impl ScaleEntity for Polygon {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.coordinates.iter_mut().for_each(|coord| {
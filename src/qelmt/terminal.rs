@@ -0,0 +1,165 @@
+use super::{two_dec, xml_f64, xml_parse_or, xml_uuid, ScaleEntity};
+use roxmltree::Node;
+use simple_xml_builder::XMLElement;
+use std::fmt::Display;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TermOrient {
+    #[serde(rename = "n")]
+    North,
+    #[serde(rename = "e")]
+    East,
+    #[serde(rename = "s")]
+    South,
+    #[serde(rename = "w")]
+    West,
+}
+
+impl TermOrient {
+    /// Orientation of a terminal block inserted with `rotation` degrees (counter clockwise),
+    /// the block is expected to be drawn with the connection pointing up
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_rotation(rotation: f64) -> Self {
+        match ((rotation / 90.0).round() as i64).rem_euclid(4) {
+            1 => Self::West,
+            2 => Self::South,
+            3 => Self::East,
+            _ => Self::North,
+        }
+    }
+
+    /// The next orientation turning clockwise
+    #[must_use]
+    pub fn rotated(self) -> Self {
+        match self {
+            Self::North => Self::East,
+            Self::East => Self::South,
+            Self::South => Self::West,
+            Self::West => Self::North,
+        }
+    }
+}
+
+impl Display for TermOrient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::North => "n",
+                Self::East => "e",
+                Self::South => "s",
+                Self::West => "w",
+            }
+        )
+    }
+}
+
+impl FromStr for TermOrient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "n" => Ok(Self::North),
+            "e" => Ok(Self::East),
+            "s" => Ok(Self::South),
+            "w" => Ok(Self::West),
+            _ => Err(format!("Unknown terminal orientation {s}")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Terminal {
+    pub x: f64,
+    pub y: f64,
+    pub uuid: Uuid,
+    pub name: String,
+    pub orientation: TermOrient,
+    //set for terminals coming from points or circles, which get pointed
+    //towards the closest edge of the element once its bounds are known
+    pub(crate) auto_orient: bool,
+    //type?
+    //  Generic
+    //  Indoor Terminal Block
+    //  External Terminal Block
+}
+
+impl Terminal {
+    #[must_use]
+    pub fn new(x: f64, y: f64, orientation: TermOrient) -> Self {
+        Self {
+            x,
+            y,
+            uuid: Uuid::new_v4(),
+            name: String::new(),
+            orientation,
+            auto_orient: false,
+        }
+    }
+}
+
+//auto_orient is done with once the element is converted
+impl PartialEq for Terminal {
+    fn eq(&self, other: &Self) -> bool {
+        self.x == other.x
+            && self.y == other.y
+            && self.uuid == other.uuid
+            && self.name == other.name
+            && self.orientation == other.orientation
+    }
+}
+
+impl From<&Terminal> for XMLElement {
+    fn from(term: &Terminal) -> Self {
+        let mut term_xml: XMLElement = XMLElement::new("terminal");
+        term_xml.add_attribute("x", two_dec(term.x));
+        term_xml.add_attribute("y", two_dec(term.y));
+        term_xml.add_attribute("orientation", term.orientation);
+        term_xml.add_attribute("uuid", format!("{{{}}}", term.uuid));
+        term_xml.add_attribute("name", &term.name);
+        term_xml.add_attribute("type", "Generic");
+        term_xml
+    }
+}
+
+impl TryFrom<Node<'_, '_>> for Terminal {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        Ok(Terminal {
+            x: xml_f64(node, "x")?,
+            y: xml_f64(node, "y")?,
+            uuid: xml_uuid(node, "uuid"),
+            name: node.attribute("name").unwrap_or_default().into(),
+            orientation: xml_parse_or(node, "orientation", TermOrient::North)?,
+            auto_orient: false,
+        })
+    }
+}
+
+impl ScaleEntity for Terminal {
+    fn scale(&mut self, fact_x: f64, fact_y: f64) {
+        self.x *= fact_x;
+        self.y *= fact_y;
+    }
+
+    fn left_bound(&self) -> f64 {
+        self.x
+    }
+
+    fn right_bound(&self) -> f64 {
+        self.x
+    }
+
+    fn top_bound(&self) -> f64 {
+        self.y
+    }
+
+    fn bot_bound(&self) -> f64 {
+        self.y
+    }
+}
@@ -1,6 +1,7 @@
-use super::{two_dec, FontInfo, ScaleEntity};
+use super::{two_dec, xml_attr, xml_color, xml_f64, xml_parse_or, DynamicText, FontInfo, ScaleEntity};
 use dxf::entities;
 use hex_color::HexColor;
+use roxmltree::Node;
 use simple_xml_builder::XMLElement;
 
 #[derive(Debug)]
@@ -15,6 +16,7 @@ pub struct Text {
 }
 
 impl From<(&entities::Text, HexColor)> for Text {
+    #[allow(clippy::cast_possible_truncation)]
     fn from((txt, color): (&entities::Text, HexColor)) -> Self {
         Text {
             x: txt.location.x,
@@ -37,6 +39,33 @@ impl From<(&entities::Text, HexColor)> for Text {
     }
 }
 
+impl From<DynamicText> for Text {
+    fn from(dtxt: DynamicText) -> Self {
+        Text {
+            x: dtxt.x,
+            //dynamic texts are placed by their top left corner, static texts by their baseline
+            y: dtxt.y + dtxt.font.point_size,
+            rotation: dtxt.rotation,
+            value: dtxt.text,
+            font: dtxt.font,
+            color: dtxt.color,
+            original_text_height: dtxt.original_text_height,
+        }
+    }
+}
+
+//the original height isn't written, it's only for the conversion log
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.rotation == other.rotation
+            && self.value == other.value
+            && self.x == other.x
+            && self.y == other.y
+            && self.font == other.font
+            && self.color == other.color
+    }
+}
+
 impl From<&Text> for XMLElement {
     fn from(txt: &Text) -> Self {
         let mut txt_xml: XMLElement = XMLElement::new("text");
@@ -50,6 +79,40 @@ impl From<&Text> for XMLElement {
     }
 }
 
+impl TryFrom<Node<'_, '_>> for Text {
+    type Error = anyhow::Error;
+
+    fn try_from(node: Node<'_, '_>) -> anyhow::Result<Self> {
+        Ok(Text {
+            x: xml_f64(node, "x")?,
+            y: xml_f64(node, "y")?,
+            rotation: xml_parse_or(node, "rotation", 0.0)?,
+            color: xml_color(node, "color"),
+            font: xml_parse_or(node, "font", FontInfo::default())?,
+            value: xml_attr(node, "text")?.into(),
+            //not read from a drawing, there is no original height
+            original_text_height: 0.0,
+        })
+    }
+}
+
+impl From<&Text> for entities::Text {
+    fn from(txt: &Text) -> Self {
+        entities::Text {
+            location: dxf::Point::new(txt.x, -txt.y, 0.0),
+            text_height: txt.font.point_size,
+            value: txt.value.clone(),
+            //the reverse of the rotation applied when converting
+            rotation: if txt.rotation.abs() > f64::EPSILON {
+                txt.rotation + 180.0
+            } else {
+                0.0
+            },
+            ..entities::Text::default()
+        }
+    }
+}
+
 impl ScaleEntity for Text {
     fn scale(&mut self, fact_x: f64, fact_y: f64) {
         self.x *= fact_x;
@@ -67,12 +130,20 @@ impl ScaleEntity for Text {
     }
 
     fn right_bound(&self) -> f64 {
-        //need to be able to measure text size to get this
-        todo!()
+        //without measuring the font this is only a rough guess of the average glyph
+        //width, but it keeps long texts from ending up outside the element
+        let longest = self
+            .value
+            .lines()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.x + f64::from(u32::try_from(longest).unwrap_or(u32::MAX)) * self.font.point_size * 0.6
     }
 
     fn bot_bound(&self) -> f64 {
-        //need to be able to measure text size to get this
-        todo!()
+        //the position is the baseline of the first line
+        let extra_lines = self.value.lines().count().saturating_sub(1);
+        self.y + f64::from(u32::try_from(extra_lines).unwrap_or(u32::MAX)) * self.font.point_size
     }
 }
@@ -0,0 +1,440 @@
+use crate::batch::build_globset;
+use crate::progress::Phase;
+use crate::qelmt::{Definition, Description, Objects, ScaleEntity};
+use crate::{file_writer, friendly_name, is_stdin_path, load_drawing};
+use crate::{updated_element, warn_unused_metadata, write_text_log};
+use crate::{ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{bail, Context, Result};
+use dxf::entities::Entity;
+use dxf::{Block, Drawing};
+use simple_xml_builder::XMLElement;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// How a drawing is turned into elements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SplitMode {
+    /// The whole drawing becomes a single element
+    #[default]
+    None,
+    /// Every block definition becomes an element named after the block
+    Blocks,
+    /// The entities of every layer become an element named after the layer
+    Layers,
+    /// Entities close to each other become an element, named after a text found among them
+    Clusters,
+}
+
+impl FromStr for SplitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "blocks" => Ok(Self::Blocks),
+            "layers" => Ok(Self::Layers),
+            "clusters" => Ok(Self::Clusters),
+            _ => Err(format!(
+                "Unknown split mode {s}, expected none, blocks, layers or clusters"
+            )),
+        }
+    }
+}
+
+impl Display for SplitMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::None => "none",
+                Self::Blocks => "blocks",
+                Self::Layers => "layers",
+                Self::Clusters => "clusters",
+            }
+        )
+    }
+}
+
+impl TryFrom<String> for SplitMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SplitMode> for String {
+    fn from(mode: SplitMode) -> Self {
+        mode.to_string()
+    }
+}
+
+/// The block definitions of the drawing that can become an element, optionally only the
+/// ones matching `patterns`.
+///
+/// Model and paper spaces, anonymous blocks (hatches, dimensions...) and empty blocks are skipped.
+///
+/// # Errors
+///
+/// Returns an error if a pattern isn't a valid glob.
+pub fn library_blocks<'a>(drw: &'a Drawing, patterns: &[String]) -> Result<Vec<&'a Block>> {
+    let filter = build_globset(patterns)?;
+    Ok(drw
+        .blocks()
+        .filter(|bl| !bl.name.is_empty() && !bl.name.starts_with('*'))
+        .filter(|bl| !bl.entities.is_empty())
+        .filter(|bl| filter.as_ref().map_or(true, |gs| gs.is_match(&bl.name)))
+        .collect())
+}
+
+/// Block, layer and text names may contain characters that aren't allowed in file names
+pub(crate) fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// `base`, or `base` with a number appended when `names` already has it, which is then
+/// added to `names`. File systems may ignore case, so names are compared in upper case.
+pub(crate) fn unique_name(names: &mut HashSet<String>, base: &str) -> String {
+    let mut unique = base.to_string();
+    let mut n = 1;
+    while !names.insert(unique.to_uppercase()) {
+        n += 1;
+        unique = format!("{base}_{n}");
+    }
+    unique
+}
+
+/// An element split out of a drawing
+#[derive(Debug)]
+pub struct SplitElement {
+    /// Name of the element, also used for its file name
+    pub name: String,
+    pub definition: Definition,
+    /// Counts of the entities that went into this element
+    pub stats: ConversionStats,
+}
+
+impl SplitElement {
+    fn new<'a>(
+        name: String,
+        definition: Definition,
+        entities: impl Iterator<Item = &'a Entity>,
+        started: Instant,
+    ) -> Self {
+        let mut stats = ConversionStats::from_entities(entities);
+        stats.elapsed_ms = started.elapsed().as_millis();
+        stats.grid_displacement = definition.grid_displacement();
+        Self {
+            name,
+            definition,
+            stats,
+        }
+    }
+}
+
+/// Splits the drawing into elements following `options.split`, `name` is used for the
+/// element when not splitting, and as the base name of clusters without a text.
+///
+/// # Errors
+///
+/// Returns an error if a block pattern isn't a valid glob.
+pub fn split_drawing(
+    drw: &Drawing,
+    name: &str,
+    options: &ConversionOptions,
+) -> Result<Vec<SplitElement>> {
+    let now = Instant::now();
+    let elements = match options.split {
+        SplitMode::None => vec![SplitElement::new(
+            name.to_string(),
+            Definition::new(name, options, drw)?,
+            drw.entities(),
+            now,
+        )],
+        SplitMode::Blocks => library_blocks(drw, &options.block_filter)?
+            .into_iter()
+            .map(|block| {
+                let now = Instant::now();
+                Ok(SplitElement::new(
+                    block.name.clone(),
+                    Definition::from_block(block.name.clone(), options, drw, block)?,
+                    block.entities.iter(),
+                    now,
+                ))
+            })
+            .collect::<Result<_>>()?,
+        SplitMode::Layers => {
+            //layer names aren't case sensitive, but keep the spelling of the first entity found
+            let mut layers: BTreeMap<String, (String, Group)> = BTreeMap::new();
+            for (ent, obj) in Description::entity_objects(drw, options)? {
+                layers
+                    .entry(ent.common.layer.to_uppercase())
+                    .or_insert_with(|| (ent.common.layer.clone(), Vec::new()))
+                    .1
+                    .push((ent, obj));
+            }
+            layers
+                .into_values()
+                .map(|(layer, group)| group_element(layer, group, options, drw))
+                .collect()
+        }
+        SplitMode::Clusters => {
+            let clusters = cluster(Description::entity_objects(drw, options)?, options.cluster_gap);
+            let mut names = HashSet::new();
+            clusters
+                .into_iter()
+                .enumerate()
+                .map(|(i, group)| {
+                    let base = label(&group).unwrap_or_else(|| format!("{name}_{}", i + 1));
+                    //two symbols with the same label would get the same name
+                    group_element(unique_name(&mut names, &base), group, options, drw)
+                })
+                .collect()
+        }
+    };
+    Ok(elements)
+}
+
+//entities of a split element with the objects converted from them
+type Group<'a> = Vec<(&'a Entity, Objects)>;
+
+fn group_element(
+    name: String,
+    group: Group<'_>,
+    options: &ConversionOptions,
+    drw: &Drawing,
+) -> SplitElement {
+    let now = Instant::now();
+    let (entities, objects): (Vec<&Entity>, Vec<Objects>) = group.into_iter().unzip();
+    let definition = Definition::from_description(name.clone(), options, drw, Description { objects });
+    SplitElement::new(name, definition, entities.into_iter(), now)
+}
+
+//bounds of an object in drawing units, y pointing down like in the element
+fn bounds(obj: &Objects) -> (f64, f64, f64, f64) {
+    (
+        obj.left_bound(),
+        obj.right_bound(),
+        obj.top_bound(),
+        obj.bot_bound(),
+    )
+}
+
+/// Groups the objects whose bounding boxes touch, or are less than `gap` apart, directly
+/// or through other objects. The clusters are sorted top to bottom, then left to right.
+fn cluster(objects: Group<'_>, gap: f64) -> Vec<Group<'_>> {
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let boxes: Vec<_> = objects.iter().map(|(_, obj)| bounds(obj)).collect();
+    let mut parents: Vec<usize> = (0..objects.len()).collect();
+    for (i, a) in boxes.iter().enumerate() {
+        for (j, b) in boxes.iter().enumerate().skip(i + 1) {
+            let close = a.0 - gap <= b.1 && b.0 - gap <= a.1 && a.2 - gap <= b.3 && b.2 - gap <= a.3;
+            if close {
+                let (ri, rj) = (root(&mut parents, i), root(&mut parents, j));
+                parents[ri] = rj;
+            }
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, Group> = BTreeMap::new();
+    for (i, item) in objects.into_iter().enumerate() {
+        let r = root(&mut parents, i);
+        clusters.entry(r).or_default().push(item);
+    }
+
+    let mut clusters: Vec<_> = clusters.into_values().collect();
+    let corner = |group: &Group| {
+        group.iter().map(|(_, obj)| bounds(obj)).fold(
+            (f64::INFINITY, f64::INFINITY),
+            |(top, left), (l, _, t, _)| (top.min(t), left.min(l)),
+        )
+    };
+    clusters.sort_by(|a, b| {
+        corner(a)
+            .partial_cmp(&corner(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    clusters
+}
+
+/// The top most, then left most, text of the cluster, which is usually the symbol's label
+fn label(group: &[(&Entity, Objects)]) -> Option<String> {
+    group
+        .iter()
+        .flat_map(|(_, obj)| std::iter::once(obj).chain(obj.descendants()))
+        .filter_map(|obj| match obj {
+            Objects::Text(txt) => Some((txt.y, txt.x, txt.value.as_str())),
+            Objects::DynamicText(dtxt) => Some((dtxt.y, dtxt.x, dtxt.text.as_str())),
+            _ => None,
+        })
+        .filter_map(|(y, x, text)| {
+            let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+            Some((y, x, line))
+        })
+        .min_by(|a, b| {
+            (a.0, a.1)
+                .partial_cmp(&(b.0, b.1))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(_, _, line)| line.to_string())
+}
+
+/// Converts `file_path` into one .elmt file per element of [`split_drawing`], written next
+/// to where the single .elmt file would go. The stats add up all the converted elements.
+/// The metadata sidecar of the drawing describes a single element, it's ignored here.
+///
+/// # Errors
+///
+/// Returns an error if the drawing can't be loaded, there is nothing to split, or a file
+/// can't be written.
+pub fn convert_split(file_path: &Path, options: &ConversionOptions) -> Result<ConversionResult> {
+    let now = Instant::now();
+    let friendly_file_name = friendly_name(file_path);
+
+    if options.verbose || is_stdin_path(file_path) {
+        bail!("Splitting {friendly_file_name} into several elements needs .elmt files to write to");
+    }
+
+    let progress = &options.progress;
+    progress.phase(Phase::Loading);
+    let drawing = load_drawing(file_path)?;
+    progress.check()?;
+    let elements = split_drawing(&drawing, &friendly_file_name, options)?;
+    progress.check()?;
+    if elements.is_empty() {
+        bail!("{friendly_file_name} has nothing to split into elements");
+    }
+    warn_unused_metadata(file_path);
+
+    let mut stats = ConversionStats::default();
+    let element_count = elements.len();
+    //names that only differ in case or in characters replaced for the file name, like A/B
+    //and A_B, would overwrite each other's file
+    let mut stems = HashSet::new();
+    progress.phase(Phase::Writing);
+    for element in elements {
+        progress.check()?;
+        let stem = unique_name(&mut stems, &file_stem(&element.name));
+        let out_path = options.element_path(file_path, &stem);
+        let definition = updated_element(element.definition, &out_path, options)?;
+        let out_file = file_writer::create_file(false, options.info, file_path, &out_path)?;
+        XMLElement::from(&definition)
+            .write(&out_file)
+            .context(format!("Failed to write output file for {}.", element.name))?;
+
+        write_text_log(
+            file_path,
+            &out_path.with_extension("log"),
+            &definition.description,
+            &element.stats,
+        )?;
+        stats.add(&element.stats);
+    }
+    stats.elapsed_ms = now.elapsed().as_millis();
+
+    Ok(ConversionResult {
+        success: true,
+        message: format!(
+            "Successfully converted {friendly_file_name} into {element_count} elements"
+        ),
+        stats: Some(stats),
+        xml_content: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::entities::{EntityType, Line};
+    use dxf::Point;
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Entity {
+        Entity::new(EntityType::Line(Line::new(
+            Point::new(x1, y1, 0.0),
+            Point::new(x2, y2, 0.0),
+        )))
+    }
+
+    #[test]
+    fn cluster_groups_entities_closer_than_the_gap() {
+        let entities = [
+            line(20.0, 0.0, 30.0, 0.0),
+            line(0.0, 0.0, 10.0, 0.0),
+            //4 away from the first one, chained to it by the second
+            line(34.0, 0.0, 40.0, 0.0),
+            line(0.0, -20.0, 10.0, -20.0),
+        ];
+        let objects = |gap| {
+            let objects = entities
+                .iter()
+                .map(|ent| match &ent.specific {
+                    EntityType::Line(l) => (ent, Objects::Line(crate::qelmt::Line::from(l))),
+                    _ => unreachable!(),
+                })
+                .collect();
+            cluster(objects, gap)
+                .into_iter()
+                .map(|group| group.iter().map(|(_, obj)| obj.left_bound()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        //sorted top to bottom, then left to right
+        assert_eq!(objects(5.0), vec![vec![0.0], vec![20.0, 34.0], vec![0.0]]);
+        assert_eq!(objects(10.0), vec![vec![20.0, 0.0, 34.0], vec![0.0]]);
+        assert_eq!(objects(25.0).len(), 1);
+    }
+
+    #[test]
+    fn unique_name_ignores_case() {
+        let mut names = HashSet::new();
+        assert_eq!(unique_name(&mut names, "A_B"), "A_B");
+        assert_eq!(unique_name(&mut names, "a_b"), "a_b_2");
+        assert_eq!(unique_name(&mut names, "A_B"), "A_B_3");
+    }
+
+    #[test]
+    fn split_files_never_overwrite_each_other() -> Result<()> {
+        let mut drw = Drawing::new();
+        for name in ["A/B", "a_b"] {
+            drw.add_block(Block {
+                name: name.to_string(),
+                entities: vec![line(0.0, 0.0, 10.0, 0.0)],
+                ..Block::default()
+            });
+        }
+        let dir = tempfile::tempdir()?;
+        let file_path = dir.path().join("library.dxf");
+        drw.save_file(&file_path)?;
+
+        let options = ConversionOptions {
+            split: SplitMode::Blocks,
+            ..ConversionOptions::default()
+        };
+        convert_split(&file_path, &options)?;
+        let mut written: Vec<String> = std::fs::read_dir(dir.path())?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "elmt"))
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_lowercase()))
+            .collect();
+        written.sort();
+        assert_eq!(written, ["library-a_b.elmt", "library-a_b_2.elmt"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,589 @@
+use crate::diagnostics::Bounds;
+use crate::qelmt::{
+    two_dec, Arc, Definition, Description, DynamicText, Ellipse, FontInfo, FontStyle, Line,
+    LineEnd, Objects, Placement, Polygon, ScaleEntity, TermOrient, Terminal, Text,
+};
+use crate::{friendly_name, load_drawing, ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{Context, Result};
+use dxf::Drawing;
+use hex_color::HexColor;
+use simple_xml_builder::XMLElement;
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+
+//length of the line QET draws for a terminal
+const TERMINAL_LENGTH: f64 = 4.0;
+
+//room left around the bounding box so the strokes on its edges aren't cut
+const MARGIN: f64 = 5.0;
+
+/// What gets drawn besides the primitives of the element
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgOptions {
+    /// Dashed outline of the bounding box QET gives the element
+    pub bounding_box: bool,
+    /// Cross on the hotspot, the origin of the element coordinates
+    pub hotspot: bool,
+    /// Terminals as short lines pointing where the conductor comes from
+    pub terminals: bool,
+    /// Size of a pixel of the element in the SVG
+    pub zoom: f64,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            bounding_box: true,
+            hotspot: true,
+            terminals: true,
+            zoom: 1.0,
+        }
+    }
+}
+
+//the parts of a QET style string that matter for drawing,
+//e.g. "line-style:dashed;line-weight:thin;filling:none;color:black"
+struct Style {
+    stroke: String,
+    stroke_width: f64,
+    dash: Option<&'static str>,
+    fill: String,
+}
+
+impl Style {
+    fn parse(style: &str) -> Self {
+        let mut parsed = Style {
+            stroke: "black".into(),
+            stroke_width: 1.0,
+            dash: None,
+            fill: "none".into(),
+        };
+        for (key, value) in style
+            .split(';')
+            .filter_map(|prop| prop.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+        {
+            match key {
+                "color" => parsed.stroke = svg_color(value).unwrap_or("black").into(),
+                //hatches have no simple SVG equivalent, they are left empty
+                "filling" => parsed.fill = svg_color(value).unwrap_or("none").into(),
+                "line-weight" => match value {
+                    "none" => parsed.stroke = "none".into(),
+                    //a cosmetic pen in QET, one screen pixel whatever the zoom
+                    "thin" => parsed.stroke_width = 0.5,
+                    "hight" => parsed.stroke_width = 2.0,
+                    "eleve" => parsed.stroke_width = 5.0,
+                    _ => parsed.stroke_width = 1.0,
+                },
+                "line-style" => {
+                    parsed.dash = match value {
+                        "dashed" => Some("4,2"),
+                        "dotted" => Some("1,2"),
+                        "dashdotted" => Some("4,2,1,2"),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    //open shapes and line ends pass the fill they need instead of the one of the style
+    fn apply(&self, xml: &mut XMLElement, fill: Option<&str>) {
+        xml.add_attribute("stroke", &self.stroke);
+        xml.add_attribute("stroke-width", self.stroke_width);
+        if let Some(dash) = self.dash {
+            xml.add_attribute("stroke-dasharray", dash);
+        }
+        xml.add_attribute("fill", fill.unwrap_or(&self.fill));
+    }
+}
+
+//the QET color names SVG understands as is, "none" and anything else gives None
+fn svg_color(name: &str) -> Option<&str> {
+    match name {
+        "white" | "black" | "green" | "red" | "blue" | "gray" | "brown" | "yellow" | "cyan"
+        | "magenta" | "lightgray" | "orange" | "purple" => Some(name),
+        _ if name.starts_with('#') => Some(name),
+        _ => None,
+    }
+}
+
+fn arc_xml(arc: &Arc) -> XMLElement {
+    let (rx, ry) = (arc.width / 2.0, arc.height / 2.0);
+    let (cx, cy) = (arc.x + rx, arc.y + ry);
+    let style = Style::parse(&arc.style);
+
+    if arc.angle.abs() >= 360.0 {
+        let mut ell_xml = XMLElement::new("ellipse");
+        ell_xml.add_attribute("cx", two_dec(cx));
+        ell_xml.add_attribute("cy", two_dec(cy));
+        ell_xml.add_attribute("rx", two_dec(rx));
+        ell_xml.add_attribute("ry", two_dec(ry));
+        style.apply(&mut ell_xml, None);
+        return ell_xml;
+    }
+
+    //angles go counter clockwise from 3 o'clock on screen, with y pointing down
+    let point = |deg: f64| {
+        let rad = deg.to_radians();
+        (two_dec(cx + rx * rad.cos()), two_dec(cy - ry * rad.sin()))
+    };
+    let (x1, y1) = point(arc.start);
+    let (x2, y2) = point(arc.start + arc.angle);
+    let large_arc = i32::from(arc.angle.abs() > 180.0);
+    let sweep = i32::from(arc.angle < 0.0);
+
+    let mut arc_xml = XMLElement::new("path");
+    arc_xml.add_attribute(
+        "d",
+        format!(
+            "M {x1} {y1} A {} {} 0 {large_arc} {sweep} {x2} {y2}",
+            two_dec(rx),
+            two_dec(ry)
+        ),
+    );
+    style.apply(&mut arc_xml, Some("none"));
+    arc_xml
+}
+
+fn ellipse_xml(ell: &Ellipse) -> XMLElement {
+    let mut ell_xml = XMLElement::new("ellipse");
+    ell_xml.add_attribute("cx", two_dec(ell.x + ell.width / 2.0));
+    ell_xml.add_attribute("cy", two_dec(ell.y + ell.height / 2.0));
+    ell_xml.add_attribute("rx", two_dec(ell.width / 2.0));
+    ell_xml.add_attribute("ry", two_dec(ell.height / 2.0));
+    Style::parse(&ell.style).apply(&mut ell_xml, None);
+    ell_xml
+}
+
+fn polygon_xml(poly: &Polygon) -> XMLElement {
+    let mut poly_xml = XMLElement::new(if poly.closed { "polygon" } else { "polyline" });
+    poly_xml.add_attribute(
+        "points",
+        poly.coordinates
+            .iter()
+            .map(|coord| format!("{},{}", two_dec(coord.x), two_dec(coord.y)))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    Style::parse(&poly.style).apply(&mut poly_xml, (!poly.closed).then_some("none"));
+    poly_xml
+}
+
+//the shape drawn at the `tip` end of a line, `dir` is the unit vector going into the line
+fn line_end_xml(end: LineEnd, tip: (f64, f64), dir: (f64, f64), length: f64, style: &Style) -> Option<XMLElement> {
+    let at = |along: f64, across: f64| {
+        (
+            two_dec(tip.0 + dir.0 * along - dir.1 * across),
+            two_dec(tip.1 + dir.1 * along + dir.0 * across),
+        )
+    };
+    let points = |pts: &[(f64, f64)]| {
+        pts.iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let (name, pts, filled) = match end {
+        LineEnd::None => return None,
+        LineEnd::Circle => {
+            let (cx, cy) = at(length / 2.0, 0.0);
+            let mut circle = XMLElement::new("circle");
+            circle.add_attribute("cx", cx);
+            circle.add_attribute("cy", cy);
+            circle.add_attribute("r", two_dec(length / 2.0));
+            style.apply(&mut circle, Some(style.stroke.as_str()));
+            return Some(circle);
+        }
+        LineEnd::SimpleArrow => (
+            "polyline",
+            vec![at(length, length / 2.0), at(0.0, 0.0), at(length, -length / 2.0)],
+            false,
+        ),
+        LineEnd::TriangleArrow => (
+            "polygon",
+            vec![at(length, length / 2.0), at(0.0, 0.0), at(length, -length / 2.0)],
+            true,
+        ),
+        LineEnd::Diamond => (
+            "polygon",
+            vec![
+                at(0.0, 0.0),
+                at(length / 2.0, length / 2.0),
+                at(length, 0.0),
+                at(length / 2.0, -length / 2.0),
+            ],
+            true,
+        ),
+    };
+    let mut end_xml = XMLElement::new(name);
+    end_xml.add_attribute("points", points(&pts));
+    style.apply(&mut end_xml, Some(if filled { style.stroke.as_str() } else { "none" }));
+    Some(end_xml)
+}
+
+fn line_xml(line: &Line) -> XMLElement {
+    let style = Style::parse(&line.style);
+    let mut line_xml = XMLElement::new("line");
+    line_xml.add_attribute("x1", two_dec(line.x1));
+    line_xml.add_attribute("y1", two_dec(line.y1));
+    line_xml.add_attribute("x2", two_dec(line.x2));
+    line_xml.add_attribute("y2", two_dec(line.y2));
+    style.apply(&mut line_xml, Some("none"));
+
+    let (dx, dy) = (line.x2 - line.x1, line.y2 - line.y1);
+    let len = dx.hypot(dy);
+    if len < f64::EPSILON || (line.end1 == LineEnd::None && line.end2 == LineEnd::None) {
+        return line_xml;
+    }
+
+    let mut group = XMLElement::new("g");
+    group.add_child(line_xml);
+    let dir = (dx / len, dy / len);
+    let ends = [
+        line_end_xml(line.end1, (line.x1, line.y1), dir, line.length1, &style),
+        line_end_xml(line.end2, (line.x2, line.y2), (-dir.0, -dir.1), line.length2, &style),
+    ];
+    for end in ends.into_iter().flatten() {
+        group.add_child(end);
+    }
+    group
+}
+
+//a text with its baseline starting at x, y, one tspan per line
+fn text_lines_xml(value: &str, (x, y): (f64, f64), font: &FontInfo, color: HexColor) -> XMLElement {
+    let mut txt_xml = XMLElement::new("text");
+    txt_xml.add_attribute("x", two_dec(x));
+    txt_xml.add_attribute("y", two_dec(y));
+    txt_xml.add_attribute("font-family", &font.family);
+    //QET lays texts out at 96 dpi, the same as the pt unit of SVG
+    txt_xml.add_attribute("font-size", format!("{}pt", font.point_size.round()));
+    //Qt5 weights go up to 99 with bold at 75, Qt6 ones up to 1000 with bold at 700
+    if (63..100).contains(&font.weight) || font.weight >= 600 {
+        txt_xml.add_attribute("font-weight", "bold");
+    }
+    if font.style != FontStyle::Normal {
+        txt_xml.add_attribute("font-style", "italic");
+    }
+    if font.underline {
+        txt_xml.add_attribute("text-decoration", "underline");
+    }
+    txt_xml.add_attribute("fill", color.display_rgb());
+
+    for (i, line) in value.lines().enumerate() {
+        let mut tspan = XMLElement::new("tspan");
+        tspan.add_attribute("x", two_dec(x));
+        if i > 0 {
+            tspan.add_attribute("dy", "1.2em");
+        }
+        tspan.add_text(line);
+        txt_xml.add_child(tspan);
+    }
+    txt_xml
+}
+
+fn rotated(mut xml: XMLElement, rotation: f64, (x, y): (f64, f64)) -> XMLElement {
+    if rotation.abs() > f64::EPSILON {
+        xml.add_attribute(
+            "transform",
+            format!("rotate({} {} {})", two_dec(rotation), two_dec(x), two_dec(y)),
+        );
+    }
+    xml
+}
+
+fn text_xml(txt: &Text) -> XMLElement {
+    rotated(
+        text_lines_xml(&txt.value, (txt.x, txt.y), &txt.font, txt.color),
+        txt.rotation,
+        (txt.x, txt.y),
+    )
+}
+
+fn dynamic_text_xml(dtxt: &DynamicText) -> XMLElement {
+    //QET places the text item by its top left corner, the baseline is inside it at the
+    //offsets from QET_ElementScaler
+    let pt_size = dtxt.font.point_size;
+    let (item_x, item_y) = dtxt.item_pos();
+    let baseline = (
+        item_x + (pt_size / 8.0) + 4.05 - 0.5,
+        item_y + (7.0 / 5.0 * pt_size + 26.0 / 5.0) - 0.5,
+    );
+    rotated(
+        text_lines_xml(&dtxt.text, baseline, &dtxt.font, dtxt.color),
+        dtxt.rotation,
+        (item_x, item_y),
+    )
+}
+
+fn terminal_xml(term: &Terminal) -> XMLElement {
+    let (dx, dy) = match term.orientation {
+        TermOrient::North => (0.0, -TERMINAL_LENGTH),
+        TermOrient::East => (TERMINAL_LENGTH, 0.0),
+        TermOrient::South => (0.0, TERMINAL_LENGTH),
+        TermOrient::West => (-TERMINAL_LENGTH, 0.0),
+    };
+    let mut group = XMLElement::new("g");
+    group.add_attribute("stroke", "red");
+    group.add_attribute("stroke-width", 0.5);
+
+    let mut line = XMLElement::new("line");
+    line.add_attribute("x1", two_dec(term.x));
+    line.add_attribute("y1", two_dec(term.y));
+    line.add_attribute("x2", two_dec(term.x + dx));
+    line.add_attribute("y2", two_dec(term.y + dy));
+    group.add_child(line);
+
+    //where the conductor gets connected
+    let mut point = XMLElement::new("circle");
+    point.add_attribute("cx", two_dec(term.x + dx));
+    point.add_attribute("cy", two_dec(term.y + dy));
+    point.add_attribute("r", 1);
+    point.add_attribute("fill", "none");
+    group.add_child(point);
+    group
+}
+
+/// The primitives of `description` as an SVG group, in element coordinates: pixels with
+/// the hotspot at the origin and y pointing down, like in QET
+#[must_use]
+pub fn description_svg(description: &Description, options: &SvgOptions) -> XMLElement {
+    let mut group = XMLElement::new("g");
+    for obj in &description.objects {
+        for obj in std::iter::once(obj).chain(obj.descendants()) {
+            let xml = match obj {
+                Objects::Arc(arc) => arc_xml(arc),
+                Objects::Ellipse(ell) => ellipse_xml(ell),
+                Objects::Polygon(poly) => polygon_xml(poly),
+                Objects::DynamicText(dtxt) => dynamic_text_xml(dtxt),
+                Objects::Text(txt) => text_xml(txt),
+                Objects::Line(line) => line_xml(line),
+                Objects::Terminal(term) if options.terminals => terminal_xml(term),
+                Objects::Terminal(_) | Objects::Group(_) => continue,
+            };
+            group.add_child(xml);
+        }
+    }
+    group
+}
+
+/// `viewBox` of [`element_svg`] as x, y, width, height in element coordinates
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn element_view_box(definition: &Definition) -> (f64, f64, f64, f64) {
+    let (width, height) = definition.size();
+    let (hotspot_x, hotspot_y) = definition.hotspot();
+    (
+        -hotspot_x as f64 - MARGIN,
+        -hotspot_y as f64 - MARGIN,
+        width as f64 + 2.0 * MARGIN,
+        height as f64 + 2.0 * MARGIN,
+    )
+}
+
+/// A standalone SVG document of the element, the view covers its bounding box
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn element_svg(definition: &Definition, options: &SvgOptions) -> XMLElement {
+    let (width, height) = definition.size();
+    let (hotspot_x, hotspot_y) = definition.hotspot();
+    let (left, top) = (-hotspot_x as f64, -hotspot_y as f64);
+    let (view_x, view_y, view_w, view_h) = element_view_box(definition);
+
+    let mut svg = XMLElement::new("svg");
+    svg.add_attribute("xmlns", "http://www.w3.org/2000/svg");
+    svg.add_attribute("width", two_dec(view_w * options.zoom));
+    svg.add_attribute("height", two_dec(view_h * options.zoom));
+    svg.add_attribute("viewBox", format!("{view_x} {view_y} {view_w} {view_h}"));
+
+    if options.bounding_box {
+        let mut rect = XMLElement::new("rect");
+        rect.add_attribute("x", left);
+        rect.add_attribute("y", top);
+        rect.add_attribute("width", width);
+        rect.add_attribute("height", height);
+        rect.add_attribute("stroke", "gray");
+        rect.add_attribute("stroke-width", 0.5);
+        rect.add_attribute("stroke-dasharray", "2,2");
+        rect.add_attribute("fill", "none");
+        svg.add_child(rect);
+    }
+
+    svg.add_child(description_svg(&definition.description, options));
+
+    if options.hotspot {
+        let mut cross = XMLElement::new("path");
+        cross.add_attribute("d", "M -3 0 H 3 M 0 -3 V 3");
+        cross.add_attribute("stroke", "blue");
+        cross.add_attribute("stroke-width", 0.5);
+        svg.add_child(cross);
+    }
+    svg
+}
+
+/// A transparent SVG with the same view as [`element_svg`] that only outlines `bounds`, to lay
+/// over it and point out an object. Points and flat objects get a small box around them.
+#[must_use]
+pub fn highlight_svg(view_box: (f64, f64, f64, f64), bounds: Bounds) -> XMLElement {
+    let (view_x, view_y, view_w, view_h) = view_box;
+    let (left, top, right, bot) = bounds;
+    let pad = 2.0;
+
+    let mut svg = XMLElement::new("svg");
+    svg.add_attribute("xmlns", "http://www.w3.org/2000/svg");
+    svg.add_attribute("viewBox", format!("{view_x} {view_y} {view_w} {view_h}"));
+    let mut rect = XMLElement::new("rect");
+    rect.add_attribute("x", two_dec(left.min(right) - pad));
+    rect.add_attribute("y", two_dec(top.min(bot) - pad));
+    rect.add_attribute("width", two_dec((right - left).abs() + 2.0 * pad));
+    rect.add_attribute("height", two_dec((bot - top).abs() + 2.0 * pad));
+    rect.add_attribute("fill", "rgba(245, 158, 11, 0.15)");
+    rect.add_attribute("stroke", "#f59e0b");
+    rect.add_attribute("stroke-width", 2);
+    rect.add_attribute("vector-effect", "non-scaling-stroke");
+    svg.add_child(rect);
+    svg
+}
+
+//every entity of the drawing in drawing units, whatever the layer filters say
+fn source_description(drw: &Drawing, options: &ConversionOptions) -> Result<Description> {
+    let mut options = options.clone();
+    options.include_layers.clear();
+    options.exclude_layers.clear();
+    options.skip_hidden_layers = false;
+    (drw, &options).try_into()
+}
+
+//drawing units can be anything from microns to miles, strokes are kept a pixel wide
+fn style_xml(css: &str) -> XMLElement {
+    let mut style = XMLElement::new("style");
+    style.add_text(css);
+    style
+}
+
+/// The drawing before it's scaled, in drawing units with y pointing down and with every
+/// layer, to compare with the element it's converted into. The SVG fills whatever it's
+/// placed in.
+///
+/// # Errors
+///
+/// Returns an error if one of the block patterns of `options` isn't a valid glob.
+pub fn drawing_svg(drw: &Drawing, options: &ConversionOptions) -> Result<XMLElement> {
+    let description = source_description(drw, options)?;
+    let (left, top) = (description.left_bound(), description.top_bound());
+    let width = (description.right_bound() - left).max(f64::EPSILON);
+    let height = (description.bot_bound() - top).max(f64::EPSILON);
+    let margin = width.max(height) * 0.02;
+
+    let mut svg = XMLElement::new("svg");
+    svg.add_attribute("xmlns", "http://www.w3.org/2000/svg");
+    svg.add_attribute(
+        "viewBox",
+        format!(
+            "{} {} {} {}",
+            left - margin,
+            top - margin,
+            width + 2.0 * margin,
+            height + 2.0 * margin
+        ),
+    );
+    svg.add_child(style_xml("* { vector-effect: non-scaling-stroke; }"));
+    svg.add_child(description_svg(
+        &description,
+        &SvgOptions {
+            terminals: false,
+            ..SvgOptions::default()
+        },
+    ));
+    Ok(svg)
+}
+
+/// [`element_svg`] with the drawing on top, scaled and moved the way the conversion placed it, to
+/// spot what the conversion moved, dropped or distorted
+///
+/// # Errors
+///
+/// Returns an error if one of the block patterns of `options` isn't a valid glob.
+pub fn overlay_svg(
+    definition: &Definition,
+    drw: &Drawing,
+    options: &ConversionOptions,
+    svg_options: &SvgOptions,
+) -> Result<XMLElement> {
+    let mut svg = element_svg(definition, svg_options);
+    svg.add_child(style_xml(
+        ".source * { stroke: #e11d48; fill: none; vector-effect: non-scaling-stroke; } \
+         .source text, .source tspan { fill: #e11d48; stroke: none; }",
+    ));
+
+    //the placement the element was built with, the y flip is already done by the description
+    let Placement { factor, shift, .. } = definition.placement();
+    let mut source = description_svg(
+        &source_description(drw, options)?,
+        &SvgOptions {
+            terminals: false,
+            ..*svg_options
+        },
+    );
+    source.add_attribute("class", "source");
+    source.add_attribute("opacity", 0.5);
+    source.add_attribute(
+        "transform",
+        format!("translate({} {}) scale({factor})", shift.0, shift.1),
+    );
+    svg.add_child(source);
+    Ok(svg)
+}
+
+/// Renders an .elmt file, or a .dxf file converted with `options`, into an .svg file next
+/// to it or into the output directory
+///
+/// # Errors
+///
+/// Returns an error if the input can't be read or the SVG can't be written.
+pub fn export_svg(
+    file_path: &Path,
+    options: &ConversionOptions,
+    svg_options: &SvgOptions,
+) -> Result<ConversionResult> {
+    let now = Instant::now();
+    let friendly_file_name = friendly_name(file_path);
+
+    let is_elmt = file_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("elmt"));
+    let (definition, mut stats) = if is_elmt {
+        (Definition::read_file(file_path)?, ConversionStats::default())
+    } else {
+        let drawing = load_drawing(file_path)?;
+        (
+            Definition::new(friendly_file_name.clone(), options, &drawing)?,
+            ConversionStats::from_drawing(&drawing),
+        )
+    };
+
+    let out_path = options.output_path(file_path).with_extension("svg");
+    if let Some(dir) = out_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).context("Could not create output directory")?;
+    }
+    let out_file =
+        File::create(&out_path).context(format!("Could not create {}", out_path.display()))?;
+    element_svg(&definition, svg_options)
+        .write(out_file)
+        .context(format!("Failed to write {}", out_path.display()))?;
+    stats.elapsed_ms = now.elapsed().as_millis();
+
+    Ok(ConversionResult {
+        success: true,
+        message: format!(
+            "Successfully rendered {friendly_file_name} to {}",
+            out_path.display()
+        ),
+        stats: Some(stats),
+        xml_content: None,
+    })
+}
@@ -0,0 +1,602 @@
+use crate::qelmt::dynamictext::normalize_mtext;
+use crate::qelmt::{Definition, EntityRules, HAlignment};
+use crate::{friendly_name, load_drawing, ConversionOptions, ConversionResult, ConversionStats};
+use anyhow::{bail, Context, Result};
+use dxf::entities::{Entity, EntityType};
+use dxf::enums::{HorizontalTextJustification, VerticalTextJustification};
+use dxf::{Drawing, Point};
+use simple_xml_builder::XMLElement;
+use std::path::Path;
+use std::time::Instant;
+
+//lines closer to horizontal or vertical than this (relative to their length) are part of the grid
+const AXIS_TOLERANCE: f64 = 0.01;
+
+//lines closer than this (relative to the size of the frame) are the same grid line
+const GRID_TOLERANCE: f64 = 0.002;
+
+//a line segment of the frame, either horizontal (fixed y) or vertical (fixed x)
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    //the fixed coordinate
+    at: f64,
+    from: f64,
+    to: f64,
+}
+
+/// A text found in the frame, in drawing units
+#[derive(Debug, Clone)]
+struct FrameText {
+    x: f64,
+    y: f64,
+    height: f64,
+    value: String,
+    //tag of the attribute, the cell gets a variable instead of a fixed text
+    tag: Option<String>,
+    align: HAlignment,
+}
+
+/// A cell of the template, spanning one or more rows and columns of the grid
+#[derive(Debug)]
+pub struct Cell {
+    pub row: usize,
+    pub col: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
+    /// Fixed text of the cell, shown as the label when the cell also has a variable
+    pub label: Option<String>,
+    /// Value of the cell, a `%{variable}` for the attributes
+    pub value: Option<String>,
+    pub align: HAlignment,
+    pub font_size: i64,
+}
+
+/// A QET title block template, a grid of cells with fixed texts and variables
+#[derive(Debug)]
+pub struct TitleBlockTemplate {
+    pub name: String,
+    /// Height of every row, in pixels
+    pub rows: Vec<i64>,
+    /// Width of every column, as a percentage of the width of the title block
+    pub cols: Vec<f64>,
+    pub cells: Vec<Cell>,
+}
+
+/// Variable QET fills in for the attribute `tag`, using the names of QET's own title block
+/// variables for the usual tags
+#[must_use]
+pub fn variable_for(tag: &str) -> String {
+    let tag = tag.trim().to_lowercase().replace(['_', ' '], "-");
+    match tag.as_str() {
+        "drawn-by" | "drawn" | "designer" => "author".into(),
+        "file" | "file-name" => "filename".into(),
+        "sheet" | "page" => "folio".into(),
+        "sheets" | "total-sheets" | "pages" => "folio-total".into(),
+        "installation" => "plant".into(),
+        "location" | "loc" => "locmach".into(),
+        "rev" | "revision" => "indexrev".into(),
+        _ => tag,
+    }
+}
+
+//position of a text, which depends on how it's justified
+fn text_point(
+    location: &Point,
+    second: &Point,
+    h: HorizontalTextJustification,
+    v: VerticalTextJustification,
+) -> (f64, f64) {
+    if h == HorizontalTextJustification::Left && v == VerticalTextJustification::Baseline {
+        (location.x, location.y)
+    } else {
+        (second.x, second.y)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameContent {
+    horizontal: Vec<Segment>,
+    vertical: Vec<Segment>,
+    texts: Vec<FrameText>,
+}
+
+impl FrameContent {
+    fn add_segment(&mut self, (x1, y1): (f64, f64), (x2, y2): (f64, f64)) {
+        let len = (x2 - x1).hypot(y2 - y1);
+        if len <= f64::EPSILON {
+            return;
+        }
+        if (y2 - y1).abs() <= len * AXIS_TOLERANCE {
+            self.horizontal.push(Segment {
+                at: (y1 + y2) / 2.0,
+                from: x1.min(x2),
+                to: x1.max(x2),
+            });
+        } else if (x2 - x1).abs() <= len * AXIS_TOLERANCE {
+            self.vertical.push(Segment {
+                at: (x1 + x2) / 2.0,
+                from: y1.min(y2),
+                to: y1.max(y2),
+            });
+        }
+    }
+
+    fn add_points(&mut self, points: &[(f64, f64)], closed: bool) {
+        for pair in points.windows(2) {
+            self.add_segment(pair[0], pair[1]);
+        }
+        if closed && points.len() > 2 {
+            self.add_segment(points[points.len() - 1], points[0]);
+        }
+    }
+
+    fn add_text(
+        &mut self,
+        (x, y): (f64, f64),
+        height: f64,
+        value: String,
+        tag: Option<String>,
+        align: HAlignment,
+    ) {
+        self.texts.push(FrameText {
+            x,
+            y,
+            height,
+            value,
+            tag,
+            align,
+        });
+    }
+
+    //the entities of blocks are added moved and scaled like the insert, rotated inserts
+    //aren't supported, title frames are drawn straight
+    fn add_entity(&mut self, drw: &Drawing, rules: &EntityRules, ent: &Entity, at: (f64, f64, f64, f64)) {
+        if !rules.allows(ent) {
+            return;
+        }
+        let (dx, dy, sx, sy) = at;
+        let tr = |x: f64, y: f64| (dx + x * sx, dy + y * sy);
+
+        match &ent.specific {
+            EntityType::Line(line) => {
+                self.add_segment(tr(line.p1.x, line.p1.y), tr(line.p2.x, line.p2.y));
+            }
+            EntityType::LwPolyline(poly) => {
+                let points: Vec<_> = poly.vertices.iter().map(|v| tr(v.x, v.y)).collect();
+                self.add_points(&points, poly.is_closed());
+            }
+            EntityType::Polyline(poly) => {
+                let points: Vec<_> = poly
+                    .vertices()
+                    .map(|v| tr(v.location.x, v.location.y))
+                    .collect();
+                self.add_points(&points, poly.is_closed());
+            }
+            EntityType::Text(txt) => {
+                let (x, y) = text_point(
+                    &txt.location,
+                    &txt.second_alignment_point,
+                    txt.horizontal_text_justification,
+                    txt.vertical_text_justification,
+                );
+                self.add_text(
+                    tr(x, y),
+                    txt.text_height * sy.abs(),
+                    normalize_mtext(&txt.value),
+                    None,
+                    txt.horizontal_text_justification.into(),
+                );
+            }
+            EntityType::MText(mtxt) => {
+                let mut raw = mtxt.extended_text.join("");
+                raw.push_str(&mtxt.text);
+                self.add_text(
+                    tr(mtxt.insertion_point.x, mtxt.insertion_point.y),
+                    mtxt.initial_text_height * sy.abs(),
+                    normalize_mtext(&raw),
+                    None,
+                    mtxt.attachment_point.into(),
+                );
+            }
+            EntityType::AttributeDefinition(attdef) => {
+                let (x, y) = text_point(
+                    &attdef.location,
+                    &attdef.second_alignment_point,
+                    attdef.horizontal_text_justification,
+                    attdef.vertical_text_justification,
+                );
+                self.add_text(
+                    tr(x, y),
+                    attdef.text_height * sy.abs(),
+                    attdef.prompt.clone(),
+                    Some(attdef.text_tag.clone()),
+                    attdef.horizontal_text_justification.into(),
+                );
+            }
+            EntityType::Insert(ins) => {
+                let Some(block) = drw.blocks().find(|bl| bl.name == ins.name) else {
+                    return;
+                };
+                let block_at = (
+                    dx + (ins.location.x - block.base_point.x * ins.x_scale_factor) * sx,
+                    dy + (ins.location.y - block.base_point.y * ins.y_scale_factor) * sy,
+                    sx * ins.x_scale_factor,
+                    sy * ins.y_scale_factor,
+                );
+                for ent in &block.entities {
+                    //the attributes of the insert replace the definitions of the block
+                    if matches!(ent.specific, EntityType::AttributeDefinition(_))
+                        && ins.attributes().next().is_some()
+                    {
+                        continue;
+                    }
+                    self.add_entity(drw, rules, ent, block_at);
+                }
+                for attr in ins.attributes() {
+                    let (x, y) = text_point(
+                        &attr.location,
+                        &attr.second_alignment_point,
+                        attr.horizontal_text_justification,
+                        attr.vertical_text_justification,
+                    );
+                    self.add_text(
+                        tr(x, y),
+                        attr.text_height * sy.abs(),
+                        String::new(),
+                        Some(attr.attribute_tag.clone()),
+                        attr.horizontal_text_justification.into(),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+//sorted positions of the grid lines, merging the ones closer than `tol`
+fn grid_lines(segments: &[Segment], tol: f64) -> Vec<f64> {
+    let mut at: Vec<f64> = segments.iter().map(|s| s.at).collect();
+    at.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mut lines: Vec<f64> = Vec::new();
+    for pos in at {
+        match lines.last() {
+            Some(last) if pos - last <= tol => {}
+            _ => lines.push(pos),
+        }
+    }
+    lines
+}
+
+//whether a segment at `at` crosses `mid`, the middle of a cell edge
+fn separated(segments: &[Segment], at: f64, mid: f64, tol: f64) -> bool {
+    segments
+        .iter()
+        .any(|s| (s.at - at).abs() <= tol && s.from - tol <= mid && mid <= s.to + tol)
+}
+
+impl TitleBlockTemplate {
+    /// Builds the template from the lines and texts of the drawing. The outer lines give
+    /// the size of the title block, the inner ones the rows and columns, and cells that
+    /// aren't separated by a line are merged. Attribute definitions become `%{tag}` variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the drawing doesn't have enough lines to make a grid.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::too_many_lines
+    )]
+    pub fn from_drawing(name: impl Into<String>, drw: &Drawing, options: &ConversionOptions) -> Result<Self> {
+        let rules = EntityRules::new(options, drw)?;
+        let mut content = FrameContent::default();
+        for ent in drw.entities() {
+            content.add_entity(drw, &rules, ent, (0.0, 0.0, 1.0, 1.0));
+        }
+
+        let extent = content
+            .horizontal
+            .iter()
+            .map(|s| s.to - s.from)
+            .chain(content.vertical.iter().map(|s| s.to - s.from))
+            .fold(0.0, f64::max);
+        let tol = (extent * GRID_TOLERANCE).max(f64::EPSILON);
+
+        let xs = grid_lines(&content.vertical, tol);
+        //rows go from the top of the frame down
+        let mut ys = grid_lines(&content.horizontal, tol);
+        ys.reverse();
+        if xs.len() < 2 || ys.len() < 2 {
+            bail!("The drawing needs horizontal and vertical lines forming a frame");
+        }
+
+        let (n_rows, n_cols) = (ys.len() - 1, xs.len() - 1);
+        let width = xs[n_cols] - xs[0];
+        let scale = Definition::unit_scale(drw, options.px_per_mm);
+
+        //cell spans, walking the grid from the top left and growing every unvisited cell
+        //right and down for as long as no line separates it from its neighbours
+        let mut visited = vec![vec![false; n_cols]; n_rows];
+        let mut cells = Vec::new();
+        for row in 0..n_rows {
+            for col in 0..n_cols {
+                if visited[row][col] {
+                    continue;
+                }
+
+                let row_mid = |r: usize| (ys[r] + ys[r + 1]) / 2.0;
+                let col_mid = |c: usize| (xs[c] + xs[c + 1]) / 2.0;
+
+                let mut colspan = 1;
+                while col + colspan < n_cols
+                    && !visited[row][col + colspan]
+                    && !separated(&content.vertical, xs[col + colspan], row_mid(row), tol)
+                {
+                    colspan += 1;
+                }
+                let mut rowspan = 1;
+                while row + rowspan < n_rows
+                    && (col..col + colspan).all(|c| {
+                        !visited[row + rowspan][c]
+                            && !separated(&content.horizontal, ys[row + rowspan], col_mid(c), tol)
+                    })
+                    && (col + 1..col + colspan).all(|c| {
+                        !separated(&content.vertical, xs[c], row_mid(row + rowspan), tol)
+                    })
+                {
+                    rowspan += 1;
+                }
+                for visited_row in &mut visited[row..row + rowspan] {
+                    visited_row[col..col + colspan].fill(true);
+                }
+
+                let (left, right) = (xs[col], xs[col + colspan]);
+                let (top, bottom) = (ys[row], ys[row + rowspan]);
+                let mut texts: Vec<&FrameText> = content
+                    .texts
+                    .iter()
+                    .filter(|t| left <= t.x && t.x <= right && bottom <= t.y && t.y <= top)
+                    .collect();
+                //reading order, top to bottom then left to right
+                texts.sort_by(|a, b| {
+                    (-a.y, a.x)
+                        .partial_cmp(&(-b.y, b.x))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+
+                let label: Vec<&str> = texts
+                    .iter()
+                    .filter(|t| t.tag.is_none() && !t.value.trim().is_empty())
+                    .map(|t| t.value.trim())
+                    .collect();
+                let value = texts
+                    .iter()
+                    .find_map(|t| t.tag.as_deref())
+                    .map(|tag| format!("%{{{}}}", variable_for(tag)));
+                let font_size = texts
+                    .iter()
+                    .map(|t| t.height)
+                    .fold(0.0, f64::max);
+
+                cells.push(Cell {
+                    row,
+                    col,
+                    rowspan,
+                    colspan,
+                    label: (!label.is_empty()).then(|| label.join(" ")),
+                    value,
+                    align: texts.first().map_or(HAlignment::Left, |t| t.align),
+                    font_size: ((font_size * scale).round() as i64).max(6),
+                });
+            }
+        }
+
+        Ok(Self {
+            name: name.into(),
+            rows: ys
+                .windows(2)
+                .map(|w| (((w[0] - w[1]) * scale).round() as i64).max(1))
+                .collect(),
+            cols: xs
+                .windows(2)
+                .map(|w| (w[1] - w[0]) / width * 100.0)
+                .collect(),
+            cells,
+        })
+    }
+}
+
+impl From<&TitleBlockTemplate> for XMLElement {
+    fn from(tb: &TitleBlockTemplate) -> Self {
+        fn translated(tag: &str, text: &str) -> XMLElement {
+            let mut xml = XMLElement::new(tag);
+            let mut tr = XMLElement::new("translation");
+            tr.add_attribute("lang", "en");
+            tr.add_text(text);
+            xml.add_child(tr);
+            xml
+        }
+
+        let mut tb_xml = XMLElement::new("titleblocktemplate");
+        tb_xml.add_attribute("name", &tb.name);
+
+        let mut info = XMLElement::new("information");
+        info.add_text("Created using dxf2elmt!");
+        tb_xml.add_child(info);
+        tb_xml.add_child(XMLElement::new("logos"));
+
+        //the last column takes whatever is left, so rounding never leaves a gap
+        let last = tb.cols.len().saturating_sub(1);
+        let cols: Vec<String> = tb
+            .cols
+            .iter()
+            .enumerate()
+            .map(|(i, pct)| {
+                if i == last {
+                    "r100%".to_string()
+                } else {
+                    format!("t{}%", pct.round())
+                }
+            })
+            .collect();
+        let rows: Vec<String> = tb.rows.iter().map(ToString::to_string).collect();
+
+        let mut grid = XMLElement::new("grid");
+        grid.add_attribute("rows", rows.join(";"));
+        grid.add_attribute("cols", cols.join(";"));
+        for cell in &tb.cells {
+            let mut cell_xml = if cell.label.is_none() && cell.value.is_none() {
+                XMLElement::new("empty")
+            } else {
+                let mut field = XMLElement::new("field");
+                field.add_attribute("name", "");
+                match (&cell.label, &cell.value) {
+                    (Some(label), Some(value)) => {
+                        field.add_attribute("displaylabel", "true");
+                        field.add_child(translated("label", label));
+                        field.add_child(translated("value", value));
+                    }
+                    (Some(text), None) | (None, Some(text)) => {
+                        field.add_attribute("displaylabel", "false");
+                        field.add_child(translated("value", text));
+                    }
+                    (None, None) => {}
+                }
+                field.add_attribute(
+                    "align",
+                    match cell.align {
+                        HAlignment::Left => "left",
+                        HAlignment::Center => "center",
+                        HAlignment::Right => "right",
+                    },
+                );
+                field.add_attribute("valign", "center");
+                field.add_attribute("fontsize", cell.font_size);
+                field.add_attribute("hadjust", "true");
+                field
+            };
+            cell_xml.add_attribute("row", cell.row);
+            cell_xml.add_attribute("col", cell.col);
+            if cell.rowspan > 1 {
+                cell_xml.add_attribute("rowspan", cell.rowspan - 1);
+            }
+            if cell.colspan > 1 {
+                cell_xml.add_attribute("colspan", cell.colspan - 1);
+            }
+            grid.add_child(cell_xml);
+        }
+        tb_xml.add_child(grid);
+        tb_xml
+    }
+}
+
+/// Converts the frame in `file_path` into a .titleblock template, written where the .elmt
+/// file would go.
+///
+/// # Errors
+///
+/// Returns an error if the drawing can't be loaded, has no frame, or the file can't be written.
+pub fn convert_titleblock(file_path: &Path, options: &ConversionOptions) -> Result<ConversionResult> {
+    let now = Instant::now();
+    let friendly_file_name = friendly_name(file_path);
+
+    let drawing = load_drawing(file_path)?;
+    let template = TitleBlockTemplate::from_drawing(friendly_file_name.clone(), &drawing, options)
+        .context(format!("Failed to find a title block in {friendly_file_name}"))?;
+
+    let out_path = options.output_path(file_path).with_extension("titleblock");
+    if let Some(dir) = out_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).context("Could not create output directory")?;
+    }
+    let out_file = std::fs::File::create(&out_path)
+        .context(format!("Could not create {}", out_path.display()))?;
+    XMLElement::from(&template)
+        .write(&out_file)
+        .context("Failed to write output file.")?;
+
+    let mut stats = ConversionStats::from_drawing(&drawing);
+    stats.elapsed_ms = now.elapsed().as_millis();
+
+    Ok(ConversionResult {
+        success: true,
+        message: format!(
+            "Successfully converted {friendly_file_name} into a title block of {} cells",
+            template.cells.len()
+        ),
+        stats: Some(stats),
+        xml_content: None,
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use dxf::entities::{AttributeDefinition, Line, Text};
+    use dxf::enums::Units;
+
+    fn line(drw: &mut Drawing, x1: f64, y1: f64, x2: f64, y2: f64) {
+        drw.add_entity(Entity::new(EntityType::Line(Line::new(
+            Point::new(x1, y1, 0.0),
+            Point::new(x2, y2, 0.0),
+        ))));
+    }
+
+    //a 100 x 20 mm frame with a full width title row, the row below split at 60 mm
+    fn frame() -> Drawing {
+        let mut drw = Drawing::new();
+        drw.header.default_drawing_units = Units::Millimeters;
+        line(&mut drw, 0.0, 0.0, 100.0, 0.0);
+        line(&mut drw, 0.0, 20.0, 100.0, 20.0);
+        line(&mut drw, 0.0, 0.0, 0.0, 20.0);
+        line(&mut drw, 100.0, 0.0, 100.0, 20.0);
+        line(&mut drw, 0.0, 10.0, 100.0, 10.0);
+        line(&mut drw, 60.0, 0.0, 60.0, 10.0);
+        drw
+    }
+
+    #[test]
+    fn from_drawing_builds_the_grid_and_merges_unseparated_cells() -> Result<()> {
+        let mut drw = frame();
+        drw.add_entity(Entity::new(EntityType::Text(Text {
+            location: Point::new(5.0, 15.0, 0.0),
+            text_height: 5.0,
+            value: "Title".into(),
+            ..Text::default()
+        })));
+        drw.add_entity(Entity::new(EntityType::AttributeDefinition(AttributeDefinition {
+            location: Point::new(5.0, 5.0, 0.0),
+            text_height: 2.0,
+            text_tag: "DRAWN_BY".into(),
+            ..AttributeDefinition::default()
+        })));
+
+        let tb = TitleBlockTemplate::from_drawing("frame", &drw, &ConversionOptions::default())?;
+        //2 px/mm by default
+        assert_eq!(tb.rows, vec![20, 20]);
+        assert_eq!(tb.cols, vec![60.0, 40.0]);
+        let spans: Vec<_> = tb
+            .cells
+            .iter()
+            .map(|cell| (cell.row, cell.col, cell.rowspan, cell.colspan))
+            .collect();
+        assert_eq!(spans, vec![(0, 0, 1, 2), (1, 0, 1, 1), (1, 1, 1, 1)]);
+
+        assert_eq!(tb.cells[0].label.as_deref(), Some("Title"));
+        assert_eq!(tb.cells[0].value, None);
+        assert_eq!(tb.cells[0].font_size, 10);
+        assert_eq!(tb.cells[1].label, None);
+        assert_eq!(tb.cells[1].value.as_deref(), Some("%{author}"));
+        //texts smaller than 6 px are made readable
+        assert_eq!(tb.cells[1].font_size, 6);
+        assert_eq!((tb.cells[2].label.as_ref(), tb.cells[2].value.as_ref()), (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn from_drawing_needs_a_frame() {
+        let mut drw = Drawing::new();
+        line(&mut drw, 0.0, 0.0, 100.0, 0.0);
+        line(&mut drw, 0.0, 20.0, 100.0, 20.0);
+        assert!(TitleBlockTemplate::from_drawing("lines", &drw, &ConversionOptions::default()).is_err());
+    }
+}
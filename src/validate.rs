@@ -0,0 +1,387 @@
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Only in the log of a conversion, validation never reports it
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A structural problem found in an .elmt file
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Where the problem is, e.g. `definition/description/line[3]`
+    pub location: String,
+    /// Line in the .elmt file
+    pub line: u32,
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: line {}: {}: {}",
+            self.severity, self.line, self.location, self.message
+        )
+    }
+}
+
+struct Validator<'a> {
+    doc: &'a Document<'a>,
+    issues: Vec<Issue>,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, severity: Severity, node: Node, location: &str, message: String) {
+        self.issues.push(Issue {
+            severity,
+            location: location.to_string(),
+            line: self.doc.text_pos_at(node.range().start).row,
+            message,
+        });
+    }
+
+    //checks a numeric attribute, returning its value if it is there and valid
+    fn number(&mut self, node: Node, location: &str, attr: &str, required: bool) -> Option<f64> {
+        let Some(value) = node.attribute(attr) else {
+            if required {
+                self.push(
+                    Severity::Error,
+                    node,
+                    location,
+                    format!("missing attribute {attr}"),
+                );
+            }
+            return None;
+        };
+
+        match value.trim().parse::<f64>() {
+            Ok(v) if v.is_finite() => Some(v),
+            Ok(_) => {
+                self.push(
+                    Severity::Error,
+                    node,
+                    location,
+                    format!("{attr} is not a finite number: {value}"),
+                );
+                None
+            }
+            Err(_) => {
+                self.push(
+                    Severity::Error,
+                    node,
+                    location,
+                    format!("{attr} is not a number: {value}"),
+                );
+                None
+            }
+        }
+    }
+
+    fn uuid(&mut self, node: Node, location: &str, seen: &mut HashMap<Uuid, String>) {
+        let Some(value) = node.attribute("uuid") else {
+            self.push(Severity::Error, node, location, "missing uuid".into());
+            return;
+        };
+
+        match Uuid::parse_str(value.trim_matches(['{', '}'])) {
+            Ok(uuid) => {
+                if let Some(first) = seen.insert(uuid, location.to_string()) {
+                    self.push(
+                        Severity::Error,
+                        node,
+                        location,
+                        format!("uuid {value} is already used by {first}"),
+                    );
+                }
+            }
+            Err(_) => self.push(
+                Severity::Error,
+                node,
+                location,
+                format!("invalid uuid: {value}"),
+            ),
+        }
+    }
+
+    fn definition(&mut self, root: Node) {
+        let loc = "definition";
+        if root.attribute("type") != Some("element") {
+            self.push(
+                Severity::Error,
+                root,
+                loc,
+                "type must be \"element\"".into(),
+            );
+        }
+        for attr in ["width", "height"] {
+            if let Some(v) = self.number(root, loc, attr, true) {
+                if v <= 0.0 {
+                    self.push(Severity::Error, root, loc, format!("{attr} must be > 0"));
+                }
+            }
+        }
+        for attr in ["hotspot_x", "hotspot_y"] {
+            self.number(root, loc, attr, true);
+        }
+
+        let mut seen = HashMap::new();
+        match root.children().find(|n| n.has_tag_name("uuid")) {
+            Some(uuid) => self.uuid(uuid, "definition/uuid", &mut seen),
+            None => self.push(Severity::Error, root, loc, "missing uuid".into()),
+        }
+
+        let has_name = root
+            .children()
+            .find(|n| n.has_tag_name("names"))
+            .is_some_and(|names| names.children().any(|n| n.has_tag_name("name")));
+        if !has_name {
+            self.push(Severity::Warning, root, loc, "element has no name".into());
+        }
+
+        let Some(description) = root.children().find(|n| n.has_tag_name("description")) else {
+            self.push(Severity::Error, root, loc, "missing description".into());
+            return;
+        };
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let mut primitives = 0;
+        for prim in description.children().filter(Node::is_element) {
+            let tag = prim.tag_name().name();
+            let count = counts.entry(tag).or_default();
+            *count += 1;
+            primitives += 1;
+            let location = format!("definition/description/{tag}[{count}]");
+            self.primitive(prim, &location, &mut seen);
+        }
+        if primitives == 0 {
+            self.push(
+                Severity::Warning,
+                description,
+                "definition/description",
+                "element has no primitives".into(),
+            );
+        }
+    }
+
+    fn primitive(&mut self, prim: Node, loc: &str, seen: &mut HashMap<Uuid, String>) {
+        match prim.tag_name().name() {
+            "line" => {
+                let pts: Vec<_> = ["x1", "y1", "x2", "y2"]
+                    .into_iter()
+                    .map(|a| self.number(prim, loc, a, true))
+                    .collect();
+                if let [Some(x1), Some(y1), Some(x2), Some(y2)] = pts[..] {
+                    if (x1 - x2).abs() < f64::EPSILON && (y1 - y2).abs() < f64::EPSILON {
+                        self.push(Severity::Warning, prim, loc, "line has zero length".into());
+                    }
+                }
+                self.number(prim, loc, "length1", false);
+                self.number(prim, loc, "length2", false);
+            }
+            tag @ ("ellipse" | "arc" | "rect" | "circle") => {
+                self.number(prim, loc, "x", true);
+                self.number(prim, loc, "y", true);
+                let sizes: &[&str] = if tag == "circle" {
+                    &["diameter"]
+                } else {
+                    &["width", "height"]
+                };
+                for &attr in sizes {
+                    if let Some(v) = self.number(prim, loc, attr, true) {
+                        if v <= 0.0 {
+                            self.push(
+                                Severity::Error,
+                                prim,
+                                loc,
+                                format!("{tag} has zero size, {attr} must be > 0"),
+                            );
+                        }
+                    }
+                }
+                if tag == "arc" {
+                    self.number(prim, loc, "start", true);
+                    self.number(prim, loc, "angle", true);
+                }
+            }
+            "polygon" => {
+                let mut points = 0;
+                while prim.attribute(format!("x{}", points + 1).as_str()).is_some() {
+                    points += 1;
+                    self.number(prim, loc, &format!("x{points}"), true);
+                    self.number(prim, loc, &format!("y{points}"), true);
+                }
+                if points < 2 {
+                    self.push(
+                        Severity::Error,
+                        prim,
+                        loc,
+                        format!("polygon needs at least 2 points, has {points}"),
+                    );
+                }
+            }
+            "text" => {
+                self.number(prim, loc, "x", true);
+                self.number(prim, loc, "y", true);
+                self.number(prim, loc, "rotation", false);
+            }
+            "dynamic_text" => {
+                self.number(prim, loc, "x", true);
+                self.number(prim, loc, "y", true);
+                self.number(prim, loc, "z", false);
+                self.number(prim, loc, "rotation", false);
+                self.number(prim, loc, "text_width", false);
+                self.uuid(prim, loc, seen);
+            }
+            "terminal" => {
+                self.number(prim, loc, "x", true);
+                self.number(prim, loc, "y", true);
+                match prim.attribute("orientation") {
+                    Some("n" | "e" | "s" | "w") => {}
+                    Some(o) => self.push(
+                        Severity::Error,
+                        prim,
+                        loc,
+                        format!("invalid terminal orientation: {o}"),
+                    ),
+                    None => self.push(
+                        Severity::Error,
+                        prim,
+                        loc,
+                        "missing attribute orientation".into(),
+                    ),
+                }
+                self.uuid(prim, loc, seen);
+            }
+            "input" => {
+                self.number(prim, loc, "x", true);
+                self.number(prim, loc, "y", true);
+            }
+            other => self.push(
+                Severity::Warning,
+                prim,
+                loc,
+                format!("unknown primitive {other}"),
+            ),
+        }
+    }
+}
+
+/// Checks the contents of an .elmt file for structural problems, like a missing uuid,
+/// zero size bounds or coordinates that aren't finite numbers.
+///
+/// # Errors
+///
+/// Returns an error if `xml` isn't well formed XML.
+pub fn validate_elmt(xml: &str) -> Result<Vec<Issue>> {
+    let doc = Document::parse(xml).context("Not a valid XML document")?;
+    let root = doc.root_element();
+
+    let mut validator = Validator {
+        doc: &doc,
+        issues: Vec::new(),
+    };
+    if root.has_tag_name("definition") {
+        validator.definition(root);
+    } else {
+        validator.push(
+            Severity::Error,
+            root,
+            root.tag_name().name(),
+            "root element must be <definition>".into(),
+        );
+    }
+
+    Ok(validator.issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(description: &str) -> String {
+        format!(
+            r#"<definition type="element" width="20" height="20" hotspot_x="10" hotspot_y="10">
+<uuid uuid="{{8a4ff1ba-1bca-4a3c-a24c-0ee0d8e1e9a1}}"/>
+<names><name lang="en">test</name></names>
+<description>{description}</description>
+</definition>"#
+        )
+    }
+
+    fn messages(description: &str) -> Vec<String> {
+        validate_elmt(&element(description))
+            .unwrap()
+            .into_iter()
+            .map(|issue| format!("{}: {}: {}", issue.severity, issue.location, issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn a_valid_element_has_no_issues() {
+        let description = r#"<line x1="0" y1="0" x2="10" y2="0"/>
+<circle x="-5" y="-5" diameter="10"/>
+<terminal x="0" y="-10" orientation="n" uuid="{0b6e0c46-7f5b-4b4c-9a57-6a2f0f0f4b11}"/>"#;
+        assert_eq!(messages(description), Vec::<String>::new());
+    }
+
+    #[test]
+    fn circles_need_a_position_and_a_diameter() {
+        assert_eq!(
+            messages(r#"<circle x="0" y="0" diameter="0"/><circle x="0" diameter="4"/>"#),
+            [
+                "error: definition/description/circle[1]: circle has zero size, diameter must be > 0",
+                "error: definition/description/circle[2]: missing attribute y",
+            ]
+        );
+        assert_eq!(
+            messages(r#"<circle x="0" y="0" width="4" height="4"/>"#),
+            ["error: definition/description/circle[1]: missing attribute diameter"]
+        );
+    }
+
+    #[test]
+    fn reports_bad_values_and_reused_uuids() {
+        let description = r#"<line x1="0" y1="0" x2="0" y2="0"/>
+<rect x="0" y="NaN" width="4" height="4"/>
+<terminal x="0" y="0" orientation="up" uuid="{8a4ff1ba-1bca-4a3c-a24c-0ee0d8e1e9a1}"/>
+<triangle/>"#;
+        assert_eq!(
+            messages(description),
+            [
+                "warning: definition/description/line[1]: line has zero length",
+                "error: definition/description/rect[1]: y is not a finite number: NaN",
+                "error: definition/description/terminal[1]: invalid terminal orientation: up",
+                "error: definition/description/terminal[1]: uuid {8a4ff1ba-1bca-4a3c-a24c-0ee0d8e1e9a1} is already used by definition/uuid",
+                "warning: definition/description/triangle[1]: unknown primitive triangle",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_root_must_be_a_definition() {
+        let issues = validate_elmt("<element/>").unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert!(validate_elmt("<definition>").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::batch::build_globset;
+use anyhow::{Context, Result};
+use globset::GlobSet;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, SystemTime};
+use tracing::error;
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Also watch the sub directories of the given directories
+    pub recursive: bool,
+    /// Quiet time after the last write before a file is reported, so a burst of saves
+    /// from the CAD program only triggers a single conversion
+    pub debounce: Duration,
+    /// Glob patterns a file changed in a watched directory must match, empty means all .dxf files
+    pub include: Vec<String>,
+    /// Glob patterns excluding files changed in a watched directory
+    pub exclude: Vec<String>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            debounce: Duration::from_millis(500),
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+enum Target {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// Watches files and directories for changed .dxf files.
+///
+/// Dropping the watcher stops watching.
+pub struct DxfWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+    rx: Receiver<DebounceEventResult>,
+    targets: Vec<Target>,
+    recursive: bool,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    //notify also reports plain reads, which includes us reading the file to convert it.
+    //Keep the last seen modification time so only real changes are reported
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl DxfWatcher {
+    /// Like in batch mode, files given explicitly are always watched and the include/exclude
+    /// globs only apply to the files of watched directories, relative to that directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob pattern is invalid, or one of the paths doesn't exist or
+    /// can't be watched.
+    pub fn new(paths: &[PathBuf], options: &WatchOptions) -> Result<Self> {
+        let include = build_globset(&options.include)?;
+        let exclude = build_globset(&options.exclude)?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut debouncer =
+            new_debouncer(options.debounce, tx).context("Failed to create the file watcher")?;
+
+        let mut targets = Vec::with_capacity(paths.len());
+        for path in paths {
+            //notify reports absolute paths, so canonicalize to be able to compare them
+            let path = path
+                .canonicalize()
+                .context(format!("Can't watch {}", path.display()))?;
+
+            if path.is_dir() {
+                let mode = if options.recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                debouncer.watcher().watch(&path, mode)?;
+                targets.push(Target::Dir(path));
+            } else {
+                //a lot of programs save by writing a temp file and renaming it over the original
+                //which drops a watch on the file itself, so watch the parent directory instead
+                let parent = path.parent().unwrap_or(Path::new("."));
+                debouncer
+                    .watcher()
+                    .watch(parent, RecursiveMode::NonRecursive)?;
+                targets.push(Target::File(path));
+            }
+        }
+
+        Ok(Self {
+            _debouncer: debouncer,
+            rx,
+            targets,
+            recursive: options.recursive,
+            include,
+            exclude,
+            modified: HashMap::new(),
+        })
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        let is_dxf = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dxf"));
+        if !is_dxf || !path.is_file() {
+            return false;
+        }
+
+        self.targets.iter().any(|target| match target {
+            Target::File(file) => file == path,
+            Target::Dir(dir) if self.recursive => path.starts_with(dir) && self.is_included(dir, path),
+            Target::Dir(dir) => path.parent() == Some(dir.as_path()) && self.is_included(dir, path),
+        })
+    }
+
+    fn is_included(&self, dir: &Path, path: &Path) -> bool {
+        let rel = path.strip_prefix(dir).unwrap_or(path);
+        !self.include.as_ref().is_some_and(|gs| !gs.is_match(rel))
+            && !self.exclude.as_ref().is_some_and(|gs| gs.is_match(rel))
+    }
+
+    fn was_modified(&mut self, path: &Path) -> bool {
+        let Ok(mtime) = path.metadata().and_then(|md| md.modified()) else {
+            return false;
+        };
+
+        self.modified.insert(path.to_path_buf(), mtime) != Some(mtime)
+    }
+
+    /// Waits up to `timeout` for the next set of changed .dxf files.
+    ///
+    /// Returns an empty list if nothing changed in time, and `None` once the watcher stopped.
+    #[must_use]
+    pub fn changed_files(&mut self, timeout: Duration) -> Option<Vec<PathBuf>> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(Ok(events)) => {
+                let mut files: Vec<PathBuf> = events
+                    .into_iter()
+                    .map(|ev| ev.path)
+                    .filter(|p| self.is_watched(p))
+                    .collect();
+                files.sort();
+                files.dedup();
+                files.retain(|p| self.was_modified(p));
+                Some(files)
+            }
+            Ok(Err(e)) => {
+                error!("Error watching files: {e}");
+                Some(Vec::new())
+            }
+            Err(RecvTimeoutError::Timeout) => Some(Vec::new()),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_changes_are_filtered_by_the_globs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        for name in ["motor.dxf", "motor_old.dxf", "relay.dxf"] {
+            std::fs::write(root.join(name), "")?;
+        }
+        let options = WatchOptions {
+            include: vec!["motor*".into()],
+            exclude: vec!["*_old.dxf".into()],
+            ..WatchOptions::default()
+        };
+        let watcher = DxfWatcher::new(&[root.clone(), root.join("relay.dxf")], &options)?;
+        assert!(watcher.is_watched(&root.join("motor.dxf")));
+        assert!(!watcher.is_watched(&root.join("motor_old.dxf")));
+        //given explicitly, so the globs don't apply to it
+        assert!(watcher.is_watched(&root.join("relay.dxf")));
+        Ok(())
+    }
+}